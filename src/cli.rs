@@ -0,0 +1,211 @@
+use std::io::Error;
+
+use async_sqlite::{rusqlite::OpenFlags, PoolBuilder};
+
+use crate::{
+    configurator::{
+        build::{build_plan, Plan},
+        diff::PlanDiff,
+        parser::Configuration,
+    },
+    db::{config_versions::ConfigVersions, events::Events, years::Years},
+};
+
+const USAGE: &str = "Usage: sportsday-scoreboard-v2 [COMMAND]
+
+Commands:
+  serve                 Start the server (default)
+  check-config [PATH]   Validate a config file (default ./config.yaml)
+  plan [PATH]           Print the plan for a config file and what applying it would
+                        change in the database at $DB_URL, without touching it
+  help                  Print this message";
+
+/// A subcommand given on the command line
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Serve,
+    CheckConfig { path: String },
+    Plan { path: String },
+    Help,
+}
+
+impl Command {
+    /// Parse the arguments after the binary name
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let path = || {
+            args.get(1)
+                .cloned()
+                .unwrap_or_else(|| "./config.yaml".to_string())
+        };
+        match args.first().map(String::as_str) {
+            None | Some("serve") => Ok(Command::Serve),
+            Some("check-config") => Ok(Command::CheckConfig { path: path() }),
+            Some("plan") => Ok(Command::Plan { path: path() }),
+            Some("help") | Some("--help") | Some("-h") => Ok(Command::Help),
+            Some(other) => Err(format!("Unknown command \"{other}\"\n\n{USAGE}")),
+        }
+    }
+}
+
+/// Run a command that doesn't start the server, returning the process exit code
+pub async fn run(command: Command) -> Result<i32, Error> {
+    match command {
+        Command::Serve => unreachable!("serve is handled by main"),
+        Command::Help => {
+            println!("{USAGE}");
+            Ok(0)
+        }
+        Command::CheckConfig { path } => {
+            let Some(config) = load_config(&path) else {
+                return Ok(1);
+            };
+            println!(
+                "{path} is valid (version {}, {} years, {} forms, {} events)",
+                config.get_version(),
+                config.years.len(),
+                config.forms.len(),
+                config.events.len()
+            );
+            Ok(0)
+        }
+        Command::Plan { path } => {
+            let Some(config) = load_config(&path) else {
+                return Ok(1);
+            };
+            let plan = build_plan(config.clone()).map_err(Error::other)?;
+            print!("{}", format_plan(&plan));
+
+            let db_url = std::env::var("DB_URL").unwrap_or_else(|_| "./db.sqlite".to_string());
+            if !std::fs::exists(&db_url)? {
+                println!("\nNo database at {db_url}, every year and event would be created");
+                return Ok(0);
+            }
+            let pool = PoolBuilder::new()
+                .path(&db_url)
+                .flags(OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX)
+                .open()
+                .await
+                .map_err(Error::other)?;
+
+            println!();
+            match ConfigVersions::latest(&pool).await.ok().flatten() {
+                Some(applied) if applied.version == config.get_version() => println!(
+                    "Version {} is already applied to {db_url}, change the version to apply this plan",
+                    applied.version
+                ),
+                Some(applied) => println!(
+                    "{db_url} is on version {}, this plan would apply version {}",
+                    applied.version,
+                    config.get_version()
+                ),
+                None => println!("{db_url} has no applied config version"),
+            }
+
+            let years = Years::all(&pool).await.map_err(Error::other)?;
+            let events = Events::all(&pool).await.map_err(Error::other)?;
+            let diff = PlanDiff::between(&plan, &years, &events);
+            if diff.is_empty() {
+                println!("Database already matches the plan");
+            } else {
+                println!("Changes against {db_url}:");
+                for change in diff.changes.iter() {
+                    println!("  {change}");
+                }
+            }
+            Ok(0)
+        }
+    }
+}
+
+fn load_config(path: &str) -> Option<Configuration> {
+    let config = match std::fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|yaml| Configuration::from_yaml_str(&yaml).map_err(|e| e.to_string()))
+    {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Error loading config: {}", e);
+            return None;
+        }
+    };
+    if let Err(errors) = config.validate() {
+        eprintln!("Error validating config: {}", errors);
+        return None;
+    }
+    Some(config)
+}
+
+/// Render a plan as an indented list of years and their generated events
+pub fn format_plan(plan: &Plan) -> String {
+    let mut out = String::new();
+    for year in plan.year_plans.iter() {
+        out.push_str(&format!(
+            "{} ({}) - {} events\n",
+            year.name,
+            year.id,
+            year.events.len()
+        ));
+        for event in year.events.iter() {
+            out.push_str(&format!("  {} ({})", event.id, event.name));
+            if event.timeslot.start.is_some() {
+                out.push_str(&format!(" {}", event.timeslot));
+            }
+            out.push('\n');
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::configurator::build::{EventPlan, YearPlan};
+
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_commands() {
+        assert_eq!(Command::from_args(&args(&[])), Ok(Command::Serve));
+        assert_eq!(Command::from_args(&args(&["serve"])), Ok(Command::Serve));
+        assert_eq!(
+            Command::from_args(&args(&["check-config"])),
+            Ok(Command::CheckConfig {
+                path: "./config.yaml".to_string()
+            })
+        );
+        assert_eq!(
+            Command::from_args(&args(&["plan", "next.yaml"])),
+            Ok(Command::Plan {
+                path: "next.yaml".to_string()
+            })
+        );
+        assert!(Command::from_args(&args(&["deploy"])).is_err());
+    }
+
+    #[test]
+    fn formats_plan() {
+        let plan = Plan {
+            year_plans: vec![YearPlan {
+                id: "y9".to_string(),
+                name: "Year 9".to_string(),
+                events: vec![EventPlan {
+                    id: "y9-boys-60m".to_string(),
+                    name: "60m".to_string(),
+                    gender_id: "boys".to_string(),
+                    filter_key: "60m".to_string(),
+                    scoring: None,
+                    multiplier: 1,
+                    timeslot: Default::default(),
+                    heat: None,
+                }],
+            }],
+        };
+        assert_eq!(
+            format_plan(&plan),
+            "Year 9 (y9) - 1 events\n  y9-boys-60m (60m)\n"
+        );
+    }
+}
@@ -1,17 +1,16 @@
-use crate::configurator::parser::Configuration;
+use serde::Serialize;
 
-pub fn build_plan(configuration: Configuration) -> Plan {
+use crate::configurator::{
+    parser::{Configuration, Heat, Timeslot},
+    validate::ValidationErrors,
+};
+
+/// Build the plan of years and events for a configuration, refusing if it is invalid
+pub fn build_plan(configuration: Configuration) -> Result<Plan, ValidationErrors> {
+    configuration.validate()?;
     let mut plan = Plan { year_plans: vec![] };
     let config = &configuration;
 
-    let mut empty_scores = serde_json::json!({});
-
-    for form in config.forms.iter() {
-        empty_scores[form.id.clone()] = 0.into();
-    }
-
-    let empty_scores = empty_scores.to_string();
-
     for year in config.years.iter() {
         let year_id = year.id.clone();
         let year_name = year.name.clone();
@@ -28,27 +27,52 @@ pub fn build_plan(configuration: Configuration) -> Plan {
             }
             for gender in config.genders.iter() {
                 if configuration.is_event_applicable_to_gender(event, gender) {
+                    let id = format!("{}-{}-{}", year_plan.clone().id, gender, event.clone().id);
+                    let mut name = event.clone().name;
+                    let timeslot = configuration.timeslot_for(event, &year.id, gender);
+                    if let Some(rounds) = &event.rounds {
+                        // Heats only decide who runs in the final, so their points count for nothing
+                        for number in 1..=rounds.heats {
+                            year_plan.events.push(EventPlan {
+                                id: format!("{id}-heat-{number}"),
+                                name: format!("{name} heat {number}"),
+                                gender_id: gender.clone(),
+                                filter_key: event.clone().id,
+                                scoring: event.scoring.clone(),
+                                multiplier: 0,
+                                timeslot: rounds.heat_timeslot(&timeslot, number),
+                                heat: Some(Heat {
+                                    final_id: id.clone(),
+                                    number,
+                                }),
+                            });
+                        }
+                        name = format!("{name} final");
+                    }
                     year_plan.events.push(EventPlan {
-                        id: format!("{}-{}-{}", year_plan.clone().id, gender, event.clone().id),
-                        name: event.clone().name,
+                        id,
+                        name,
                         gender_id: gender.clone(),
                         filter_key: event.clone().id,
-                        scores: empty_scores.clone(),
+                        scoring: event.scoring.clone(),
+                        multiplier: event.multiplier,
+                        timeslot,
+                        heat: None,
                     })
                 }
             }
         }
         plan.year_plans.push(year_plan);
     }
-    plan
+    Ok(plan)
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct Plan {
     pub year_plans: Vec<YearPlan>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 
 pub struct YearPlan {
     pub id: String,
@@ -56,12 +80,127 @@ pub struct YearPlan {
     pub events: Vec<EventPlan>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 
 pub struct EventPlan {
     pub id: String,
     pub name: String,
     pub gender_id: String,
     pub filter_key: String,
-    pub scores: String,
+    pub scoring: Option<String>,
+    pub multiplier: i64,
+    pub timeslot: Timeslot,
+    /// Which heat this is, for the heats of an event run in rounds
+    pub heat: Option<Heat>,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{configurator::parser::Rounds, test_harness::config};
+
+    use super::*;
+
+    #[test]
+    fn schedule_slots_override_the_event_timeslot() {
+        let mut config = config();
+        config.events[0].schedule = Some(
+            serde_yml::from_str(
+                r#"
+start: "09:30"
+venue: Track
+duration: 10
+slots:
+  - year: y10
+    start: "10:00"
+  - year: y10
+    gender: girls
+    venue: Back straight
+"#,
+            )
+            .unwrap(),
+        );
+        let plan = build_plan(config).unwrap();
+        let timeslot = |id: &str| {
+            plan.year_plans
+                .iter()
+                .flat_map(|year_plan| year_plan.events.iter())
+                .find(|event| event.id == id)
+                .unwrap()
+                .timeslot
+                .to_string()
+        };
+        assert_eq!(timeslot("y9-boys-60m"), "09:30 for 10m at Track");
+        assert_eq!(timeslot("y10-boys-60m"), "10:00 for 10m at Track");
+        assert_eq!(timeslot("y10-girls-60m"), "10:00 for 10m at Back straight");
+        assert_eq!(timeslot("y9-boys-javelin"), "unscheduled");
+    }
+
+    #[test]
+    fn rounds_add_heats_before_the_final() {
+        let mut config = config();
+        config.events[0].rounds = Some(Rounds {
+            heats: 2,
+            qualifiers: 1,
+            heat_offsets: vec![],
+        });
+        let plan = build_plan(config).unwrap();
+        let events: Vec<(&str, &str, i64)> = plan.year_plans[0]
+            .events
+            .iter()
+            .filter(|event| event.filter_key == "60m" && event.gender_id == "boys")
+            .map(|event| (event.id.as_str(), event.name.as_str(), event.multiplier))
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                ("y9-boys-60m-heat-1", "60m heat 1", 0),
+                ("y9-boys-60m-heat-2", "60m heat 2", 0),
+                ("y9-boys-60m", "60m final", 1)
+            ]
+        );
+        let heat = plan.year_plans[0].events[1].heat.clone().unwrap();
+        assert_eq!((heat.final_id.as_str(), heat.number), ("y9-boys-60m", 2));
+    }
+
+    #[test]
+    fn heats_are_timetabled_from_the_final() {
+        let mut config = config();
+        config.events[0].schedule = Some(
+            serde_yml::from_str(
+                r#"
+start: "11:00"
+venue: Track
+duration: 10
+slots:
+  - year: y10
+    start: "13:00"
+"#,
+            )
+            .unwrap(),
+        );
+        config.events[0].rounds = Some(Rounds {
+            heats: 2,
+            qualifiers: 1,
+            heat_offsets: vec![-90, -75],
+        });
+        let plan = build_plan(config).unwrap();
+        let timeslots: Vec<(&str, String)> = plan
+            .year_plans
+            .iter()
+            .flat_map(|year_plan| year_plan.events.iter())
+            .filter(|event| event.filter_key == "60m" && event.gender_id == "girls")
+            .map(|event| (event.id.as_str(), event.timeslot.to_string()))
+            .collect();
+        assert_eq!(
+            timeslots,
+            vec![
+                ("y9-girls-60m-heat-1", "09:30 for 10m at Track".to_string()),
+                ("y9-girls-60m-heat-2", "09:45 for 10m at Track".to_string()),
+                ("y9-girls-60m", "11:00 for 10m at Track".to_string()),
+                ("y10-girls-60m-heat-1", "11:30 for 10m at Track".to_string()),
+                ("y10-girls-60m-heat-2", "11:45 for 10m at Track".to_string()),
+                ("y10-girls-60m", "13:00 for 10m at Track".to_string()),
+            ]
+        );
+    }
 }
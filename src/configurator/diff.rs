@@ -0,0 +1,357 @@
+use std::{collections::HashMap, fmt};
+
+use crate::{
+    configurator::build::{EventPlan, Plan},
+    db::{events::Events, years::Years},
+};
+
+/// A single change needed to bring the database in line with a [`Plan`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    AddYear {
+        id: String,
+        name: String,
+    },
+    RenameYear {
+        id: String,
+        from: String,
+        to: String,
+    },
+    RemoveYear {
+        id: String,
+    },
+    AddEvent {
+        year_id: String,
+        event: EventPlan,
+    },
+    UpdateEvent {
+        year_id: String,
+        from: Box<Events>,
+        to: EventPlan,
+    },
+    RemoveEvent {
+        event: Events,
+    },
+}
+
+/// The ordered set of changes between a [`Plan`] and the live `years`/`events` rows
+#[derive(Debug, Default, PartialEq)]
+pub struct PlanDiff {
+    pub changes: Vec<Change>,
+}
+
+impl PlanDiff {
+    /// Work out what needs to change so the database matches the plan.
+    /// Years are added before events, and events are removed before years.
+    pub fn between(plan: &Plan, years: &[Years], events: &[Events]) -> Self {
+        let mut changes = vec![];
+
+        let existing_years: HashMap<&str, &Years> =
+            years.iter().map(|year| (year.id.as_str(), year)).collect();
+        let existing_events: HashMap<&str, &Events> = events
+            .iter()
+            .map(|event| (event.id.as_str(), event))
+            .collect();
+
+        for year_plan in plan.year_plans.iter() {
+            match existing_years.get(year_plan.id.as_str()) {
+                Some(year) if year.name != year_plan.name => changes.push(Change::RenameYear {
+                    id: year_plan.id.clone(),
+                    from: year.name.clone(),
+                    to: year_plan.name.clone(),
+                }),
+                Some(_) => {}
+                None => changes.push(Change::AddYear {
+                    id: year_plan.id.clone(),
+                    name: year_plan.name.clone(),
+                }),
+            }
+        }
+
+        for year_plan in plan.year_plans.iter() {
+            for event_plan in year_plan.events.iter() {
+                match existing_events.get(event_plan.id.as_str()) {
+                    Some(event)
+                        if event.name != event_plan.name
+                            || event.gender_id != event_plan.gender_id
+                            || event.filter_key != event_plan.filter_key
+                            || event.scoring != event_plan.scoring
+                            || event.multiplier != event_plan.multiplier
+                            || event.timeslot != event_plan.timeslot
+                            || event.heat != event_plan.heat =>
+                    {
+                        changes.push(Change::UpdateEvent {
+                            year_id: year_plan.id.clone(),
+                            from: Box::new((*event).clone()),
+                            to: event_plan.clone(),
+                        })
+                    }
+                    Some(_) => {}
+                    None => changes.push(Change::AddEvent {
+                        year_id: year_plan.id.clone(),
+                        event: event_plan.clone(),
+                    }),
+                }
+            }
+        }
+
+        for event in events.iter() {
+            let planned = plan
+                .year_plans
+                .iter()
+                .flat_map(|year_plan| year_plan.events.iter())
+                .any(|event_plan| event_plan.id == event.id);
+            if !planned {
+                changes.push(Change::RemoveEvent {
+                    event: event.clone(),
+                });
+            }
+        }
+
+        for year in years.iter() {
+            if !plan
+                .year_plans
+                .iter()
+                .any(|year_plan| year_plan.id == year.id)
+            {
+                changes.push(Change::RemoveYear {
+                    id: year.id.clone(),
+                });
+            }
+        }
+
+        Self { changes }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Events that applying the diff would keep in a year the plan doesn't have, which
+    /// nothing could show or score
+    pub fn orphans<'a>(&self, plan: &Plan, events: &'a [Events]) -> Vec<&'a Events> {
+        events
+            .iter()
+            .filter(|event| {
+                !self.changes.iter().any(|change| {
+                    matches!(change, Change::RemoveEvent { event: removed } if removed.id == event.id)
+                })
+            })
+            .filter(|event| {
+                !plan
+                    .year_plans
+                    .iter()
+                    .any(|year_plan| year_plan.id == event.year_id)
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::AddYear { id, name } => write!(f, "+ year {id} ({name})"),
+            Change::RenameYear { id, from, to } => {
+                write!(f, "~ year {id}: \"{from}\" -> \"{to}\"")
+            }
+            Change::RemoveYear { id } => write!(f, "- year {id}"),
+            Change::AddEvent { event, .. } => write!(f, "+ event {} ({})", event.id, event.name),
+            Change::UpdateEvent { from, to, .. } => {
+                write!(
+                    f,
+                    "~ event {}: \"{}\" -> \"{}\"",
+                    from.id, from.name, to.name
+                )?;
+                if from.scoring != to.scoring {
+                    write!(
+                        f,
+                        ", scoring {} -> {}",
+                        from.scoring.as_deref().unwrap_or("default"),
+                        to.scoring.as_deref().unwrap_or("default")
+                    )?;
+                }
+                if from.multiplier != to.multiplier {
+                    write!(f, ", ×{} -> ×{}", from.multiplier, to.multiplier)?;
+                }
+                if from.timeslot != to.timeslot {
+                    write!(f, ", {} -> {}", from.timeslot, to.timeslot)?;
+                }
+                Ok(())
+            }
+            Change::RemoveEvent { event } => {
+                write!(f, "- event {} ({}), scores archived", event.id, event.name)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::configurator::build::YearPlan;
+
+    use super::*;
+
+    fn event_plan(id: &str, name: &str) -> EventPlan {
+        EventPlan {
+            id: id.to_string(),
+            name: name.to_string(),
+            gender_id: "boys".to_string(),
+            filter_key: "test".to_string(),
+            scoring: None,
+            multiplier: 1,
+            timeslot: Default::default(),
+            heat: None,
+        }
+    }
+
+    fn event(id: &str, name: &str) -> Events {
+        Events::new(
+            id.to_string(),
+            name.to_string(),
+            "y9".to_string(),
+            "boys".to_string(),
+            "test".to_string(),
+        )
+    }
+
+    fn plan(events: Vec<EventPlan>) -> Plan {
+        Plan {
+            year_plans: vec![YearPlan {
+                id: "y9".to_string(),
+                name: "Year 9".to_string(),
+                events,
+            }],
+        }
+    }
+
+    #[test]
+    fn empty_when_in_sync() {
+        let diff = PlanDiff::between(
+            &plan(vec![event_plan("y9-boys-test", "Test")]),
+            &[Years::new("y9".to_string(), "Year 9".to_string())],
+            &[event("y9-boys-test", "Test")],
+        );
+        assert!(diff.is_empty());
+    }
+
+    #[test]
+    fn events_kept_in_removed_years_are_orphans() {
+        // The event is still planned, but for a year it was never stored under
+        let mut stored = event("y9-boys-test", "Test");
+        stored.year_id = "y8".to_string();
+        let years = [
+            Years::new("y8".to_string(), "Year 8".to_string()),
+            Years::new("y9".to_string(), "Year 9".to_string()),
+        ];
+        let planned = plan(vec![event_plan("y9-boys-test", "Test")]);
+        let events = [stored];
+        let diff = PlanDiff::between(&planned, &years, &events);
+        assert_eq!(diff.orphans(&planned, &events), vec![&events[0]]);
+
+        let events = [event("y9-boys-gone", "Gone")];
+        let diff = PlanDiff::between(&planned, &years, &events);
+        assert!(diff.orphans(&planned, &events).is_empty());
+    }
+
+    #[test]
+    fn adds_new_years_and_events() {
+        let diff = PlanDiff::between(&plan(vec![event_plan("y9-boys-test", "Test")]), &[], &[]);
+        assert_eq!(
+            diff.changes,
+            vec![
+                Change::AddYear {
+                    id: "y9".to_string(),
+                    name: "Year 9".to_string()
+                },
+                Change::AddEvent {
+                    year_id: "y9".to_string(),
+                    event: event_plan("y9-boys-test", "Test")
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn renames_changed_years_and_events() {
+        let diff = PlanDiff::between(
+            &plan(vec![event_plan("y9-boys-test", "Renamed")]),
+            &[Years::new("y9".to_string(), "Year Nine".to_string())],
+            &[event("y9-boys-test", "Test")],
+        );
+        assert_eq!(
+            diff.changes,
+            vec![
+                Change::RenameYear {
+                    id: "y9".to_string(),
+                    from: "Year Nine".to_string(),
+                    to: "Year 9".to_string()
+                },
+                Change::UpdateEvent {
+                    year_id: "y9".to_string(),
+                    from: Box::new(event("y9-boys-test", "Test")),
+                    to: event_plan("y9-boys-test", "Renamed")
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn updates_events_with_changed_scoring() {
+        let mut planned = event_plan("y9-boys-test", "Test");
+        planned.scoring = Some("top_three".to_string());
+        planned.multiplier = 2;
+        let diff = PlanDiff::between(
+            &plan(vec![planned.clone()]),
+            &[Years::new("y9".to_string(), "Year 9".to_string())],
+            &[event("y9-boys-test", "Test")],
+        );
+        assert_eq!(
+            diff.changes,
+            vec![Change::UpdateEvent {
+                year_id: "y9".to_string(),
+                from: Box::new(event("y9-boys-test", "Test")),
+                to: planned
+            }]
+        );
+        assert_eq!(
+            diff.changes[0].to_string(),
+            "~ event y9-boys-test: \"Test\" -> \"Test\", scoring default -> top_three, ×1 -> ×2"
+        );
+    }
+
+    #[test]
+    fn updates_events_with_changed_timeslots() {
+        let mut planned = event_plan("y9-boys-test", "Test");
+        planned.timeslot.start = Some("09:30".to_string());
+        planned.timeslot.venue = Some("Track".to_string());
+        let diff = PlanDiff::between(
+            &plan(vec![planned]),
+            &[Years::new("y9".to_string(), "Year 9".to_string())],
+            &[event("y9-boys-test", "Test")],
+        );
+        assert_eq!(
+            diff.changes[0].to_string(),
+            "~ event y9-boys-test: \"Test\" -> \"Test\", unscheduled -> 09:30 at Track"
+        );
+    }
+
+    #[test]
+    fn removes_events_and_years_no_longer_planned() {
+        let diff = PlanDiff::between(
+            &Plan { year_plans: vec![] },
+            &[Years::new("y9".to_string(), "Year 9".to_string())],
+            &[event("y9-boys-test", "Test")],
+        );
+        assert_eq!(
+            diff.changes,
+            vec![
+                Change::RemoveEvent {
+                    event: event("y9-boys-test", "Test")
+                },
+                Change::RemoveYear {
+                    id: "y9".to_string()
+                }
+            ]
+        );
+    }
+}
@@ -1,3 +1,6 @@
 pub mod build;
+pub mod diff;
 pub mod parser;
+pub mod reload;
 pub mod run;
+pub mod validate;
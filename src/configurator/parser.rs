@@ -1,14 +1,26 @@
+use std::fmt;
+
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
 /// Main configuration structure containing all years, forms, and events
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Configuration {
     /// Config Version
     pub version: String,
+    /// The day of the sports day as `YYYY-MM-DD`, for calendar feeds
+    #[serde(default)]
+    pub date: Option<String>,
     /// Genders for Events
     pub genders: Vec<String>,
     // The Scoring System
     pub scores: Vec<Score>,
+    /// How forms that tie for a placing are scored
+    #[serde(default)]
+    pub tie_rule: TieRule,
+    /// Named alternatives to `scores` that events can opt into
+    #[serde(default)]
+    pub scoring_schemes: Vec<ScoringScheme>,
     /// All available years in the system
     pub years: Vec<Year>,
     /// All available forms/classes in the system  
@@ -18,7 +30,7 @@ pub struct Configuration {
 }
 
 /// Represents a school year
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
 pub struct Year {
     /// Unique identifier for the year (e.g., "2024", "2025")
     pub id: String,
@@ -27,7 +39,7 @@ pub struct Year {
 }
 
 /// Represents a form/class level
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
 pub struct Form {
     /// Unique identifier (e.g., "year7", "year8", "reception")
     pub id: String,
@@ -48,15 +60,222 @@ pub struct Event {
     pub applicable_years: ApplicabilityRules,
     /// Rules for which gender this event applies to
     pub applicable_genders: ApplicabilityRules,
+    /// How performances are measured, if scorers enter times/distances instead of placings
+    #[serde(default)]
+    pub measurement: Option<Measurement>,
+    /// Id of the scoring scheme to use instead of the global scores
+    #[serde(default)]
+    pub scoring: Option<String>,
+    /// Points earned in this event count this many times towards the totals
+    #[serde(default = "default_multiplier")]
+    pub multiplier: i64,
+    /// When and where the event is run
+    #[serde(default)]
+    pub schedule: Option<Schedule>,
+    /// Heats feeding a final, if the event isn't run in one go
+    #[serde(default)]
+    pub rounds: Option<Rounds>,
+}
+
+fn default_multiplier() -> i64 {
+    1
+}
+
+/// Heats run before an event's final. Forms are split between the heats in config
+/// order, and the best placed in each heat go through to the final.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Rounds {
+    pub heats: i64,
+    /// How many forms go through to the final from each heat
+    pub qualifiers: i64,
+    /// Minutes from the start of the final to the start of each heat, one per heat.
+    /// Heats aren't timetabled without these.
+    #[serde(default)]
+    pub heat_offsets: Vec<i64>,
+}
+
+impl Rounds {
+    /// The heat (1-based) the form at `index` in the config runs in
+    pub fn heat_of(&self, index: usize) -> i64 {
+        index as i64 % self.heats + 1
+    }
+
+    /// When and where a heat (1-based) is run, given the final's timeslot
+    pub fn heat_timeslot(&self, final_timeslot: &Timeslot, heat: i64) -> Timeslot {
+        usize::try_from(heat - 1)
+            .ok()
+            .and_then(|index| self.heat_offsets.get(index))
+            .map_or_else(Timeslot::default, |offset| final_timeslot.moved_by(*offset))
+    }
+}
+
+/// Which heat of which final an event is
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct Heat {
+    pub final_id: String,
+    pub number: i64,
+}
+
+/// An event's timetable. The top-level `start`, `venue` and `duration` apply to every
+/// year and gender the event runs for, and `slots` override them for some of them.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Schedule {
+    #[serde(flatten)]
+    pub timeslot: Timeslot,
+    #[serde(default)]
+    pub slots: Vec<ScheduleSlot>,
+}
+
+/// A timetable override for the instances of an event in a year and/or gender.
+/// Later slots win over earlier ones.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScheduleSlot {
+    /// Year id this slot is for, or every year if missing
+    #[serde(default)]
+    pub year: Option<String>,
+    /// Gender this slot is for, or every gender if missing
+    #[serde(default)]
+    pub gender: Option<String>,
+    #[serde(flatten)]
+    pub timeslot: Timeslot,
+}
+
+/// When and where one instance of an event is run
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Timeslot {
+    /// Start time on the day as `HH:MM`, in the server's local time
+    #[serde(default)]
+    pub start: Option<String>,
+    #[serde(default)]
+    pub venue: Option<String>,
+    /// Length in minutes
+    #[serde(default)]
+    pub duration: Option<i64>,
+}
+
+impl Timeslot {
+    /// This timeslot with any fields `other` sets replaced
+    fn overridden_by(&self, other: &Timeslot) -> Timeslot {
+        Timeslot {
+            start: other.start.clone().or_else(|| self.start.clone()),
+            venue: other.venue.clone().or_else(|| self.venue.clone()),
+            duration: other.duration.or(self.duration),
+        }
+    }
+
+    /// The end time as `HH:MM`, if the start and duration are known
+    pub fn end(&self) -> Option<String> {
+        let start = minutes_of_day(self.start.as_deref()?)?;
+        Some(time_of_day(start + self.duration?))
+    }
+
+    /// This timeslot starting `minutes` later (or earlier, if negative), at the same
+    /// venue and for the same length
+    pub fn moved_by(&self, minutes: i64) -> Timeslot {
+        Timeslot {
+            start: self
+                .start
+                .as_deref()
+                .and_then(minutes_of_day)
+                .map(|start| time_of_day(start + minutes)),
+            ..self.clone()
+        }
+    }
+}
+
+impl fmt::Display for Timeslot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.start.as_deref().unwrap_or("unscheduled"))?;
+        if let Some(duration) = self.duration {
+            write!(f, " for {duration}m")?;
+        }
+        if let Some(venue) = &self.venue {
+            write!(f, " at {venue}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether a date is written as `YYYY-MM-DD`
+pub fn is_date(date: &str) -> bool {
+    let parts: Vec<&str> = date.split('-').collect();
+    let [year, month, day] = parts.as_slice() else {
+        return false;
+    };
+    let number = |part: &str, len: usize| {
+        (part.len() == len)
+            .then(|| part.parse::<u32>().ok())
+            .flatten()
+    };
+    number(year, 4).is_some()
+        && number(month, 2).is_some_and(|month| (1..=12).contains(&month))
+        && number(day, 2).is_some_and(|day| (1..=31).contains(&day))
+}
+
+/// A number of minutes since midnight as `HH:MM`, wrapping around at midnight
+fn time_of_day(minutes: i64) -> String {
+    let minutes = minutes.rem_euclid(24 * 60);
+    format!("{:02}:{:02}", minutes / 60, minutes % 60)
 }
 
+/// Minutes since midnight of a `HH:MM` time, or `None` if it isn't one
+pub fn minutes_of_day(time: &str) -> Option<i64> {
+    let (hours, minutes) = time.split_once(':')?;
+    if hours.len() != 2 || minutes.len() != 2 {
+        return None;
+    }
+    let hours: i64 = hours.parse().ok()?;
+    let minutes: i64 = minutes.parse().ok()?;
+    (hours < 24 && minutes < 60).then_some(hours * 60 + minutes)
+}
+
+/// A named set of scores, e.g. one that only awards the top three
 #[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScoringScheme {
+    /// Unique identifier that events reference from `scoring`
+    pub id: String,
+    pub scores: Vec<Score>,
+    /// Overrides the global tie rule for events using this scheme
+    #[serde(default)]
+    pub tie_rule: Option<TieRule>,
+}
+
+/// The kind of performance recorded for a measured event
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Measurement {
+    /// Times in seconds, lowest wins
+    #[serde(rename = "time")]
+    Time,
+    /// Distances in metres, highest wins
+    #[serde(rename = "distance")]
+    Distance,
+    /// Heights in metres, highest wins
+    #[serde(rename = "height")]
+    Height,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Score {
     pub name: String,
     pub value: i64,
     pub default: bool,
 }
 
+/// How tied forms share a placing
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub enum TieRule {
+    /// Tied forms share the placing and split the points for the places they cover
+    #[serde(rename = "shared_average")]
+    SharedAverage,
+    /// Tied forms share the placing and each get its full points
+    #[default]
+    #[serde(rename = "shared_full")]
+    SharedFull,
+    /// Ties aren't allowed to stand, scorers must break them on countback
+    #[serde(rename = "countback")]
+    Countback,
+}
+
 /// Flexible rules for determining applicability
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(tag = "type")]
@@ -76,10 +295,9 @@ pub enum ApplicabilityRules {
 }
 
 impl Configuration {
-    /// Load configuration from YAML file
-    pub fn from_yaml_file(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
-        let content = std::fs::read_to_string(path)?;
-        let config: Configuration = serde_yml::from_str(&content)?;
+    /// Load configuration from a YAML string
+    pub fn from_yaml_str(content: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let config: Configuration = serde_yml::from_str(content)?;
         Ok(config)
     }
 
@@ -103,6 +321,61 @@ impl Configuration {
         }
     }
 
+    /// Find the configured event an `events` row was generated from
+    pub fn event_for_filter_key(&self, filter_key: &str) -> Option<&Event> {
+        self.events.iter().find(|event| event.id == filter_key)
+    }
+
+    /// When and where an event runs for a year and gender, from its schedule and the
+    /// slots that match them
+    pub fn timeslot_for(&self, event: &Event, year_id: &str, gender: &str) -> Timeslot {
+        let Some(schedule) = &event.schedule else {
+            return Timeslot::default();
+        };
+        schedule
+            .slots
+            .iter()
+            .filter(|slot| slot.year.as_deref().is_none_or(|year| year == year_id))
+            .filter(|slot| {
+                slot.gender
+                    .as_deref()
+                    .is_none_or(|slot_gender| slot_gender == gender)
+            })
+            .fold(schedule.timeslot.clone(), |timeslot, slot| {
+                timeslot.overridden_by(&slot.timeslot)
+            })
+    }
+
+    /// The forms that run in a heat of an event
+    pub fn forms_in_heat(&self, rounds: &Rounds, heat: i64) -> Vec<&Form> {
+        self.forms
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| rounds.heat_of(*index) == heat)
+            .map(|(_, form)| form)
+            .collect()
+    }
+
+    /// The scores an event with the given scoring scheme is marked with.
+    /// Falls back to the global scores when there's no scheme or it no longer exists.
+    pub fn scores_for(&self, scheme: Option<&str>) -> &[Score] {
+        self.scheme(scheme)
+            .map(|scheme| scheme.scores.as_slice())
+            .unwrap_or(&self.scores)
+    }
+
+    /// The tie rule for an event with the given scoring scheme
+    pub fn tie_rule_for(&self, scheme: Option<&str>) -> &TieRule {
+        self.scheme(scheme)
+            .and_then(|scheme| scheme.tie_rule.as_ref())
+            .unwrap_or(&self.tie_rule)
+    }
+
+    fn scheme(&self, scheme: Option<&str>) -> Option<&ScoringScheme> {
+        let scheme = scheme?;
+        self.scoring_schemes.iter().find(|s| s.id == scheme)
+    }
+
     /// Get Schema Version
     pub fn get_version(&self) -> String {
         self.version.clone()
@@ -0,0 +1,122 @@
+use std::{
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use actix::Addr;
+use async_sqlite::Pool;
+use futures::lock::Mutex;
+use log::{debug, error, info};
+
+use crate::{
+    configurator::{diff::PlanDiff, parser::Configuration, run},
+    db::config_versions::ConfigVersions,
+    utils,
+    websocket::{ChannelsActor, Publish},
+};
+
+pub const CONFIG_PATH: &str = "./config.yaml";
+
+/// The live configuration, shared by every worker and swapped atomically on reload
+#[derive(Clone)]
+pub struct SharedConfig {
+    inner: Arc<RwLock<Arc<Configuration>>>,
+    /// Held for the whole of a reload, so the watcher and the admin page can't
+    /// reconcile the database at the same time
+    reloading: Arc<Mutex<()>>,
+}
+
+impl SharedConfig {
+    pub fn new(config: Configuration) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(Arc::new(config))),
+            reloading: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// A snapshot of the current configuration
+    pub fn get(&self) -> Arc<Configuration> {
+        self.inner.read().unwrap().clone()
+    }
+
+    pub fn replace(&self, config: Configuration) {
+        *self.inner.write().unwrap() = Arc::new(config);
+    }
+}
+
+/// Re-read config.yaml, validate it, reconcile the database, swap it in and push a
+/// fresh scoreboard. The current configuration is kept if anything fails, and only
+/// one reload runs at a time.
+pub async fn reload(
+    config: &SharedConfig,
+    pool: &Pool,
+    channels: &Addr<ChannelsActor>,
+) -> Result<Option<PlanDiff>, Box<dyn std::error::Error>> {
+    let _reloading = config.reloading.lock().await;
+    let yaml = std::fs::read_to_string(CONFIG_PATH)?;
+    let new_config = Configuration::from_yaml_str(&yaml)?;
+    let diff = run::apply(&new_config, &yaml, pool).await?;
+    config.replace(new_config);
+    info!("Reloaded config version {}", config.get().get_version());
+
+    channels.do_send(Publish {
+        channel: "scores".to_string(),
+        payload: utils::render_scoreboard(pool, &config.get()).await,
+    });
+    Ok(diff)
+}
+
+/// Poll config.yaml and reload whenever its contents change
+pub fn watch(config: SharedConfig, pool: Pool, channels: Addr<ChannelsActor>) {
+    actix_web::rt::spawn(async move {
+        let mut last_hash = std::fs::read_to_string(CONFIG_PATH)
+            .map(|yaml| ConfigVersions::hash_yaml(&yaml))
+            .unwrap_or_default();
+        let mut interval = actix_web::rt::time::interval(Duration::from_secs(2));
+        loop {
+            interval.tick().await;
+            let hash = match std::fs::read_to_string(CONFIG_PATH) {
+                Ok(yaml) => ConfigVersions::hash_yaml(&yaml),
+                Err(e) => {
+                    debug!("Could not read {CONFIG_PATH}: {e}");
+                    continue;
+                }
+            };
+            if hash == last_hash {
+                continue;
+            }
+            last_hash = hash;
+            info!("{CONFIG_PATH} changed, reloading");
+            match reload(&config, &pool, &channels).await {
+                Ok(Some(diff)) => {
+                    for change in diff.changes.iter() {
+                        info!("{change}");
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => error!("Keeping current config, reload failed: {e}"),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_harness;
+
+    #[test]
+    fn replace_is_seen_by_clones() {
+        let config = test_harness::config();
+        let shared = SharedConfig::new(config.clone());
+        let worker = shared.clone();
+
+        let before = worker.get();
+        let mut changed = config;
+        changed.forms[0].colour = "red".to_string();
+        shared.replace(changed);
+
+        assert_eq!(worker.get().forms[0].colour, "red");
+        assert_ne!(before.forms[0].colour, "red");
+    }
+}
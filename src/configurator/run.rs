@@ -2,32 +2,285 @@ use async_sqlite::Pool;
 use log::{debug, info};
 
 use crate::{
-    configurator::build::Plan,
-    db::{events::Events, years::Years},
+    configurator::{
+        build::{build_plan, Plan},
+        diff::{Change, PlanDiff},
+        parser::Configuration,
+    },
+    db::{
+        archived_events::ArchivedEvents, config_versions::ConfigVersions, events::Events,
+        forms::Forms, years::Years,
+    },
 };
 
-pub async fn run(plan: Plan, pool: &Pool) -> Result<(), async_sqlite::Error> {
+/// Apply the config unless the same YAML was the last to be applied, recording it in
+/// `config_versions`. Returns the changes made, or `None` if nothing was applied.
+/// The config is validated before anything is written, and forms are synced whether or
+/// not anything else changed, since their names and colours apply immediately.
+pub async fn apply(
+    config: &Configuration,
+    yaml: &str,
+    pool: &Pool,
+) -> Result<Option<PlanDiff>, Box<dyn std::error::Error>> {
+    let plan = build_plan(config.clone())?;
+    Forms::sync(pool, config.forms.clone()).await?;
+
+    if let Some(applied) = ConfigVersions::latest(pool).await? {
+        if applied.yaml_hash == ConfigVersions::hash_yaml(yaml) {
+            debug!("Config matches the last one applied, not reconciling");
+            return Ok(None);
+        }
+    }
+
+    debug!("Config has changed since it was last applied, reconciling");
+    let plan_json = serde_json::to_string(&plan).unwrap();
+    let version = ConfigVersions::new(config.get_version(), yaml, plan_json);
+    Ok(Some(run(plan, pool, Some(version)).await?))
+}
+
+/// Reconcile the database with the plan, keeping the scores of events that still exist.
+/// Every change is made in one transaction along with recording `version`, so a plan
+/// is either applied in full and recorded or not applied at all. Plans that would leave
+/// events in a year that no longer exists are refused.
+pub async fn run(
+    plan: Plan,
+    pool: &Pool,
+    version: Option<ConfigVersions>,
+) -> Result<PlanDiff, Box<dyn std::error::Error>> {
     info!("Implementing Plan");
-    Events::delete_all(&pool).await.unwrap();
-    Years::delete_all(&pool).await.unwrap();
-    for year in plan.year_plans.iter() {
-        debug!("Inserting Planned Year {}", year.id);
-        let mut year_struct = Years::new(year.id.clone(), year.name.clone())
-            .insert(&pool)
-            .await?;
-        for event in year.events.iter() {
-            debug!("Inserting Planned Event {}", event.id);
-            year_struct = year_struct
-                .new_event(
-                    &pool,
-                    event.clone().id,
-                    event.clone().name,
-                    event.clone().gender_id,
-                    event.clone().filter_key,
-                    event.clone().scores,
+    let years = Years::all(pool).await?;
+    let events = Events::all(pool).await?;
+    let diff = PlanDiff::between(&plan, &years, &events);
+    let orphans = diff.orphans(&plan, &events);
+    if !orphans.is_empty() {
+        let ids: Vec<&str> = orphans.iter().map(|event| event.id.as_str()).collect();
+        return Err(format!(
+            "{} would be left in years that are no longer in the config",
+            ids.join(", ")
+        )
+        .into());
+    }
+
+    let changes = diff.changes.clone();
+    pool.conn_mut(move |conn| {
+        let tx = conn.transaction()?;
+        for change in changes {
+            debug!("Applying {change}");
+            match change {
+                Change::AddYear { id, name } => Years::new(id, name).insert_in(&tx)?,
+                Change::RenameYear { id, to, .. } => Years::update_name_in(&tx, &id, &to)?,
+                Change::RemoveYear { id } => Years::delete_in(&tx, &id)?,
+                Change::AddEvent { year_id, event } => Events::new(
+                    event.id,
+                    event.name,
+                    year_id,
+                    event.gender_id,
+                    event.filter_key,
                 )
-                .await?
+                .with_scoring(event.scoring, event.multiplier)
+                .with_timeslot(event.timeslot)
+                .with_heat(event.heat)
+                .insert_in(&tx)?,
+                Change::UpdateEvent { year_id, to, .. } => {
+                    Events::new(to.id, to.name, year_id, to.gender_id, to.filter_key)
+                        .with_scoring(to.scoring, to.multiplier)
+                        .with_timeslot(to.timeslot)
+                        .with_heat(to.heat)
+                        .update_details_in(&tx)?
+                }
+                Change::RemoveEvent { event } => ArchivedEvents::archive_in(&tx, &event.id)?,
+            }
+        }
+        if let Some(version) = version {
+            version.insert_in(&tx)?;
         }
+        tx.commit()?;
+        Ok(())
+    })
+    .await?;
+    info!("Applied {} plan changes", diff.changes.len());
+    Ok(diff)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        configurator::{
+            build::{EventPlan, YearPlan},
+            parser::{Form, Score},
+        },
+        db::{
+            event_scores::{EventScores, ScoreUpdate},
+            forms::Forms,
+            score_changes::ChangedBy,
+        },
+        test_harness::{self, ConfigBuilder},
+    };
+
+    use super::*;
+
+    /// One event for one form, so each apply makes a single change
+    fn builder() -> ConfigBuilder {
+        ConfigBuilder::default()
+            .genders(&["boys"])
+            .years(&["y9"])
+            .forms(&["w"])
+            .events(&["60m"])
+    }
+
+    fn plan(event_names: &[(&str, &str)]) -> Plan {
+        Plan {
+            year_plans: vec![YearPlan {
+                id: "y9".to_string(),
+                name: "Year 9".to_string(),
+                events: event_names
+                    .iter()
+                    .map(|(id, name)| EventPlan {
+                        id: format!("y9-boys-{id}"),
+                        name: name.to_string(),
+                        gender_id: "boys".to_string(),
+                        filter_key: id.to_string(),
+                        scoring: None,
+                        multiplier: 1,
+                        timeslot: Default::default(),
+                        heat: None,
+                    })
+                    .collect(),
+            }],
+        }
+    }
+
+    async fn set_points(db: &Pool, event_id: &str, points: i64) {
+        assert!(EventScores::set_batch(
+            db,
+            vec![ScoreUpdate {
+                event_id: event_id.to_string(),
+                scores: vec![(
+                    "w".to_string(),
+                    Score {
+                        name: points.to_string(),
+                        value: points,
+                        default: false,
+                    }
+                )],
+                performances: None,
+                entries: None,
+                revision: None
+            }],
+            ChangedBy::default()
+        )
+        .await
+        .is_ok());
+    }
+
+    #[tokio::test]
+    async fn run_keeps_scores_test() {
+        let db = test_harness::setup_db("run_keeps_scores").await;
+        assert!(run(plan(&[("60m", "60m"), ("200m", "200m")]), &db, None)
+            .await
+            .is_ok());
+        assert!(Forms::sync(
+            &db,
+            vec![Form {
+                id: "w".to_string(),
+                name: "Winston".to_string(),
+                colour: "red".to_string(),
+            }]
+        )
+        .await
+        .is_ok());
+        set_points(&db, "y9-boys-60m", 20).await;
+        set_points(&db, "y9-boys-200m", 15).await;
+
+        let diff = run(
+            plan(&[("60m", "60 Metres"), ("javelin", "Javelin")]),
+            &db,
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(diff.changes.len(), 3);
+
+        let events = Events::all(&db).await.unwrap();
+        assert_eq!(events.len(), 2);
+        let sixty = events.iter().find(|e| e.id == "y9-boys-60m").unwrap();
+        assert_eq!(sixty.name, "60 Metres");
+        let scores = EventScores::all(&db).await.unwrap();
+        assert_eq!(scores.len(), 1);
+        assert_eq!(scores[0].event_id, sixty.id);
+        assert_eq!(scores[0].points, 20);
+
+        let archived = ArchivedEvents::all(&db).await.unwrap();
+        assert_eq!(archived.len(), 1);
+        assert_eq!(archived[0].event_id, "y9-boys-200m");
+        assert_eq!(archived[0].scores, "{\"w\":15}");
+    }
+
+    #[tokio::test]
+    async fn apply_records_version_test() {
+        let db = test_harness::setup_db("run_apply_records_version").await;
+        let yaml = builder().yaml();
+        let config = Configuration::from_yaml_str(&yaml).unwrap();
+
+        assert!(apply(&config, &yaml, &db).await.unwrap().is_some());
+        let applied = ConfigVersions::latest(&db).await.unwrap().unwrap();
+        assert_eq!(applied.version, config.get_version());
+        assert_eq!(applied.yaml_hash, ConfigVersions::hash_yaml(&yaml));
+
+        assert!(apply(&config, &yaml, &db).await.unwrap().is_none());
+        assert_eq!(ConfigVersions::all(&db).await.unwrap().len(), 1);
+
+        // Edits are reconciled even when the version stays the same
+        let mut renamed = config.clone();
+        renamed.events[0].name = "60 Metres".to_string();
+        let renamed_yaml = serde_yml::to_string(&renamed).unwrap();
+        let diff = apply(&renamed, &renamed_yaml, &db).await.unwrap().unwrap();
+        assert_eq!(diff.changes.len(), 1);
+        assert_eq!(Events::all(&db).await.unwrap()[0].name, "60 Metres");
+        assert_eq!(ConfigVersions::all(&db).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn invalid_config_writes_nothing_test() {
+        let db = test_harness::setup_db("run_invalid_config_writes_nothing").await;
+        // Duplicate form ids fail validation
+        let mut config = builder().build();
+        config.forms.push(config.forms[0].clone());
+        let yaml = serde_yml::to_string(&config).unwrap();
+
+        assert!(apply(&config, &yaml, &db).await.is_err());
+        let forms: i64 = db
+            .conn(|conn| conn.query_row("SELECT COUNT(*) FROM forms", [], |row| row.get(0)))
+            .await
+            .unwrap();
+        assert_eq!(forms, 0);
+        assert!(ConfigVersions::latest(&db).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn failed_run_changes_nothing_test() {
+        let db = test_harness::setup_db("run_failed_changes_nothing").await;
+        let version = ConfigVersions::new("test".to_string(), &builder().yaml(), "{}".to_string());
+        // The second insert of the same event id fails part way through the plan
+        assert!(run(
+            plan(&[("60m", "60m"), ("60m", "60m again")]),
+            &db,
+            Some(version)
+        )
+        .await
+        .is_err());
+        assert!(Years::all(&db).await.unwrap().is_empty());
+        assert!(Events::all(&db).await.unwrap().is_empty());
+        assert!(ConfigVersions::latest(&db).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn run_is_idempotent_test() {
+        let db = test_harness::setup_db("run_is_idempotent").await;
+        assert!(run(plan(&[("60m", "60m")]), &db, None).await.is_ok());
+        assert!(run(plan(&[("60m", "60m")]), &db, None)
+            .await
+            .unwrap()
+            .is_empty());
     }
-    Ok(())
 }
@@ -0,0 +1,479 @@
+use std::{collections::HashSet, fmt};
+
+use crate::configurator::parser::{
+    is_date, minutes_of_day, ApplicabilityRules, Configuration, Score, Timeslot,
+};
+
+/// A single problem found in the configuration, with the path to the offending value
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationError {
+    /// Path into the config (e.g. `events[3].applicable_years.ids[1]`)
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// Every problem found in the configuration
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationErrors(pub Vec<ValidationError>);
+
+impl fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "config has {} error(s):", self.0.len())?;
+        for error in self.0.iter() {
+            writeln!(f, "  {error}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ValidationErrors {}
+
+impl Configuration {
+    /// Check the configuration for problems that deserialising alone doesn't catch
+    pub fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = vec![];
+
+        if let Some(date) = &self.date {
+            if !is_date(date) {
+                errors.push(ValidationError {
+                    path: "date".to_string(),
+                    message: format!("date must be like \"2026-07-10\", got \"{date}\""),
+                });
+            }
+        }
+
+        check_unique(
+            &mut errors,
+            self.genders.iter().map(String::as_str),
+            |i| format!("genders[{i}]"),
+            "gender",
+        );
+        check_unique(
+            &mut errors,
+            self.years.iter().map(|year| year.id.as_str()),
+            |i| format!("years[{i}].id"),
+            "year id",
+        );
+        check_unique(
+            &mut errors,
+            self.forms.iter().map(|form| form.id.as_str()),
+            |i| format!("forms[{i}].id"),
+            "form id",
+        );
+        check_unique(
+            &mut errors,
+            self.events.iter().map(|event| event.id.as_str()),
+            |i| format!("events[{i}].id"),
+            "event id",
+        );
+        check_unique(
+            &mut errors,
+            self.scores.iter().map(|score| score.name.as_str()),
+            |i| format!("scores[{i}].name"),
+            "score name",
+        );
+
+        check_single_default(&mut errors, &self.scores, "scores");
+
+        check_unique(
+            &mut errors,
+            self.scoring_schemes.iter().map(|scheme| scheme.id.as_str()),
+            |i| format!("scoring_schemes[{i}].id"),
+            "scoring scheme id",
+        );
+        for (i, scheme) in self.scoring_schemes.iter().enumerate() {
+            check_unique(
+                &mut errors,
+                scheme.scores.iter().map(|score| score.name.as_str()),
+                |j| format!("scoring_schemes[{i}].scores[{j}].name"),
+                "score name",
+            );
+            check_single_default(
+                &mut errors,
+                &scheme.scores,
+                &format!("scoring_schemes[{i}].scores"),
+            );
+        }
+
+        let year_ids: Vec<&str> = self.years.iter().map(|year| year.id.as_str()).collect();
+        let gender_ids: Vec<&str> = self.genders.iter().map(String::as_str).collect();
+
+        for (i, event) in self.events.iter().enumerate() {
+            check_rule_ids(
+                &mut errors,
+                &event.applicable_years,
+                &year_ids,
+                format!("events[{i}].applicable_years"),
+                "year",
+            );
+            check_rule_ids(
+                &mut errors,
+                &event.applicable_genders,
+                &gender_ids,
+                format!("events[{i}].applicable_genders"),
+                "gender",
+            );
+
+            if let Some(scoring) = &event.scoring {
+                if !self
+                    .scoring_schemes
+                    .iter()
+                    .any(|scheme| &scheme.id == scoring)
+                {
+                    errors.push(ValidationError {
+                        path: format!("events[{i}].scoring"),
+                        message: format!("unknown scoring scheme \"{scoring}\""),
+                    });
+                }
+            }
+            if event.multiplier < 1 {
+                errors.push(ValidationError {
+                    path: format!("events[{i}].multiplier"),
+                    message: format!("multiplier must be at least 1, got {}", event.multiplier),
+                });
+            }
+
+            if let Some(rounds) = &event.rounds {
+                let path = format!("events[{i}].rounds");
+                if rounds.heats < 1 || rounds.heats as usize > self.forms.len() {
+                    errors.push(ValidationError {
+                        path: format!("{path}.heats"),
+                        message: format!(
+                            "heats must be between 1 and the number of forms ({}), got {}",
+                            self.forms.len(),
+                            rounds.heats
+                        ),
+                    });
+                }
+                if !rounds.heat_offsets.is_empty()
+                    && rounds.heat_offsets.len() as i64 != rounds.heats
+                {
+                    errors.push(ValidationError {
+                        path: format!("{path}.heat_offsets"),
+                        message: format!(
+                            "heat_offsets must give one offset per heat ({}), got {}",
+                            rounds.heats,
+                            rounds.heat_offsets.len()
+                        ),
+                    });
+                }
+                // Qualifiers are taken from the placings, so they have to be placed
+                let places = self
+                    .scores_for(event.scoring.as_deref())
+                    .iter()
+                    .filter(|score| !score.default)
+                    .count();
+                if rounds.qualifiers < 1 || rounds.qualifiers as usize > places {
+                    errors.push(ValidationError {
+                        path: format!("{path}.qualifiers"),
+                        message: format!(
+                            "qualifiers must be between 1 and the number of placings scored ({places}), got {}",
+                            rounds.qualifiers
+                        ),
+                    });
+                }
+            }
+
+            if let Some(schedule) = &event.schedule {
+                let path = format!("events[{i}].schedule");
+                check_timeslot(&mut errors, &schedule.timeslot, &path);
+                for (j, slot) in schedule.slots.iter().enumerate() {
+                    let path = format!("{path}.slots[{j}]");
+                    if let Some(year) = &slot.year {
+                        if !year_ids.contains(&year.as_str()) {
+                            errors.push(ValidationError {
+                                path: format!("{path}.year"),
+                                message: format!("unknown year \"{year}\""),
+                            });
+                        }
+                    }
+                    if let Some(gender) = &slot.gender {
+                        if !gender_ids.contains(&gender.as_str()) {
+                            errors.push(ValidationError {
+                                path: format!("{path}.gender"),
+                                message: format!("unknown gender \"{gender}\""),
+                            });
+                        }
+                    }
+                    check_timeslot(&mut errors, &slot.timeslot, &path);
+                }
+            }
+
+            let disabled = matches!(event.applicable_years, ApplicabilityRules::None)
+                || matches!(event.applicable_genders, ApplicabilityRules::None);
+            let applies_to_something = self
+                .years
+                .iter()
+                .any(|year| self.is_event_applicable_to_year(event, &year.id))
+                && self
+                    .genders
+                    .iter()
+                    .any(|gender| self.is_event_applicable_to_gender(event, gender));
+            if !disabled && !applies_to_something {
+                errors.push(ValidationError {
+                    path: format!("events[{i}]"),
+                    message: format!(
+                        "event \"{}\" does not apply to any year and gender, use `type: none` to disable it",
+                        event.id
+                    ),
+                });
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationErrors(errors))
+        }
+    }
+}
+
+fn check_single_default(errors: &mut Vec<ValidationError>, scores: &[Score], path: &str) {
+    let defaults: Vec<usize> = scores
+        .iter()
+        .enumerate()
+        .filter(|(_, score)| score.default)
+        .map(|(i, _)| i)
+        .collect();
+    for i in defaults.iter().skip(1) {
+        errors.push(ValidationError {
+            path: format!("{path}[{i}].default"),
+            message: format!(
+                "only one score may be the default, {path}[{}] already is",
+                defaults[0]
+            ),
+        });
+    }
+}
+
+fn check_timeslot(errors: &mut Vec<ValidationError>, timeslot: &Timeslot, path: &str) {
+    if let Some(start) = &timeslot.start {
+        if minutes_of_day(start).is_none() {
+            errors.push(ValidationError {
+                path: format!("{path}.start"),
+                message: format!("start must be a time like \"09:30\", got \"{start}\""),
+            });
+        }
+    }
+    if let Some(duration) = timeslot.duration {
+        if duration < 1 {
+            errors.push(ValidationError {
+                path: format!("{path}.duration"),
+                message: format!("duration must be at least 1 minute, got {duration}"),
+            });
+        }
+    }
+}
+
+fn check_unique<'a>(
+    errors: &mut Vec<ValidationError>,
+    ids: impl Iterator<Item = &'a str>,
+    path: impl Fn(usize) -> String,
+    kind: &str,
+) {
+    let mut seen = HashSet::new();
+    for (i, id) in ids.enumerate() {
+        if id.is_empty() {
+            errors.push(ValidationError {
+                path: path(i),
+                message: format!("{kind} must not be empty"),
+            });
+        } else if !seen.insert(id) {
+            errors.push(ValidationError {
+                path: path(i),
+                message: format!("duplicate {kind} \"{id}\""),
+            });
+        }
+    }
+}
+
+fn check_rule_ids(
+    errors: &mut Vec<ValidationError>,
+    rules: &ApplicabilityRules,
+    known: &[&str],
+    path: String,
+    kind: &str,
+) {
+    let ids = match rules {
+        ApplicabilityRules::Include { ids } | ApplicabilityRules::Exclude { ids } => ids,
+        ApplicabilityRules::All | ApplicabilityRules::None => return,
+    };
+    for (i, id) in ids.iter().enumerate() {
+        if !known.contains(&id.as_str()) {
+            errors.push(ValidationError {
+                path: format!("{path}.ids[{i}]"),
+                message: format!("unknown {kind} \"{id}\""),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        configurator::parser::{Rounds, Schedule, ScheduleSlot, ScoringScheme},
+        test_harness::config,
+    };
+
+    use super::*;
+
+    fn paths(result: Result<(), ValidationErrors>) -> Vec<String> {
+        result
+            .unwrap_err()
+            .0
+            .into_iter()
+            .map(|error| error.path)
+            .collect()
+    }
+
+    #[test]
+    fn shipped_config_is_valid() {
+        let yaml = std::fs::read_to_string("./config.yaml").unwrap();
+        assert!(Configuration::from_yaml_str(&yaml)
+            .unwrap()
+            .validate()
+            .is_ok());
+    }
+
+    #[test]
+    fn valid_config_passes() {
+        assert!(config().validate().is_ok());
+    }
+
+    #[test]
+    fn duplicate_ids_are_reported() {
+        let mut config = config();
+        config.years.push(config.years[0].clone());
+        config.forms.push(config.forms[0].clone());
+        config.events.push(config.events[0].clone());
+        assert_eq!(
+            paths(config.validate()),
+            vec!["years[2].id", "forms[4].id", "events[2].id"]
+        );
+    }
+
+    #[test]
+    fn unknown_rule_ids_are_reported() {
+        let mut config = config();
+        config.events[0].applicable_years = ApplicabilityRules::Include {
+            ids: vec!["y9".to_string(), "y12".to_string()],
+        };
+        config.events[0].applicable_genders = ApplicabilityRules::Exclude {
+            ids: vec!["mixed".to_string()],
+        };
+        assert_eq!(
+            paths(config.validate()),
+            vec![
+                "events[0].applicable_years.ids[1]",
+                "events[0].applicable_genders.ids[0]"
+            ]
+        );
+    }
+
+    #[test]
+    fn multiple_defaults_are_reported() {
+        let mut config = config();
+        config.scores[0].default = true;
+        assert_eq!(paths(config.validate()), vec!["scores[2].default"]);
+    }
+
+    #[test]
+    fn scoring_scheme_problems_are_reported() {
+        let mut config = config();
+        config.scoring_schemes.push(ScoringScheme {
+            id: "top3".to_string(),
+            scores: vec![config.scores[2].clone(), config.scores[2].clone()],
+            tie_rule: None,
+        });
+        config
+            .scoring_schemes
+            .push(config.scoring_schemes[0].clone());
+        config.scoring_schemes[1].scores.truncate(1);
+        config.events[0].scoring = Some("top4".to_string());
+        config.events[0].multiplier = 0;
+        assert_eq!(
+            paths(config.validate()),
+            vec![
+                "scoring_schemes[1].id",
+                "scoring_schemes[0].scores[1].name",
+                "scoring_schemes[0].scores[1].default",
+                "events[0].scoring",
+                "events[0].multiplier"
+            ]
+        );
+    }
+
+    #[test]
+    fn schedule_problems_are_reported() {
+        let mut config = config();
+        config.date = Some("10/07/2026".to_string());
+        config.events[0].schedule = Some(Schedule {
+            timeslot: Timeslot {
+                start: Some("9:30".to_string()),
+                venue: None,
+                duration: Some(0),
+            },
+            slots: vec![ScheduleSlot {
+                year: Some("y12".to_string()),
+                gender: Some("boys".to_string()),
+                timeslot: Timeslot {
+                    start: Some("25:00".to_string()),
+                    ..Default::default()
+                },
+            }],
+        });
+        assert_eq!(
+            paths(config.validate()),
+            vec![
+                "date",
+                "events[0].schedule.start",
+                "events[0].schedule.duration",
+                "events[0].schedule.slots[0].year",
+                "events[0].schedule.slots[0].start"
+            ]
+        );
+    }
+
+    #[test]
+    fn rounds_problems_are_reported() {
+        let mut config = config();
+        config.events[0].rounds = Some(Rounds {
+            heats: 1,
+            qualifiers: 1,
+            heat_offsets: vec![-30],
+        });
+        assert!(config.validate().is_ok());
+
+        // Four forms can't fill five heats, only 1st and 2nd are placed, and there's
+        // one offset for five heats
+        config.events[0].rounds = Some(Rounds {
+            heats: 5,
+            qualifiers: 3,
+            heat_offsets: vec![-30],
+        });
+        assert_eq!(
+            paths(config.validate()),
+            vec![
+                "events[0].rounds.heats",
+                "events[0].rounds.heat_offsets",
+                "events[0].rounds.qualifiers"
+            ]
+        );
+    }
+
+    #[test]
+    fn event_applying_to_nothing_is_reported() {
+        let mut config = config();
+        config.events[0].applicable_genders = ApplicabilityRules::Include { ids: vec![] };
+        assert_eq!(paths(config.validate()), vec!["events[0]"]);
+
+        config.events[0].applicable_genders = ApplicabilityRules::None;
+        assert!(config.validate().is_ok());
+    }
+}
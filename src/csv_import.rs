@@ -0,0 +1,131 @@
+//! Reading the CSVs admins paste in to seed tables
+
+/// A row of a CSV, holding the values of the columns asked for in the order asked
+#[derive(Debug, PartialEq)]
+pub struct CsvRow {
+    /// Line number in the CSV, counting the header as line 1
+    pub line: usize,
+    pub values: Vec<String>,
+}
+
+/// Read the named columns from a CSV with a header row. Headers are matched ignoring
+/// case and can be in any order, and values are trimmed. Returns a message for every
+/// missing column, or for every row that can't be read.
+pub fn read(csv: &str, columns: &[&str]) -> Result<Vec<CsvRow>, Vec<String>> {
+    read_with_optional(csv, columns, &[])
+}
+
+/// Read the named columns like `read`, followed by the `optional` columns, which are
+/// left empty if the CSV doesn't have them
+pub fn read_with_optional(
+    csv: &str,
+    columns: &[&str],
+    optional: &[&str],
+) -> Result<Vec<CsvRow>, Vec<String>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .flexible(true)
+        .from_reader(csv.as_bytes());
+    let headers = match reader.headers() {
+        Ok(headers) => headers.clone(),
+        Err(e) => return Err(vec![e.to_string()]),
+    };
+    let positions: Vec<Option<usize>> = columns
+        .iter()
+        .map(|column| {
+            headers
+                .iter()
+                .position(|header| header.eq_ignore_ascii_case(column))
+        })
+        .collect();
+    let missing: Vec<String> = columns
+        .iter()
+        .zip(positions.iter())
+        .filter(|(_, position)| position.is_none())
+        .map(|(column, _)| format!("missing a {column} column"))
+        .collect();
+    if !missing.is_empty() {
+        return Err(missing);
+    }
+    let optional_positions: Vec<Option<usize>> = optional
+        .iter()
+        .map(|column| {
+            headers
+                .iter()
+                .position(|header| header.eq_ignore_ascii_case(column))
+        })
+        .collect();
+
+    let mut rows = vec![];
+    let mut errors = vec![];
+    for (i, record) in reader.records().enumerate() {
+        let line = i + 2;
+        match record {
+            Ok(record) => rows.push(CsvRow {
+                line,
+                values: positions
+                    .iter()
+                    .chain(optional_positions.iter())
+                    .map(|position| {
+                        position
+                            .and_then(|position| record.get(position))
+                            .unwrap_or_default()
+                            .to_string()
+                    })
+                    .collect(),
+            }),
+            Err(e) => errors.push(format!("line {line}: {e}")),
+        }
+    }
+    if errors.is_empty() {
+        Ok(rows)
+    } else {
+        Err(errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_columns_in_the_order_asked() {
+        let rows = read("Form,Name\nw,\"Smith, Sam\"\nt, Ada \n", &["name", "form"]).unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                CsvRow {
+                    line: 2,
+                    values: vec!["Smith, Sam".to_string(), "w".to_string()]
+                },
+                CsvRow {
+                    line: 3,
+                    values: vec!["Ada".to_string(), "t".to_string()]
+                },
+            ]
+        );
+        assert_eq!(
+            read("name\nAda\n", &["name", "form", "year"]).unwrap_err(),
+            vec!["missing a form column", "missing a year column"]
+        );
+    }
+
+    #[test]
+    fn optional_columns_are_left_empty_when_missing() {
+        let rows = read_with_optional(
+            "form,year
+w,y9
+",
+            &["form"],
+            &["gender", "year"],
+        )
+        .unwrap();
+        assert_eq!(
+            rows,
+            vec![CsvRow {
+                line: 2,
+                values: vec!["w".to_string(), String::new(), "y9".to_string()]
+            }]
+        );
+    }
+}
@@ -0,0 +1,293 @@
+use async_sqlite::rusqlite::{Error as RusqliteError, OptionalExtension};
+use async_sqlite::{rusqlite::Row, Pool};
+use log::debug;
+use sha2::{Digest, Sha256};
+
+use crate::db::events::Events;
+
+/// Columns read by `map_from_row`, in order
+const COLUMNS: &str = "api_tokens.id, name, scopes, year_ids, event_ids, expires_at, created_by,
+    users.email, created_at, last_used_at";
+
+/// What an API token lets its holder do
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Scope {
+    /// Submit scores, as scorers do from the set-scores page
+    ScoresWrite,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 1] = [Scope::ScoresWrite];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::ScoresWrite => "scores:write",
+        }
+    }
+
+    pub fn parse(scope: &str) -> Option<Scope> {
+        Self::ALL.into_iter().find(|known| known.as_str() == scope)
+    }
+}
+
+/// A token for tools like timing systems to use the API with, sent as
+/// `Authorization: Bearer <token>`. Only a hash of the token is stored.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ApiTokens {
+    pub id: Option<i64>,
+    /// What it's for, e.g. "Track timing system"
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    /// Year ids it's limited to, or empty for every year
+    pub year_ids: Vec<String>,
+    /// Event ids or config event ids it's limited to, or empty for every event
+    pub event_ids: Vec<String>,
+    /// UTC, as `YYYY-MM-DD HH:MM:SS`, or `None` if it never expires
+    pub expires_at: Option<String>,
+    /// The admin who made it. Scores set with the token are recorded as theirs, along
+    /// with the token.
+    pub created_by: Option<i64>,
+    pub created_by_email: Option<String>,
+    pub created_at: String,
+    pub last_used_at: Option<String>,
+}
+
+impl ApiTokens {
+    pub fn new(
+        name: String,
+        scopes: Vec<Scope>,
+        year_ids: Vec<String>,
+        event_ids: Vec<String>,
+        expires_at: Option<String>,
+    ) -> Self {
+        Self {
+            id: None,
+            name,
+            scopes,
+            year_ids,
+            event_ids,
+            expires_at,
+            created_by: None,
+            created_by_email: None,
+            created_at: String::new(),
+            last_used_at: None,
+        }
+    }
+
+    fn map_from_row(row: &Row) -> Result<Self, RusqliteError> {
+        let scopes: String = row.get(2)?;
+        let year_ids: String = row.get(3)?;
+        let event_ids: String = row.get(4)?;
+        Ok(Self {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            scopes: scopes.split_whitespace().filter_map(Scope::parse).collect(),
+            year_ids: split_ids(&year_ids),
+            event_ids: split_ids(&event_ids),
+            expires_at: row.get(5)?,
+            created_by: row.get(6)?,
+            created_by_email: row.get(7)?,
+            created_at: row.get(8)?,
+            last_used_at: row.get(9)?,
+        })
+    }
+
+    fn hash(token: &str) -> String {
+        Sha256::digest(token.as_bytes())
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+
+    /// Store a new token made by `created_by`, returning the token itself. It can't be
+    /// shown again.
+    pub async fn create(
+        self,
+        pool: &Pool,
+        created_by: Option<i64>,
+    ) -> Result<String, async_sqlite::Error> {
+        let token = format!(
+            "sds_{}{}",
+            uuid::Uuid::new_v4().simple(),
+            uuid::Uuid::new_v4().simple()
+        );
+        let hash = Self::hash(&token);
+        pool.conn(move |conn| {
+            debug!("Creating API Token {}", self.name);
+            conn.execute(
+                "INSERT INTO api_tokens(name, token_hash, scopes, year_ids, event_ids, expires_at, created_by)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7);",
+                (
+                    &self.name,
+                    &hash,
+                    self.scope_names(),
+                    self.year_ids.join(","),
+                    self.event_ids.join(","),
+                    &self.expires_at,
+                    created_by,
+                ),
+            )?;
+            Ok(())
+        })
+        .await?;
+        Ok(token)
+    }
+
+    /// The unexpired token matching `token`, noting that it's been used
+    pub async fn verify(pool: &Pool, token: &str) -> Result<Option<Self>, async_sqlite::Error> {
+        let hash = Self::hash(token);
+        pool.conn(move |conn| {
+            let token = conn
+                .query_row(
+                    &format!(
+                        "SELECT {COLUMNS} FROM api_tokens LEFT JOIN users ON users.id = created_by
+                            WHERE token_hash = ?1
+                                AND (expires_at IS NULL OR expires_at > CURRENT_TIMESTAMP)"
+                    ),
+                    [&hash],
+                    Self::map_from_row,
+                )
+                .optional()?;
+            if let Some(token) = &token {
+                conn.execute(
+                    "UPDATE api_tokens SET last_used_at = CURRENT_TIMESTAMP WHERE id = ?1;",
+                    [token.id],
+                )?;
+            }
+            Ok(token)
+        })
+        .await
+    }
+
+    /// e.g. "scores:write"
+    pub fn scope_names(&self) -> String {
+        self.scopes
+            .iter()
+            .map(|scope| scope.as_str())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Whether the token can act on the event with the scope
+    pub fn allows(&self, scope: Scope, event: &Events) -> bool {
+        self.scopes.contains(&scope)
+            && (self.year_ids.is_empty() || self.year_ids.contains(&event.year_id))
+            && (self.event_ids.is_empty()
+                || self.event_ids.contains(&event.id)
+                || self.event_ids.contains(&event.filter_key))
+    }
+
+    /// Revoke a token
+    pub async fn delete(pool: &Pool, id: i64) -> Result<(), async_sqlite::Error> {
+        pool.conn(move |conn| {
+            debug!("Deleting API Token with id {}", id);
+            conn.execute("DELETE FROM api_tokens WHERE id = ?1;", [id])?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Every token, newest first
+    pub async fn all(pool: &Pool) -> Result<Vec<Self>, async_sqlite::Error> {
+        pool.conn(move |conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {COLUMNS} FROM api_tokens LEFT JOIN users ON users.id = created_by
+                    ORDER BY api_tokens.id DESC"
+            ))?;
+            let token_iter = stmt.query_map([], Self::map_from_row)?;
+            let mut tokens = Vec::new();
+
+            for token in token_iter {
+                tokens.push(token?);
+            }
+            Ok(tokens)
+        })
+        .await
+    }
+}
+
+/// Read a comma separated list of ids, ignoring blanks
+pub fn split_ids(ids: &str) -> Vec<String> {
+    ids.split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{db::users::Users, test_harness};
+
+    use super::*;
+
+    fn token(expires_at: Option<&str>) -> ApiTokens {
+        ApiTokens::new(
+            "Timing".to_string(),
+            vec![Scope::ScoresWrite],
+            vec!["y9".to_string()],
+            vec![],
+            expires_at.map(str::to_string),
+        )
+    }
+
+    #[tokio::test]
+    async fn verify_test() {
+        let db = test_harness::setup_db("api_tokens_verify").await;
+        assert!(Users::new("example@example.com".to_string(), true, true)
+            .insert(&db)
+            .await
+            .is_ok());
+        let secret = token(None).create(&db, Some(1)).await.unwrap();
+        let expired = token(Some("2000-01-01 00:00:00"))
+            .create(&db, Some(1))
+            .await
+            .unwrap();
+
+        let verified = ApiTokens::verify(&db, &secret).await.unwrap().unwrap();
+        assert_eq!(verified.scopes, vec![Scope::ScoresWrite]);
+        assert_eq!(verified.year_ids, vec!["y9"]);
+        assert_eq!(
+            verified.created_by_email.as_deref(),
+            Some("example@example.com")
+        );
+        assert!(ApiTokens::verify(&db, &expired).await.unwrap().is_none());
+        assert!(ApiTokens::verify(&db, "sds_nope").await.unwrap().is_none());
+
+        let all = ApiTokens::all(&db).await.unwrap();
+        assert_eq!(all.len(), 2);
+        assert!(all.iter().all(|token| !token.created_at.is_empty()));
+        assert!(ApiTokens::delete(&db, verified.id.unwrap()).await.is_ok());
+        assert!(ApiTokens::verify(&db, &secret).await.unwrap().is_none());
+    }
+
+    #[test]
+    fn allows_test() {
+        let event = |id: &str, year_id: &str, filter_key: &str| {
+            Events::new(
+                id.to_string(),
+                filter_key.to_string(),
+                year_id.to_string(),
+                "boys".to_string(),
+                filter_key.to_string(),
+            )
+        };
+        let mut token = token(None);
+        assert!(token.allows(Scope::ScoresWrite, &event("y9-boys-60m", "y9", "60m")));
+        assert!(!token.allows(Scope::ScoresWrite, &event("y10-boys-60m", "y10", "60m")));
+
+        token.event_ids = vec!["60m".to_string(), "y9-boys-relay".to_string()];
+        assert!(token.allows(Scope::ScoresWrite, &event("y9-boys-60m", "y9", "60m")));
+        assert!(token.allows(Scope::ScoresWrite, &event("y9-boys-relay", "y9", "relay")));
+        assert!(!token.allows(Scope::ScoresWrite, &event("y9-boys-800m", "y9", "800m")));
+
+        token.scopes = vec![];
+        assert!(!token.allows(Scope::ScoresWrite, &event("y9-boys-60m", "y9", "60m")));
+    }
+
+    #[test]
+    fn split_ids_test() {
+        assert_eq!(split_ids(" y9, ,y10,"), vec!["y9", "y10"]);
+        assert!(split_ids("").is_empty());
+    }
+}
@@ -0,0 +1,147 @@
+use async_sqlite::rusqlite::{Connection, Error as RusqliteError};
+use async_sqlite::{rusqlite::Row, Pool};
+use log::debug;
+
+/// An event removed from the config, kept so its scores aren't lost
+#[derive(Clone, PartialEq, Debug)]
+pub struct ArchivedEvents {
+    pub id: i64,
+    pub event_id: String,
+    pub name: String,
+    pub year_id: String,
+    pub gender_id: String,
+    pub filter_key: String,
+    pub scores: String,
+    pub archived_at: String,
+}
+
+impl ArchivedEvents {
+    fn map_from_row(row: &Row) -> Result<Self, async_sqlite::Error> {
+        Ok(Self {
+            id: row.get(0)?,
+            event_id: row.get(1)?,
+            name: row.get(2)?,
+            year_id: row.get(3)?,
+            gender_id: row.get(4)?,
+            filter_key: row.get(5)?,
+            scores: row.get(6)?,
+            archived_at: row.get(7)?,
+        })
+    }
+
+    /// Move an event (and its scores) out of `events` and into `archived_events`.
+    /// Call it in a transaction, since it takes several statements.
+    pub fn archive_in(conn: &Connection, event_id: &str) -> Result<(), RusqliteError> {
+        debug!("Archiving Event with id {}", event_id);
+        conn.execute(
+            "INSERT INTO archived_events(event_id, name, year_id, gender_id, filter_key, scores)
+                SELECT id, name, year_id, gender_id, filter_key,
+                    (SELECT json_group_object(form_id, points) FROM event_scores WHERE event_id = events.id)
+                FROM events WHERE id = ?1;",
+            [event_id],
+        )?;
+        conn.execute("DELETE FROM event_scores WHERE event_id = ?1;", [event_id])?;
+        conn.execute("DELETE FROM performances WHERE event_id = ?1;", [event_id])?;
+        conn.execute("DELETE FROM event_entries WHERE event_id = ?1;", [event_id])?;
+        conn.execute("DELETE FROM events WHERE id = ?1;", [event_id])?;
+        Ok(())
+    }
+
+    pub async fn all(pool: &Pool) -> Result<Vec<Self>, async_sqlite::Error> {
+        pool.conn(move |conn| {
+            let mut stmt = conn.prepare("SELECT * FROM archived_events")?;
+            let event_iter = stmt
+                .query_map([], |row| Ok(Self::map_from_row(row).unwrap()))
+                .unwrap();
+            let mut events = Vec::new();
+
+            for event in event_iter {
+                events.push(event?);
+            }
+            Ok(events)
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        configurator::parser::{Form, Score},
+        db::{
+            event_scores::{EventScores, ScoreUpdate},
+            events::Events,
+            forms::Forms,
+            score_changes::ChangedBy,
+            years::Years,
+        },
+        test_harness,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn archive_test() {
+        let db = test_harness::setup_db("archived_events_archive").await;
+        assert!(Years::new("test".to_string(), "Test".to_string())
+            .insert(&db)
+            .await
+            .is_ok());
+        assert!(Events::new(
+            "test-test".to_string(),
+            "Test".to_string(),
+            "test".to_string(),
+            "mixed".to_string(),
+            "test".to_string()
+        )
+        .insert(&db)
+        .await
+        .is_ok());
+        assert!(Forms::sync(
+            &db,
+            vec![Form {
+                id: "w".to_string(),
+                name: "Winston".to_string(),
+                colour: "red".to_string(),
+            }]
+        )
+        .await
+        .is_ok());
+        assert!(EventScores::set_batch(
+            &db,
+            vec![ScoreUpdate {
+                event_id: "test-test".to_string(),
+                scores: vec![(
+                    "w".to_string(),
+                    Score {
+                        name: "1st".to_string(),
+                        value: 20,
+                        default: false,
+                    }
+                )],
+                performances: None,
+                entries: None,
+                revision: None
+            }],
+            ChangedBy::default()
+        )
+        .await
+        .is_ok());
+
+        assert!(db
+            .conn_mut(|conn| {
+                let tx = conn.transaction()?;
+                ArchivedEvents::archive_in(&tx, "test-test")?;
+                tx.commit()
+            })
+            .await
+            .is_ok());
+        assert_eq!(Events::all(&db).await.unwrap().len(), 0);
+
+        let archived = ArchivedEvents::all(&db).await.unwrap();
+        assert_eq!(archived.len(), 1);
+        assert_eq!(archived[0].event_id, "test-test");
+        assert_eq!(archived[0].scores, "{\"w\":20}");
+        assert!(EventScores::all(&db).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,221 @@
+use async_sqlite::rusqlite::Error as RusqliteError;
+use async_sqlite::{rusqlite::Row, Pool};
+use log::debug;
+
+use crate::{configurator::parser::Configuration, csv_import};
+
+/// A student who competes for their form
+#[derive(Clone, PartialEq, Debug)]
+pub struct Competitors {
+    pub id: Option<i64>,
+    pub name: String,
+    pub form_id: String,
+    pub year_id: String,
+    pub gender_id: String,
+}
+
+impl Competitors {
+    pub fn new(name: String, form_id: String, year_id: String, gender_id: String) -> Self {
+        Self {
+            id: None,
+            name,
+            form_id,
+            year_id,
+            gender_id,
+        }
+    }
+
+    fn map_from_row(row: &Row) -> Result<Self, RusqliteError> {
+        Ok(Self {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            form_id: row.get(2)?,
+            year_id: row.get(3)?,
+            gender_id: row.get(4)?,
+        })
+    }
+
+    /// Read competitors from a CSV with `name`, `form`, `year` and `gender` columns, in
+    /// any order. Forms and years can be given by id or name. Returns a message for
+    /// every row that doesn't match the config.
+    pub fn from_csv(config: &Configuration, csv: &str) -> Result<Vec<Self>, Vec<String>> {
+        let rows = csv_import::read(csv, &["name", "form", "year", "gender"])?;
+
+        let mut competitors = vec![];
+        let mut errors = vec![];
+        for row in rows {
+            let [name, form, year, gender] = row.values.as_slice() else {
+                unreachable!("read returns the columns asked for")
+            };
+            let form_id = config
+                .forms
+                .iter()
+                .find(|f| &f.id == form || f.name.eq_ignore_ascii_case(form))
+                .map(|f| f.id.clone());
+            let year_id = config
+                .years
+                .iter()
+                .find(|y| &y.id == year || y.name.eq_ignore_ascii_case(year))
+                .map(|y| y.id.clone());
+            let gender_id = config
+                .genders
+                .iter()
+                .find(|g| g.eq_ignore_ascii_case(gender))
+                .cloned();
+
+            let mut problems = vec![];
+            if name.is_empty() {
+                problems.push("the name is empty".to_string());
+            }
+            if form_id.is_none() {
+                problems.push(format!("unknown form \"{form}\""));
+            }
+            if year_id.is_none() {
+                problems.push(format!("unknown year \"{year}\""));
+            }
+            if gender_id.is_none() {
+                problems.push(format!("unknown gender \"{gender}\""));
+            }
+            match (form_id, year_id, gender_id) {
+                (Some(form_id), Some(year_id), Some(gender_id)) if problems.is_empty() => {
+                    competitors.push(Self::new(name.clone(), form_id, year_id, gender_id))
+                }
+                _ => errors.push(format!("line {}: {}", row.line, problems.join(", "))),
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(competitors)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Add competitors, updating the gender of any already registered with the same
+    /// name, form and year. Returns the number of rows imported.
+    pub async fn import(pool: &Pool, competitors: Vec<Self>) -> Result<usize, async_sqlite::Error> {
+        pool.conn_mut(move |conn| {
+            debug!("Importing {} Competitors", competitors.len());
+            let tx = conn.transaction()?;
+            for competitor in competitors.iter() {
+                tx.execute(
+                    "INSERT INTO competitors(name, form_id, year_id, gender_id) VALUES (?1, ?2, ?3, ?4)
+                        ON CONFLICT(name, form_id, year_id) DO UPDATE SET gender_id = excluded.gender_id;",
+                    [
+                        &competitor.name,
+                        &competitor.form_id,
+                        &competitor.year_id,
+                        &competitor.gender_id,
+                    ],
+                )?;
+            }
+            tx.commit()?;
+            Ok(competitors.len())
+        })
+        .await
+    }
+
+    /// Remove a competitor, and every event they were entered for
+    pub async fn delete(pool: &Pool, id: i64) -> Result<(), async_sqlite::Error> {
+        pool.conn(move |conn| {
+            debug!("Deleting Competitor with id {}", id);
+            conn.execute("DELETE FROM competitors WHERE id = ?1;", [id])?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Every competitor, by year, form then name
+    pub async fn all(pool: &Pool) -> Result<Vec<Self>, async_sqlite::Error> {
+        pool.conn(move |conn| {
+            let mut stmt = conn.prepare(
+                "SELECT id, name, form_id, year_id, gender_id FROM competitors
+                    ORDER BY year_id, form_id, name",
+            )?;
+            let competitor_iter = stmt.query_map([], Self::map_from_row)?;
+            let mut competitors = Vec::new();
+
+            for competitor in competitor_iter {
+                competitors.push(competitor?);
+            }
+            Ok(competitors)
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{db::forms::Forms, test_harness};
+
+    use super::*;
+    use crate::test_harness::config;
+
+    #[test]
+    fn from_csv_test() {
+        let competitors = Competitors::from_csv(
+            &config(),
+            "Name,Year,Form,Gender\nAda Lovelace,Year 9,Turing,girls\n\"Smith, Sam\", y10 ,w,Boys\n",
+        )
+        .unwrap();
+        assert_eq!(
+            competitors,
+            vec![
+                Competitors::new(
+                    "Ada Lovelace".to_string(),
+                    "t".to_string(),
+                    "y9".to_string(),
+                    "girls".to_string()
+                ),
+                Competitors::new(
+                    "Smith, Sam".to_string(),
+                    "w".to_string(),
+                    "y10".to_string(),
+                    "boys".to_string()
+                ),
+            ]
+        );
+
+        assert_eq!(
+            Competitors::from_csv(&config(), "name,form,year\nAda,t,y9\n").unwrap_err(),
+            vec!["missing a gender column"]
+        );
+        assert_eq!(
+            Competitors::from_csv(
+                &config(),
+                "name,form,year,gender\nAda,t,y9,girls\n,x,y7,girls\n"
+            )
+            .unwrap_err(),
+            vec!["line 3: the name is empty, unknown form \"x\", unknown year \"y7\""]
+        );
+    }
+
+    #[tokio::test]
+    async fn import_test() {
+        let db = test_harness::setup_db("competitors_import").await;
+        assert!(Forms::sync(&db, config().forms).await.is_ok());
+        let ada = |gender: &str| {
+            Competitors::new(
+                "Ada".to_string(),
+                "t".to_string(),
+                "y9".to_string(),
+                gender.to_string(),
+            )
+        };
+        assert_eq!(
+            Competitors::import(&db, vec![ada("boys")]).await.unwrap(),
+            1
+        );
+        assert_eq!(
+            Competitors::import(&db, vec![ada("girls")]).await.unwrap(),
+            1
+        );
+
+        let all = Competitors::all(&db).await.unwrap();
+        assert_eq!(all.len(), 1);
+        assert_eq!(all[0].gender_id, "girls");
+
+        assert!(Competitors::delete(&db, all[0].id.unwrap()).await.is_ok());
+        assert!(Competitors::all(&db).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,184 @@
+use async_sqlite::rusqlite::{Connection, Error as RusqliteError};
+use async_sqlite::{rusqlite::Row, Pool};
+use log::debug;
+use sha2::{Digest, Sha256};
+
+/// A record of a config.yaml that has been applied to the database
+#[derive(Clone, PartialEq, Debug)]
+pub struct ConfigVersions {
+    pub id: Option<i64>,
+    pub version: String,
+    pub applied_at: Option<String>,
+    /// SHA-256 of the raw YAML, hex encoded
+    pub yaml_hash: String,
+    /// The generated plan, serialised as JSON
+    pub plan: String,
+}
+
+impl ConfigVersions {
+    pub fn new(version: String, yaml: &str, plan: String) -> Self {
+        Self {
+            id: None,
+            version,
+            applied_at: None,
+            yaml_hash: Self::hash_yaml(yaml),
+            plan,
+        }
+    }
+
+    pub fn hash_yaml(yaml: &str) -> String {
+        Sha256::digest(yaml.as_bytes())
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+
+    fn map_from_row(row: &Row) -> Result<Self, RusqliteError> {
+        Ok(Self {
+            id: row.get(0)?,
+            version: row.get(1)?,
+            applied_at: row.get(2)?,
+            yaml_hash: row.get(3)?,
+            plan: row.get(4)?,
+        })
+    }
+
+    /// Tests set up rows with this; `run::apply` uses `insert_in` inside its transaction
+    #[cfg(test)]
+    pub async fn insert(self, pool: &Pool) -> Result<(), async_sqlite::Error> {
+        pool.conn(move |conn| self.insert_in(conn)).await
+    }
+
+    /// Record the version on `conn`, which can be the transaction that applied it
+    pub fn insert_in(&self, conn: &Connection) -> Result<(), RusqliteError> {
+        debug!("Recording applied config version {}", self.version);
+        conn.execute(
+            "INSERT INTO config_versions(version, yaml_hash, plan) VALUES (?1, ?2, ?3);",
+            [&self.version, &self.yaml_hash, &self.plan],
+        )?;
+        Ok(())
+    }
+
+    /// The most recently applied config, if any
+    pub async fn latest(pool: &Pool) -> Result<Option<Self>, async_sqlite::Error> {
+        pool.conn(move |conn| {
+            let mut stmt =
+                conn.prepare("SELECT * FROM config_versions ORDER BY id DESC LIMIT 1")?;
+            let mut rows = stmt.query([])?;
+
+            if let Some(row) = rows.next()? {
+                Ok(Some(Self::map_from_row(row)?))
+            } else {
+                Ok(None)
+            }
+        })
+        .await
+    }
+
+    pub async fn all(pool: &Pool) -> Result<Vec<Self>, async_sqlite::Error> {
+        pool.conn(move |conn| {
+            let mut stmt = conn.prepare("SELECT * FROM config_versions ORDER BY id DESC")?;
+            let version_iter = stmt
+                .query_map([], |row| Ok(Self::map_from_row(row).unwrap()))
+                .unwrap();
+            let mut versions = Vec::new();
+
+            for version in version_iter {
+                versions.push(version?);
+            }
+            Ok(versions)
+        })
+        .await
+    }
+
+    pub async fn find_by_id(id: i64, pool: &Pool) -> Result<Option<Self>, async_sqlite::Error> {
+        pool.conn(move |conn| {
+            let mut stmt = conn.prepare("SELECT * FROM config_versions WHERE id = ?1")?;
+            let mut rows = stmt.query([id])?;
+
+            if let Some(row) = rows.next()? {
+                Ok(Some(Self::map_from_row(row)?))
+            } else {
+                Ok(None)
+            }
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_harness;
+
+    use super::*;
+
+    #[test]
+    fn is_constructed_properly() {
+        assert_eq!(
+            ConfigVersions::new("2025".to_string(), "version: 2025", "{}".to_string()),
+            ConfigVersions {
+                id: None,
+                version: "2025".to_string(),
+                applied_at: None,
+                yaml_hash: "5b7f9bb20eeab6d479640949b3ad163526ef40fe2b994a55b7002f88dfce4de9"
+                    .to_string(),
+                plan: "{}".to_string()
+            }
+        )
+    }
+
+    #[tokio::test]
+    async fn latest_test() {
+        let db = test_harness::setup_db("config_versions_latest").await;
+        assert!(ConfigVersions::latest(&db).await.unwrap().is_none());
+        assert!(
+            ConfigVersions::new("2024".to_string(), "version: 2024", "{}".to_string())
+                .insert(&db)
+                .await
+                .is_ok()
+        );
+        assert!(
+            ConfigVersions::new("2025".to_string(), "version: 2025", "{}".to_string())
+                .insert(&db)
+                .await
+                .is_ok()
+        );
+        let latest = ConfigVersions::latest(&db).await.unwrap().unwrap();
+        assert_eq!(latest.id, Some(2));
+        assert_eq!(latest.version, "2025");
+        assert!(latest.applied_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn all_test() {
+        let db = test_harness::setup_db("config_versions_all").await;
+        assert!(
+            ConfigVersions::new("2024".to_string(), "version: 2024", "{}".to_string())
+                .insert(&db)
+                .await
+                .is_ok()
+        );
+        assert!(
+            ConfigVersions::new("2025".to_string(), "version: 2025", "{}".to_string())
+                .insert(&db)
+                .await
+                .is_ok()
+        );
+        let versions = ConfigVersions::all(&db).await.unwrap();
+        assert_eq!(versions.len(), 2);
+        assert_eq!(versions[0].version, "2025");
+    }
+
+    #[tokio::test]
+    async fn find_by_id_test() {
+        let db = test_harness::setup_db("config_versions_find_by_id").await;
+        assert!(
+            ConfigVersions::new("2025".to_string(), "version: 2025", "{}".to_string())
+                .insert(&db)
+                .await
+                .is_ok()
+        );
+        assert!(ConfigVersions::find_by_id(1, &db).await.unwrap().is_some());
+        assert!(ConfigVersions::find_by_id(2, &db).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,126 @@
+use async_sqlite::rusqlite::Error as RusqliteError;
+use async_sqlite::{
+    rusqlite::{Row, Transaction},
+    Pool,
+};
+use log::debug;
+
+/// The competitor who placed for a form in an event
+#[derive(Clone, PartialEq, Debug)]
+pub struct EventEntries {
+    pub event_id: String,
+    pub form_id: String,
+    pub competitor_id: i64,
+    pub competitor_name: String,
+}
+
+impl EventEntries {
+    fn map_from_row(row: &Row) -> Result<Self, RusqliteError> {
+        Ok(Self {
+            event_id: row.get(0)?,
+            form_id: row.get(1)?,
+            competitor_id: row.get(2)?,
+            competitor_name: row.get(3)?,
+        })
+    }
+
+    /// Replace every entry for an event with (form id, competitor id) pairs, as part of
+    /// a larger transaction
+    pub fn replace(
+        tx: &Transaction,
+        event_id: &str,
+        entries: Vec<(String, i64)>,
+    ) -> Result<(), RusqliteError> {
+        debug!("Setting Entries for Event with id {}", event_id);
+        tx.execute("DELETE FROM event_entries WHERE event_id = ?1;", [event_id])?;
+        for (form_id, competitor_id) in entries {
+            tx.execute(
+                "INSERT INTO event_entries(event_id, form_id, competitor_id) VALUES (?1, ?2, ?3);",
+                (event_id, form_id, competitor_id),
+            )?;
+        }
+        Ok(())
+    }
+
+    pub async fn all(pool: &Pool) -> Result<Vec<Self>, async_sqlite::Error> {
+        pool.conn(move |conn| {
+            let mut stmt = conn.prepare(
+                "SELECT event_id, event_entries.form_id, competitor_id, competitors.name
+                    FROM event_entries JOIN competitors ON competitors.id = event_entries.competitor_id",
+            )?;
+            let entry_iter = stmt.query_map([], Self::map_from_row)?;
+            let mut entries = Vec::new();
+
+            for entry in entry_iter {
+                entries.push(entry?);
+            }
+            Ok(entries)
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        configurator::parser::Form,
+        db::{competitors::Competitors, events::Events, forms::Forms, years::Years},
+        test_harness,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn replace_test() {
+        let db = test_harness::setup_db("event_entries_replace").await;
+        assert!(Years::new("y9".to_string(), "Year 9".to_string())
+            .insert(&db)
+            .await
+            .is_ok());
+        assert!(Events::new(
+            "y9-girls-60m".to_string(),
+            "60m".to_string(),
+            "y9".to_string(),
+            "girls".to_string(),
+            "60m".to_string()
+        )
+        .insert(&db)
+        .await
+        .is_ok());
+        let form = Form {
+            id: "t".to_string(),
+            name: "Turing".to_string(),
+            colour: "lightblue".to_string(),
+        };
+        assert!(Forms::sync(&db, vec![form]).await.is_ok());
+        let competitors = ["Ada", "Grace"]
+            .iter()
+            .map(|name| {
+                Competitors::new(
+                    name.to_string(),
+                    "t".to_string(),
+                    "y9".to_string(),
+                    "girls".to_string(),
+                )
+            })
+            .collect();
+        assert!(Competitors::import(&db, competitors).await.is_ok());
+
+        let replace = |competitor_id: i64| {
+            db.conn_mut(move |conn| {
+                let tx = conn.transaction()?;
+                EventEntries::replace(&tx, "y9-girls-60m", vec![("t".to_string(), competitor_id)])?;
+                tx.commit()
+            })
+        };
+        assert!(replace(1).await.is_ok());
+        assert!(replace(2).await.is_ok());
+        let entries = EventEntries::all(&db).await.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].competitor_name, "Grace");
+
+        // Removing a competitor removes their entries
+        assert!(Competitors::delete(&db, 2).await.is_ok());
+        assert!(EventEntries::all(&db).await.unwrap().is_empty());
+    }
+}
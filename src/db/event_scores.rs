@@ -0,0 +1,691 @@
+use std::collections::{BTreeMap, HashMap};
+
+use async_sqlite::rusqlite::Error as RusqliteError;
+use async_sqlite::{
+    rusqlite::{OptionalExtension, Row, Transaction},
+    Pool,
+};
+use log::debug;
+
+use crate::{
+    configurator::parser::{Configuration, Score},
+    db::{
+        event_entries::EventEntries,
+        performances::Performances,
+        score_changes::{ChangedBy, RestorePoint, ScoreChanges},
+    },
+    scoring,
+};
+
+/// New scores for one event, and the performances they were placed from if it's measured
+#[derive(Clone, PartialEq, Debug)]
+pub struct ScoreUpdate {
+    pub event_id: String,
+    /// (form id, score awarded) for every form
+    pub scores: Vec<(String, Score)>,
+    pub performances: Option<Vec<(String, f64)>>,
+    /// (form id, competitor id) of who placed for each form, or `None` to leave them as they are
+    pub entries: Option<Vec<(String, i64)>>,
+    /// The event revision the scores were set against, or `None` to save regardless
+    pub revision: Option<i64>,
+}
+
+/// Whether a batch of score updates was saved
+#[derive(Clone, PartialEq, Debug)]
+pub enum BatchOutcome {
+    Saved,
+    /// Nothing was saved, because these events changed since their updates were made
+    Stale(Vec<String>),
+}
+
+/// The score a form was awarded in an event
+#[derive(Clone, PartialEq, Debug)]
+pub struct EventScores {
+    pub event_id: String,
+    pub form_id: String,
+    /// Name of the awarded score (e.g. "1st"), as configured when it was awarded
+    pub score_name: String,
+    /// Points before the event's multiplier is applied
+    pub points: i64,
+    pub updated_at: String,
+    /// Id of the user who set the score, if it wasn't imported
+    pub updated_by: Option<i64>,
+    /// The API token the score was set with, if it wasn't set by hand
+    pub updated_by_token: Option<i64>,
+}
+
+impl EventScores {
+    fn map_from_row(row: &Row) -> Result<Self, RusqliteError> {
+        Ok(Self {
+            event_id: row.get(0)?,
+            form_id: row.get(1)?,
+            score_name: row.get(2)?,
+            points: row.get(3)?,
+            updated_at: row.get(4)?,
+            updated_by: row.get(5)?,
+            updated_by_token: row.get(6)?,
+        })
+    }
+
+    /// Replace the scores (and performances, where given) of every event in the batch,
+    /// recording each form whose score changes in `score_changes`.
+    /// Runs in a single transaction, so either every update lands or none do. Updates
+    /// made against an old revision of their event make the whole batch stale.
+    pub async fn set_batch(
+        pool: &Pool,
+        updates: Vec<ScoreUpdate>,
+        updated_by: ChangedBy,
+    ) -> Result<BatchOutcome, async_sqlite::Error> {
+        pool.conn_mut(move |conn| {
+            let tx = conn.transaction()?;
+            let mut stale = vec![];
+            for update in updates.iter() {
+                let Some(revision) = update.revision else {
+                    continue;
+                };
+                let current: Option<i64> = tx
+                    .query_row(
+                        "SELECT revision FROM events WHERE id = ?1",
+                        [&update.event_id],
+                        |row| row.get(0),
+                    )
+                    .optional()?;
+                if current.is_some_and(|current| current != revision) {
+                    debug!(
+                        "Event with id {} changed since revision {}",
+                        update.event_id, revision
+                    );
+                    stale.push(update.event_id.clone());
+                }
+            }
+            if !stale.is_empty() {
+                return Ok(BatchOutcome::Stale(stale));
+            }
+            Self::apply(&tx, updates, updated_by)?;
+            tx.commit()?;
+            Ok(BatchOutcome::Saved)
+        })
+        .await
+    }
+
+    /// Put events' scores back to how they were at a point in the `score_changes` history.
+    /// Performances aren't part of the history, so they're left as they are.
+    /// Returns the ids of the events whose scores were restored.
+    pub async fn restore(
+        pool: &Pool,
+        point: RestorePoint,
+        updated_by: Option<i64>,
+    ) -> Result<Vec<String>, async_sqlite::Error> {
+        pool.conn_mut(move |conn| {
+            let tx = conn.transaction()?;
+            let mut events: BTreeMap<String, HashMap<String, (String, i64)>> = BTreeMap::new();
+            for target in ScoreChanges::restore_targets(&tx, &point)? {
+                let event_id = target.event_id;
+                if !events.contains_key(&event_id) {
+                    let exists = tx
+                        .prepare("SELECT 1 FROM events WHERE id = ?1")?
+                        .exists([&event_id])?;
+                    if !exists {
+                        debug!("Not restoring archived Event with id {}", event_id);
+                        continue;
+                    }
+                    events.insert(event_id.clone(), Self::current(&tx, &event_id)?);
+                }
+                let scores = events.get_mut(&event_id).expect("inserted above");
+                match target.score {
+                    Some(score) => scores.insert(target.form_id, score),
+                    None => scores.remove(&target.form_id),
+                };
+            }
+
+            let updates: Vec<ScoreUpdate> = events
+                .into_iter()
+                .map(|(event_id, scores)| ScoreUpdate {
+                    event_id,
+                    scores: scores
+                        .into_iter()
+                        .map(|(form_id, (name, value))| {
+                            let score = Score {
+                                name,
+                                value,
+                                default: false,
+                            };
+                            (form_id, score)
+                        })
+                        .collect(),
+                    performances: None,
+                    entries: None,
+                    revision: None,
+                })
+                .collect();
+            let restored = updates
+                .iter()
+                .map(|update| update.event_id.clone())
+                .collect();
+            Self::apply(&tx, updates, ChangedBy::user(updated_by))?;
+            tx.commit()?;
+            Ok(restored)
+        })
+        .await
+    }
+
+    /// The (score name, points) currently awarded to each form in an event
+    fn current(
+        tx: &Transaction,
+        event_id: &str,
+    ) -> Result<HashMap<String, (String, i64)>, RusqliteError> {
+        let mut stmt =
+            tx.prepare("SELECT form_id, score_name, points FROM event_scores WHERE event_id = ?1")?;
+        let rows = stmt.query_map([event_id], |row| {
+            Ok((row.get(0)?, (row.get(1)?, row.get(2)?)))
+        })?;
+        rows.collect()
+    }
+
+    fn apply(
+        tx: &Transaction,
+        updates: Vec<ScoreUpdate>,
+        updated_by: ChangedBy,
+    ) -> Result<(), RusqliteError> {
+        for update in updates {
+            debug!("Setting Scores for Event with id {}", update.event_id);
+            let mut before = Self::current(tx, &update.event_id)?;
+            for (form_id, score) in update.scores.iter() {
+                let after = (score.name.clone(), score.value);
+                let previous = before.remove(form_id);
+                if previous.as_ref() != Some(&after) {
+                    ScoreChanges::record(
+                        tx,
+                        &update.event_id,
+                        form_id,
+                        previous,
+                        Some(after),
+                        updated_by,
+                    )?;
+                }
+            }
+            for (form_id, previous) in before {
+                ScoreChanges::record(
+                    tx,
+                    &update.event_id,
+                    &form_id,
+                    Some(previous),
+                    None,
+                    updated_by,
+                )?;
+            }
+            tx.execute(
+                "DELETE FROM event_scores WHERE event_id = ?1;",
+                [&update.event_id],
+            )?;
+            for (form_id, score) in update.scores {
+                tx.execute(
+                    "INSERT INTO event_scores(event_id, form_id, score_name, points, updated_by, updated_by_token)
+                        VALUES (?1, ?2, ?3, ?4, ?5, ?6);",
+                    (
+                        &update.event_id,
+                        form_id,
+                        score.name,
+                        score.value,
+                        updated_by.user_id,
+                        updated_by.token_id,
+                    ),
+                )?;
+            }
+            if let Some(performances) = update.performances {
+                Performances::replace(tx, &update.event_id, performances)?;
+            }
+            if let Some(entries) = update.entries {
+                EventEntries::replace(tx, &update.event_id, entries)?;
+            }
+            // Saving scores for an event that hasn't started means it has
+            tx.execute(
+                "UPDATE events SET revision = revision + 1,
+                        status = CASE status WHEN 'scheduled' THEN 'in_progress' ELSE status END
+                    WHERE id = ?1;",
+                [&update.event_id],
+            )?;
+        }
+        Ok(())
+    }
+
+    pub async fn all(pool: &Pool) -> Result<Vec<Self>, async_sqlite::Error> {
+        pool.conn(move |conn| {
+            let mut stmt = conn.prepare("SELECT * FROM event_scores")?;
+            let score_iter = stmt.query_map([], Self::map_from_row)?;
+            let mut scores = Vec::new();
+
+            for score in score_iter {
+                scores.push(score?);
+            }
+            Ok(scores)
+        })
+        .await
+    }
+
+    /// Move the JSON `events.scores` column older versions stored scores in into
+    /// `event_scores`, then drop it. The blobs only held points, so score names are
+    /// looked up in the config. Returns the number of scores imported.
+    pub async fn import_blobs(
+        pool: &Pool,
+        config: &Configuration,
+    ) -> Result<usize, async_sqlite::Error> {
+        let config = config.clone();
+        pool.conn_mut(move |conn| {
+            let has_blobs = conn
+                .prepare("SELECT 1 FROM pragma_table_info('events') WHERE name = 'scores'")?
+                .exists([])?;
+            if !has_blobs {
+                return Ok(0);
+            }
+
+            let tx = conn.transaction()?;
+            let blobs = {
+                let mut stmt = tx.prepare("SELECT id, scoring, scores FROM events")?;
+                let rows = stmt.query_map([], |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, Option<String>>(1)?,
+                        row.get::<_, String>(2)?,
+                    ))
+                })?;
+                rows.collect::<Result<Vec<_>, _>>()?
+            };
+
+            let mut imported = 0;
+            for (event_id, scheme, blob) in blobs {
+                let scores = config.scores_for(scheme.as_deref());
+                let points = scoring::parse_points(&blob);
+                // Older versions filled every unscored event with placeholder zeroes
+                let fallback = scoring::default_score(scores).value;
+                if points
+                    .iter()
+                    .all(|(_, points)| *points == 0 || *points == fallback)
+                {
+                    debug!("Skipping unscored Event with id {}", event_id);
+                    continue;
+                }
+                for (form_id, points) in points {
+                    let score_name = scores
+                        .iter()
+                        .find(|score| score.value == points)
+                        .map(|score| score.name.clone())
+                        .unwrap_or_else(|| points.to_string());
+                    let (name, colour) = config
+                        .forms
+                        .iter()
+                        .find(|form| form.id == form_id)
+                        .map(|form| (form.name.clone(), form.colour.clone()))
+                        .unwrap_or_else(|| (form_id.clone(), String::new()));
+                    tx.execute(
+                        "INSERT OR IGNORE INTO forms(id, name, colour) VALUES (?1, ?2, ?3);",
+                        [form_id.clone(), name, colour],
+                    )?;
+                    tx.execute(
+                        "INSERT OR REPLACE INTO event_scores(event_id, form_id, score_name, points)
+                            VALUES (?1, ?2, ?3, ?4);",
+                        (event_id.clone(), form_id, score_name, points),
+                    )?;
+                    imported += 1;
+                }
+            }
+            tx.execute("ALTER TABLE events DROP COLUMN scores;", [])?;
+            tx.commit()?;
+            Ok(imported)
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        configurator::parser::Form,
+        db::{
+            events::{EventStatus, Events},
+            forms::Forms,
+            years::Years,
+        },
+        test_harness::{self, ConfigBuilder},
+    };
+
+    use super::*;
+
+    async fn setup(name: &str) -> Pool {
+        let db = test_harness::setup_db(name).await;
+        assert!(Years::new("test".to_string(), "Test".to_string())
+            .insert(&db)
+            .await
+            .is_ok());
+        assert!(Events::new(
+            "test-test".to_string(),
+            "Test".to_string(),
+            "test".to_string(),
+            "mixed".to_string(),
+            "test".to_string()
+        )
+        .insert(&db)
+        .await
+        .is_ok());
+        assert!(Forms::sync(
+            &db,
+            ["w", "e"]
+                .iter()
+                .map(|id| Form {
+                    id: id.to_string(),
+                    name: id.to_string(),
+                    colour: "red".to_string(),
+                })
+                .collect()
+        )
+        .await
+        .is_ok());
+        db
+    }
+
+    fn score(name: &str, value: i64) -> Score {
+        Score {
+            name: name.to_string(),
+            value,
+            default: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn set_batch_test() {
+        let db = setup("event_scores_set_batch").await;
+        assert!(EventScores::set_batch(
+            &db,
+            vec![ScoreUpdate {
+                event_id: "test-test".to_string(),
+                scores: vec![
+                    ("w".to_string(), score("1st", 20)),
+                    ("e".to_string(), score("2nd", 15))
+                ],
+                performances: None,
+                entries: None,
+                revision: None
+            }],
+            ChangedBy::default()
+        )
+        .await
+        .is_ok());
+        assert!(EventScores::set_batch(
+            &db,
+            vec![ScoreUpdate {
+                event_id: "test-test".to_string(),
+                scores: vec![("e".to_string(), score("1st", 20))],
+                performances: None,
+                entries: None,
+                revision: None
+            }],
+            ChangedBy::default()
+        )
+        .await
+        .is_ok());
+        let scores = EventScores::all(&db).await.unwrap();
+        assert_eq!(scores.len(), 1);
+        assert_eq!(scores[0].form_id, "e");
+        assert_eq!(scores[0].score_name, "1st");
+        assert_eq!(scores[0].points, 20);
+
+        let changes = ScoreChanges::r#where(&db, Default::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            changes
+                .iter()
+                .map(|change| (change.form_id.as_str(), change.before(), change.after()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("w", "1st (20)".to_string(), "-".to_string()),
+                ("e", "2nd (15)".to_string(), "1st (20)".to_string()),
+                ("e", "-".to_string(), "2nd (15)".to_string()),
+                ("w", "-".to_string(), "1st (20)".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn set_batch_is_all_or_nothing_test() {
+        let db = setup("event_scores_set_batch_all_or_nothing").await;
+        assert!(EventScores::set_batch(
+            &db,
+            vec![
+                ScoreUpdate {
+                    event_id: "test-test".to_string(),
+                    scores: vec![("w".to_string(), score("1st", 20))],
+                    performances: Some(vec![("w".to_string(), 8.4)]),
+                    entries: None,
+                    revision: None
+                },
+                ScoreUpdate {
+                    event_id: "test-test".to_string(),
+                    scores: vec![("nope".to_string(), score("1st", 20))],
+                    performances: None,
+                    entries: None,
+                    revision: None
+                }
+            ],
+            ChangedBy::default()
+        )
+        .await
+        .is_err());
+        assert!(EventScores::all(&db).await.unwrap().is_empty());
+        assert!(Performances::all(&db).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn set_batch_refuses_stale_revisions_test() {
+        let db = setup("event_scores_set_batch_stale").await;
+        let set = |points: i64, revision: Option<i64>| ScoreUpdate {
+            event_id: "test-test".to_string(),
+            scores: vec![("w".to_string(), score("1st", points))],
+            performances: None,
+            entries: None,
+            revision,
+        };
+        assert_eq!(
+            EventScores::set_batch(&db, vec![set(20, Some(0))], ChangedBy::default())
+                .await
+                .unwrap(),
+            BatchOutcome::Saved
+        );
+        let event = Events::all(&db).await.unwrap()[0].clone();
+        assert_eq!(event.revision, 1);
+        assert_eq!(event.status, EventStatus::InProgress);
+
+        // A second scorer who loaded the page at revision 0
+        assert_eq!(
+            EventScores::set_batch(&db, vec![set(15, Some(0))], ChangedBy::default())
+                .await
+                .unwrap(),
+            BatchOutcome::Stale(vec!["test-test".to_string()])
+        );
+        assert_eq!(EventScores::all(&db).await.unwrap()[0].points, 20);
+        assert_eq!(Events::all(&db).await.unwrap()[0].revision, 1);
+
+        assert_eq!(
+            EventScores::set_batch(&db, vec![set(15, None)], ChangedBy::default())
+                .await
+                .unwrap(),
+            BatchOutcome::Saved
+        );
+        assert_eq!(Events::all(&db).await.unwrap()[0].revision, 2);
+    }
+
+    #[tokio::test]
+    async fn restore_test() {
+        let db = setup("event_scores_restore").await;
+        let set = |w: Score, e: Score| ScoreUpdate {
+            event_id: "test-test".to_string(),
+            scores: vec![("w".to_string(), w), ("e".to_string(), e)],
+            performances: None,
+            entries: None,
+            revision: None,
+        };
+        assert!(EventScores::set_batch(
+            &db,
+            vec![set(score("1st", 20), score("2nd", 15))],
+            ChangedBy::default()
+        )
+        .await
+        .is_ok());
+        assert!(EventScores::set_batch(
+            &db,
+            vec![set(score("2nd", 15), score("1st", 20))],
+            ChangedBy::default()
+        )
+        .await
+        .is_ok());
+        let points = |scores: Vec<EventScores>| {
+            let mut points: Vec<(String, i64)> = scores
+                .into_iter()
+                .map(|score| (score.form_id, score.points))
+                .collect();
+            points.sort();
+            points
+        };
+
+        // Change 3 is the first of the second submission
+        assert_eq!(
+            EventScores::restore(&db, RestorePoint::BeforeChange(3), None)
+                .await
+                .unwrap(),
+            vec!["test-test".to_string()]
+        );
+        assert_eq!(
+            points(EventScores::all(&db).await.unwrap()),
+            vec![("e".to_string(), 15), ("w".to_string(), 20)]
+        );
+        // The revert is itself recorded
+        assert_eq!(
+            ScoreChanges::r#where(&db, Default::default())
+                .await
+                .unwrap()
+                .len(),
+            6
+        );
+
+        assert_eq!(
+            EventScores::restore(
+                &db,
+                RestorePoint::AsOf("2999-01-01T00:00".to_string()),
+                None
+            )
+            .await
+            .unwrap()
+            .len(),
+            0
+        );
+        assert_eq!(
+            EventScores::restore(
+                &db,
+                RestorePoint::AsOf("2000-01-01T00:00".to_string()),
+                None
+            )
+            .await
+            .unwrap()
+            .len(),
+            1
+        );
+        assert!(EventScores::all(&db).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn restore_keeps_performances_test() {
+        let db = setup("event_scores_restore_keeps_performances").await;
+        let set = |w: Score, e: Score, performances: Vec<(String, f64)>| ScoreUpdate {
+            event_id: "test-test".to_string(),
+            scores: vec![("w".to_string(), w), ("e".to_string(), e)],
+            performances: Some(performances),
+            entries: None,
+            revision: None,
+        };
+        let timed = vec![("w".to_string(), 8.1), ("e".to_string(), 8.4)];
+        assert!(EventScores::set_batch(
+            &db,
+            vec![set(score("1st", 20), score("2nd", 15), timed.clone())],
+            ChangedBy::default()
+        )
+        .await
+        .is_ok());
+        assert!(EventScores::set_batch(
+            &db,
+            vec![set(score("2nd", 15), score("1st", 20), timed)],
+            ChangedBy::default()
+        )
+        .await
+        .is_ok());
+
+        assert!(
+            EventScores::restore(&db, RestorePoint::BeforeChange(3), None)
+                .await
+                .is_ok()
+        );
+        let mut performances: Vec<(String, f64)> = Performances::all(&db)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|performance| (performance.form_id, performance.value))
+            .collect();
+        performances.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            performances,
+            vec![("e".to_string(), 8.4), ("w".to_string(), 8.1)]
+        );
+    }
+
+    #[tokio::test]
+    async fn import_blobs_test() {
+        let db = setup("event_scores_import_blobs").await;
+        let config = ConfigBuilder::default().forms(&["w", "e"]).build();
+        assert!(Events::new(
+            "test-unscored".to_string(),
+            "Unscored".to_string(),
+            "test".to_string(),
+            "mixed".to_string(),
+            "unscored".to_string()
+        )
+        .insert(&db)
+        .await
+        .is_ok());
+        db.conn(|conn| {
+            conn.execute(
+                "ALTER TABLE events ADD COLUMN scores TEXT NOT NULL DEFAULT '{}';",
+                [],
+            )?;
+            conn.execute(
+                "UPDATE events SET scores = '{\"w\":\"20\",\"e\":\"15\",\"s\":\"oops\"}'
+                    WHERE id = 'test-test';",
+                [],
+            )?;
+            // How older versions left events nobody had scored yet
+            conn.execute(
+                "UPDATE events SET scores = '{\"w\":0,\"e\":\"0\"}' WHERE id = 'test-unscored';",
+                [],
+            )
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(EventScores::import_blobs(&db, &config).await.unwrap(), 2);
+        let mut scores = EventScores::all(&db).await.unwrap();
+        scores.sort_by(|a, b| a.form_id.cmp(&b.form_id));
+        assert_eq!(
+            scores
+                .iter()
+                .map(|score| (
+                    score.form_id.as_str(),
+                    score.score_name.as_str(),
+                    score.points
+                ))
+                .collect::<Vec<_>>(),
+            vec![("e", "2nd", 15), ("w", "1st", 20)]
+        );
+        assert!(scores.iter().all(|score| score.event_id == "test-test"));
+
+        // The column is gone, so importing again is a no-op
+        assert_eq!(EventScores::import_blobs(&db, &config).await.unwrap(), 0);
+    }
+}
@@ -1,6 +1,103 @@
-use async_sqlite::{rusqlite::Row, Pool};
+use async_sqlite::rusqlite::Error as RusqliteError;
+use async_sqlite::{
+    rusqlite::{
+        types::{FromSql, FromSqlError, FromSqlResult, ToSqlOutput, ValueRef},
+        Connection, Row, ToSql,
+    },
+    Pool,
+};
 use log::debug;
-use serde_json::Value;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::configurator::parser::{Heat, Timeslot};
+
+/// Columns read by `map_from_row`, in order
+const COLUMNS: &str =
+    "id, name, year_id, gender_id, filter_key, scoring, multiplier, revision, status,
+    start_time, venue, duration, final_id, heat";
+
+/// Where an event is in the day, in the order events move through them
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EventStatus {
+    Scheduled,
+    InProgress,
+    /// Scores are in but may still be corrected
+    Provisional,
+    /// Scores are locked, only admins can move the event back
+    Final,
+}
+
+impl EventStatus {
+    pub const ALL: [EventStatus; 4] = [
+        EventStatus::Scheduled,
+        EventStatus::InProgress,
+        EventStatus::Provisional,
+        EventStatus::Final,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventStatus::Scheduled => "scheduled",
+            EventStatus::InProgress => "in_progress",
+            EventStatus::Provisional => "provisional",
+            EventStatus::Final => "final",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            EventStatus::Scheduled => "Scheduled",
+            EventStatus::InProgress => "In progress",
+            EventStatus::Provisional => "Provisional",
+            EventStatus::Final => "Final",
+        }
+    }
+
+    fn position(&self) -> usize {
+        Self::ALL
+            .iter()
+            .position(|status| status == self)
+            .expect("every status is in ALL")
+    }
+
+    pub fn next(&self) -> Option<EventStatus> {
+        Self::ALL.get(self.position() + 1).copied()
+    }
+
+    pub fn previous(&self) -> Option<EventStatus> {
+        self.position()
+            .checked_sub(1)
+            .map(|position| Self::ALL[position])
+    }
+
+    /// Events move one step at a time, forwards or back to correct a mistake
+    pub fn can_become(&self, to: EventStatus) -> bool {
+        self.next() == Some(to) || self.previous() == Some(to)
+    }
+
+    /// Locking an event as final, or unlocking it, is left to admins
+    pub fn needs_admin_to_become(&self, to: EventStatus) -> bool {
+        *self == EventStatus::Final || to == EventStatus::Final
+    }
+}
+
+impl ToSql for EventStatus {
+    fn to_sql(&self) -> Result<ToSqlOutput<'_>, RusqliteError> {
+        Ok(self.as_str().into())
+    }
+}
+
+impl FromSql for EventStatus {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        let value = value.as_str()?;
+        Self::ALL
+            .into_iter()
+            .find(|status| status.as_str() == value)
+            .ok_or_else(|| FromSqlError::Other(format!("unknown event status {value}").into()))
+    }
+}
 
 #[derive(Clone, PartialEq, Debug)]
 pub struct Events {
@@ -9,7 +106,16 @@ pub struct Events {
     pub year_id: String,
     pub gender_id: String,
     pub filter_key: String,
-    pub scores: String,
+    /// Id of the scoring scheme this event uses, or `None` for the global scores
+    pub scoring: Option<String>,
+    /// Points earned in this event are multiplied by this when totalled
+    pub multiplier: i64,
+    /// Bumped whenever the event's scores or status change, so stale submissions can be refused
+    pub revision: i64,
+    pub status: EventStatus,
+    pub timeslot: Timeslot,
+    /// Which heat of which final this is, if it's a heat
+    pub heat: Option<Heat>,
 }
 
 impl Events {
@@ -19,7 +125,6 @@ impl Events {
         year_id: String,
         gender_id: String,
         filter_key: String,
-        scores: String,
     ) -> Self {
         Self {
             id,
@@ -27,38 +132,87 @@ impl Events {
             year_id,
             gender_id,
             filter_key,
-            scores: scores,
+            scoring: None,
+            multiplier: 1,
+            revision: 0,
+            status: EventStatus::Scheduled,
+            timeslot: Timeslot::default(),
+            heat: None,
         }
     }
 
-    fn map_from_row(row: &Row) -> Result<Self, async_sqlite::Error> {
+    pub fn with_scoring(mut self, scoring: Option<String>, multiplier: i64) -> Self {
+        self.scoring = scoring;
+        self.multiplier = multiplier;
+        self
+    }
+
+    pub fn with_timeslot(mut self, timeslot: Timeslot) -> Self {
+        self.timeslot = timeslot;
+        self
+    }
+
+    pub fn with_heat(mut self, heat: Option<Heat>) -> Self {
+        self.heat = heat;
+        self
+    }
+
+    fn map_from_row(row: &Row) -> Result<Self, RusqliteError> {
         Ok(Self {
             id: row.get(0)?,
             name: row.get(1)?,
             year_id: row.get(2)?,
             gender_id: row.get(3)?,
             filter_key: row.get(4)?,
-            scores: row.get(5)?,
+            scoring: row.get(5)?,
+            multiplier: row.get(6)?,
+            revision: row.get(7)?,
+            status: row.get(8)?,
+            timeslot: Timeslot {
+                start: row.get(9)?,
+                venue: row.get(10)?,
+                duration: row.get(11)?,
+            },
+            heat: row
+                .get::<_, Option<String>>(12)?
+                .zip(row.get(13)?)
+                .map(|(final_id, number)| Heat { final_id, number }),
         })
     }
 
+    /// Tests set up rows with this; reconciliation uses `insert_in` inside its transaction
+    #[cfg(test)]
     pub async fn insert(self, pool: &Pool) -> Result<(), async_sqlite::Error> {
-        pool.conn(move |conn| {
-            debug!("Inserting Event with id {}", self.id);
-            conn.execute(
-                "INSERT INTO events(id, name, year_id, gender_id, filter_key, scores) VALUES (?1, ?2, ?3, ?4, ?5, ?6);",
-                [self.id, self.name, self.year_id, self.gender_id, self.filter_key, self.scores],
-            )
-            .unwrap();
-            Ok(())
-        })
-        .await?;
+        pool.conn(move |conn| self.insert_in(conn)).await
+    }
+
+    /// Insert the event on `conn`, which can be a transaction
+    pub fn insert_in(&self, conn: &Connection) -> Result<(), RusqliteError> {
+        debug!("Inserting Event with id {}", self.id);
+        conn.execute(
+            "INSERT INTO events(id, name, year_id, gender_id, filter_key, scoring, multiplier, start_time, venue, duration, final_id, heat)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12);",
+            (
+                &self.id,
+                &self.name,
+                &self.year_id,
+                &self.gender_id,
+                &self.filter_key,
+                &self.scoring,
+                self.multiplier,
+                &self.timeslot.start,
+                &self.timeslot.venue,
+                self.timeslot.duration,
+                self.heat.as_ref().map(|heat| heat.final_id.clone()),
+                self.heat.as_ref().map(|heat| heat.number),
+            ),
+        )?;
         Ok(())
     }
 
     pub async fn all(pool: &Pool) -> Result<Vec<Self>, async_sqlite::Error> {
         pool.conn(move |conn| {
-            let mut stmt = conn.prepare("SELECT * FROM events")?;
+            let mut stmt = conn.prepare(&format!("SELECT {COLUMNS} FROM events"))?;
             let event_iter = stmt
                 .query_map([], |row| Ok(Self::map_from_row(row).unwrap()))
                 .unwrap();
@@ -79,7 +233,7 @@ impl Events {
         group: Option<String>,
     ) -> Result<Vec<Self>, async_sqlite::Error> {
         pool.conn(move |conn| {
-            let mut stmt = conn.prepare("SELECT * FROM events")?;
+            let mut stmt = conn.prepare(&format!("SELECT {COLUMNS} FROM events"))?;
             let event_iter = stmt
                 .query_map([], |row| Ok(Self::map_from_row(row).unwrap()))
                 .unwrap();
@@ -109,31 +263,105 @@ impl Events {
         .await
     }
 
-    pub async fn set_scores(
+    /// Update everything about an event from config, leaving its year, revision and status
+    pub fn update_details_in(&self, conn: &Connection) -> Result<(), RusqliteError> {
+        debug!("Updating details for Event with id {}", self.id);
+        conn.execute(
+            "UPDATE events SET name = ?1, gender_id = ?2, filter_key = ?3, scoring = ?4, multiplier = ?5,
+                    start_time = ?6, venue = ?7, duration = ?8, final_id = ?9, heat = ?10
+                WHERE id = ?11;",
+            (
+                &self.name,
+                &self.gender_id,
+                &self.filter_key,
+                &self.scoring,
+                self.multiplier,
+                &self.timeslot.start,
+                &self.timeslot.venue,
+                self.timeslot.duration,
+                self.heat.as_ref().map(|heat| heat.final_id.clone()),
+                self.heat.as_ref().map(|heat| heat.number),
+                &self.id,
+            ),
+        )?;
+        Ok(())
+    }
+
+    /// Move an event from one status to another, bumping its revision. Returns false,
+    /// changing nothing, if the event isn't in the `from` status any more.
+    pub async fn set_status(
         pool: &Pool,
         id: String,
-        scores: Value,
-    ) -> Result<(), async_sqlite::Error> {
+        from: EventStatus,
+        to: EventStatus,
+    ) -> Result<bool, async_sqlite::Error> {
         pool.conn(move |conn| {
-            debug!("Setting Scores for Event with id {}", id);
-            conn.execute(
-                "UPDATE events SET scores = ?1 WHERE id = ?2;",
-                [serde_json::to_string(&scores).unwrap(), id],
-            )
-            .unwrap();
-            Ok(())
+            debug!(
+                "Moving Event with id {} from {} to {}",
+                id,
+                from.as_str(),
+                to.as_str()
+            );
+            let changed = conn.execute(
+                "UPDATE events SET status = ?1, revision = revision + 1 WHERE id = ?2 AND status = ?3;",
+                (to, id, from),
+            )?;
+            Ok(changed == 1)
         })
-        .await?;
-        Ok(())
+        .await
+    }
+
+    /// Events that have a start time, in timetable order
+    pub async fn scheduled(pool: &Pool) -> Result<Vec<Self>, async_sqlite::Error> {
+        pool.conn(move |conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {COLUMNS} FROM events WHERE start_time IS NOT NULL
+                    ORDER BY start_time, venue, year_id, id"
+            ))?;
+            let event_iter = stmt.query_map([], Self::map_from_row)?;
+            let mut events = Vec::new();
+
+            for event in event_iter {
+                events.push(event?);
+            }
+            Ok(events)
+        })
+        .await
     }
 
-    pub async fn delete_all(pool: &Pool) -> Result<(), async_sqlite::Error> {
+    /// The events running at a time (`HH:MM`), and those starting soonest after it.
+    /// Without a time, the server's local time is used. Events without a duration
+    /// are never running, since when they end isn't known.
+    pub async fn now_and_next(
+        pool: &Pool,
+        at: Option<String>,
+    ) -> Result<(Vec<Self>, Vec<Self>), async_sqlite::Error> {
         pool.conn(move |conn| {
-            conn.execute("DELETE FROM events;", []).unwrap();
-            Ok(())
+            let at: String = conn.query_row(
+                "SELECT COALESCE(?1, strftime('%H:%M', 'now', 'localtime'))",
+                [at],
+                |row| row.get(0),
+            )?;
+            let mut now = conn.prepare(&format!(
+                "SELECT {COLUMNS} FROM events
+                    WHERE start_time <= ?1
+                        AND ?1 < strftime('%H:%M', start_time, '+' || duration || ' minutes')
+                    ORDER BY start_time, venue, year_id, id"
+            ))?;
+            let now = now
+                .query_map([&at], Self::map_from_row)?
+                .collect::<Result<Vec<_>, _>>()?;
+            let mut next = conn.prepare(&format!(
+                "SELECT {COLUMNS} FROM events
+                    WHERE start_time = (SELECT MIN(start_time) FROM events WHERE start_time > ?1)
+                    ORDER BY venue, year_id, id"
+            ))?;
+            let next = next
+                .query_map([&at], Self::map_from_row)?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok((now, next))
         })
-        .await?;
-        Ok(())
+        .await
     }
 
     pub async fn count(pool: &Pool) -> Result<i64, async_sqlite::Error> {
@@ -147,9 +375,16 @@ impl Events {
 
 #[cfg(test)]
 mod tests {
-    use serde_json::json;
-
-    use crate::{db::years::Years, test_harness};
+    use crate::{
+        configurator::parser::{Form, Score},
+        db::{
+            event_scores::{EventScores, ScoreUpdate},
+            forms::Forms,
+            score_changes::ChangedBy,
+            years::Years,
+        },
+        test_harness,
+    };
 
     use super::*;
 
@@ -161,8 +396,7 @@ mod tests {
                 "Test".to_string(),
                 "test".to_string(),
                 "mixed".to_string(),
-                "test".to_string(),
-                "{}".to_string()
+                "test".to_string()
             ),
             Events {
                 id: "test-test".to_string(),
@@ -170,7 +404,12 @@ mod tests {
                 year_id: "test".to_string(),
                 gender_id: "mixed".to_string(),
                 filter_key: "test".to_string(),
-                scores: "{}".to_string()
+                scoring: None,
+                multiplier: 1,
+                revision: 0,
+                status: EventStatus::Scheduled,
+                timeslot: Timeslot::default(),
+                heat: None
             }
         )
     }
@@ -187,8 +426,7 @@ mod tests {
             "Test".to_string(),
             "test".to_string(),
             "mixed".to_string(),
-            "test".to_string(),
-            "{}".to_string()
+            "test".to_string()
         )
         .insert(&db)
         .await
@@ -207,8 +445,7 @@ mod tests {
             "Test".to_string(),
             "test".to_string(),
             "mixed".to_string(),
-            "test".to_string(),
-            "{}".to_string()
+            "test".to_string()
         )
         .insert(&db)
         .await
@@ -218,8 +455,7 @@ mod tests {
             "Test2".to_string(),
             "test".to_string(),
             "mixed".to_string(),
-            "test".to_string(),
-            "{}".to_string()
+            "test".to_string()
         )
         .insert(&db)
         .await
@@ -229,8 +465,7 @@ mod tests {
             "Test3".to_string(),
             "test".to_string(),
             "mixed".to_string(),
-            "test".to_string(),
-            "{}".to_string()
+            "test".to_string()
         )
         .insert(&db)
         .await
@@ -240,8 +475,7 @@ mod tests {
             "Test4".to_string(),
             "test".to_string(),
             "mixed".to_string(),
-            "test".to_string(),
-            "{}".to_string()
+            "test".to_string()
         )
         .insert(&db)
         .await
@@ -262,8 +496,7 @@ mod tests {
                 "Test".to_string(),
                 year_id.to_string(),
                 "boys".to_string(),
-                "test".to_string(),
-                "{}".to_string()
+                "test".to_string()
             )
             .insert(&db)
             .await
@@ -273,8 +506,7 @@ mod tests {
                 "Test2".to_string(),
                 year_id.to_string(),
                 "girls".to_string(),
-                "test".to_string(),
-                "{}".to_string()
+                "test".to_string()
             )
             .insert(&db)
             .await
@@ -284,8 +516,7 @@ mod tests {
                 "Test3".to_string(),
                 year_id.to_string(),
                 "mixed".to_string(),
-                "test".to_string(),
-                "{}".to_string()
+                "test".to_string()
             )
             .insert(&db)
             .await
@@ -295,8 +526,7 @@ mod tests {
                 "Test4".to_string(),
                 year_id.to_string(),
                 "mixed".to_string(),
-                "test".to_string(),
-                "{}".to_string()
+                "test".to_string()
             )
             .insert(&db)
             .await
@@ -329,9 +559,19 @@ mod tests {
         );
     }
 
+    #[test]
+    fn statuses_move_one_step_at_a_time() {
+        assert!(EventStatus::Scheduled.can_become(EventStatus::InProgress));
+        assert!(EventStatus::Final.can_become(EventStatus::Provisional));
+        assert!(!EventStatus::Scheduled.can_become(EventStatus::Final));
+        assert!(!EventStatus::Provisional.can_become(EventStatus::Provisional));
+        assert_eq!(EventStatus::Final.next(), None);
+        assert_eq!(EventStatus::Scheduled.previous(), None);
+    }
+
     #[tokio::test]
-    async fn set_scores_test() {
-        let db = test_harness::setup_db("events_set_score").await;
+    async fn set_status_test() {
+        let db = test_harness::setup_db("events_set_status").await;
         assert!(Years::new("test".to_string(), "Test".to_string())
             .insert(&db)
             .await
@@ -341,34 +581,88 @@ mod tests {
             "Test".to_string(),
             "test".to_string(),
             "mixed".to_string(),
-            "test".to_string(),
-            "{}".to_string()
+            "test".to_string()
         )
         .insert(&db)
         .await
         .is_ok());
-
-        assert!(Events::set_scores(
+        assert!(Events::set_status(
             &db,
             "test-test".to_string(),
-            json!({
-                "test": "test"
-            })
+            EventStatus::Scheduled,
+            EventStatus::InProgress
         )
         .await
-        .is_ok());
-        assert_eq!(
-            Events::all(&db).await.unwrap()[0].scores,
-            json!({
-                "test": "test"
-            })
-            .to_string()
+        .unwrap());
+        // Someone else already moved it on
+        assert!(!Events::set_status(
+            &db,
+            "test-test".to_string(),
+            EventStatus::Scheduled,
+            EventStatus::InProgress
         )
+        .await
+        .unwrap());
+        let event = Events::all(&db).await.unwrap()[0].clone();
+        assert_eq!(event.status, EventStatus::InProgress);
+        assert_eq!(event.revision, 1);
+    }
+
+    #[tokio::test]
+    async fn now_and_next_test() {
+        let db = test_harness::setup_db("events_now_and_next").await;
+        assert!(Years::new("y9".to_string(), "Year 9".to_string())
+            .insert(&db)
+            .await
+            .is_ok());
+        for (id, start, duration) in [
+            ("60m", Some("09:30"), Some(15)),
+            ("200m", Some("09:40"), None),
+            ("javelin", Some("10:00"), Some(60)),
+            ("shot_putt", Some("10:00"), Some(60)),
+            ("relay", None, None),
+        ] {
+            assert!(Events::new(
+                format!("y9-boys-{id}"),
+                id.to_string(),
+                "y9".to_string(),
+                "boys".to_string(),
+                id.to_string()
+            )
+            .with_timeslot(Timeslot {
+                start: start.map(str::to_string),
+                venue: None,
+                duration,
+            })
+            .insert(&db)
+            .await
+            .is_ok());
+        }
+        let ids = |events: Vec<Events>| {
+            events
+                .into_iter()
+                .map(|event| event.filter_key)
+                .collect::<Vec<_>>()
+        };
+
+        let (now, next) = Events::now_and_next(&db, Some("09:42".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(ids(now), vec!["60m"]);
+        assert_eq!(ids(next), vec!["javelin", "shot_putt"]);
+
+        let (now, next) = Events::now_and_next(&db, Some("11:00".to_string()))
+            .await
+            .unwrap();
+        assert!(now.is_empty());
+        assert!(next.is_empty());
+
+        assert_eq!(Events::scheduled(&db).await.unwrap().len(), 4);
     }
 
     #[tokio::test]
-    async fn delete_all_test() {
-        let db = test_harness::setup_db("events_delete_all").await;
+    async fn update_details_test() {
+        let db = test_harness::setup_db("events_update_details").await;
         assert!(Years::new("test".to_string(), "Test".to_string())
             .insert(&db)
             .await
@@ -378,47 +672,70 @@ mod tests {
             "Test".to_string(),
             "test".to_string(),
             "mixed".to_string(),
-            "test".to_string(),
-            "{}".to_string()
+            "test".to_string()
         )
         .insert(&db)
         .await
         .is_ok());
-        assert!(Events::new(
-            "test-test2".to_string(),
-            "Test2".to_string(),
-            "test".to_string(),
-            "mixed".to_string(),
-            "test".to_string(),
-            "{}".to_string()
+        assert!(Forms::sync(
+            &db,
+            vec![Form {
+                id: "w".to_string(),
+                name: "Winston".to_string(),
+                colour: "red".to_string(),
+            }]
         )
-        .insert(&db)
         .await
         .is_ok());
-        assert!(Events::new(
-            "test-test3".to_string(),
-            "Test3".to_string(),
-            "test".to_string(),
-            "mixed".to_string(),
-            "test".to_string(),
-            "{}".to_string()
+        assert!(EventScores::set_batch(
+            &db,
+            vec![ScoreUpdate {
+                event_id: "test-test".to_string(),
+                scores: vec![(
+                    "w".to_string(),
+                    Score {
+                        name: "1st".to_string(),
+                        value: 20,
+                        default: false,
+                    }
+                )],
+                performances: None,
+                entries: None,
+                revision: None
+            }],
+            ChangedBy::default()
         )
-        .insert(&db)
         .await
         .is_ok());
-        assert!(Events::new(
-            "test-test4".to_string(),
-            "Test4".to_string(),
+        let timeslot = Timeslot {
+            start: Some("09:30".to_string()),
+            venue: Some("Track".to_string()),
+            duration: Some(10),
+        };
+        let heat = Heat {
+            final_id: "test-final".to_string(),
+            number: 2,
+        };
+        let renamed = Events::new(
+            "test-test".to_string(),
+            "Renamed".to_string(),
             "test".to_string(),
             "mixed".to_string(),
             "test".to_string(),
-            "{}".to_string()
         )
-        .insert(&db)
-        .await
-        .is_ok());
-        assert_eq!(Events::all(&db).await.unwrap().len(), 4);
-        assert!(Events::delete_all(&db).await.is_ok());
-        assert_eq!(Events::all(&db).await.unwrap().len(), 0);
+        .with_scoring(Some("relay".to_string()), 2)
+        .with_timeslot(timeslot.clone())
+        .with_heat(Some(heat.clone()));
+        assert!(db
+            .conn(move |conn| renamed.update_details_in(conn))
+            .await
+            .is_ok());
+        let event = Events::all(&db).await.unwrap()[0].clone();
+        assert_eq!(event.name, "Renamed");
+        assert_eq!(event.scoring, Some("relay".to_string()));
+        assert_eq!(event.multiplier, 2);
+        assert_eq!(event.timeslot, timeslot);
+        assert_eq!(event.heat, Some(heat));
+        assert_eq!(EventScores::all(&db).await.unwrap()[0].points, 20);
     }
 }
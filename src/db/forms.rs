@@ -0,0 +1,71 @@
+use async_sqlite::Pool;
+use log::debug;
+
+use crate::configurator::parser::Form;
+
+/// The forms from config.yaml, mirrored so scores can reference them
+pub struct Forms;
+
+impl Forms {
+    /// Insert or update every configured form. Forms dropped from the config are kept,
+    /// since scores may still reference them.
+    pub async fn sync(pool: &Pool, forms: Vec<Form>) -> Result<(), async_sqlite::Error> {
+        pool.conn_mut(move |conn| {
+            debug!("Syncing {} Forms", forms.len());
+            let tx = conn.transaction()?;
+            for form in forms {
+                tx.execute(
+                    "INSERT INTO forms(id, name, colour) VALUES (?1, ?2, ?3)
+                        ON CONFLICT(id) DO UPDATE SET name = excluded.name, colour = excluded.colour;",
+                    [form.id, form.name, form.colour],
+                )?;
+            }
+            tx.commit()?;
+            Ok(())
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_harness;
+
+    use super::*;
+
+    fn form(id: &str, name: &str) -> Form {
+        Form {
+            id: id.to_string(),
+            name: name.to_string(),
+            colour: "red".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn sync_test() {
+        let db = test_harness::setup_db("forms_sync").await;
+        assert!(
+            Forms::sync(&db, vec![form("w", "Winston"), form("e", "Ennis")])
+                .await
+                .is_ok()
+        );
+        assert!(Forms::sync(&db, vec![form("w", "Winston Churchill")])
+            .await
+            .is_ok());
+        let forms = db
+            .conn(|conn| {
+                let mut stmt = conn.prepare("SELECT id, name FROM forms ORDER BY id")?;
+                let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+                rows.collect::<Result<Vec<(String, String)>, _>>()
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            forms,
+            vec![
+                ("e".to_string(), "Ennis".to_string()),
+                ("w".to_string(), "Winston Churchill".to_string())
+            ]
+        );
+    }
+}
@@ -1,14 +1,30 @@
-use async_sqlite::Pool;
+use async_sqlite::{rusqlite::Connection, Pool};
 
+pub mod api_tokens;
+pub mod archived_events;
+pub mod competitors;
+pub mod config_versions;
+pub mod event_entries;
+pub mod event_scores;
 pub mod events;
+pub mod forms;
+pub mod performances;
+pub mod records;
+pub mod score_changes;
 pub mod user_sessions;
 pub mod users;
 pub mod years;
 
 pub async fn create_tables(pool: &Pool) -> Result<(), async_sqlite::Error> {
-    pool.conn(move |conn| {
-        conn.execute("PRAGMA foreign_keys = ON", []).unwrap();
+    // Foreign keys are enforced per connection, so turn them on for the whole pool
+    for result in pool
+        .conn_for_each(|conn| conn.execute("PRAGMA foreign_keys = ON", []))
+        .await
+    {
+        result?;
+    }
 
+    pool.conn(move |conn| {
         conn.execute(
             "CREATE TABLE IF NOT EXISTS years (
                 id TEXT PRIMARY KEY,
@@ -25,12 +41,166 @@ pub async fn create_tables(pool: &Pool) -> Result<(), async_sqlite::Error> {
                 year_id TEXT NOT NULL,
                 gender_id TEXT NOT NULL,
                 filter_key TEXT NOT NULL,
-                scores TEXT NOT NULL DEFAULT '{}',
+                scoring TEXT,
+                multiplier INTEGER NOT NULL DEFAULT 1,
+                revision INTEGER NOT NULL DEFAULT 0,
+                status TEXT NOT NULL DEFAULT 'scheduled',
+                start_time TEXT,
+                venue TEXT,
+                duration INTEGER,
+                final_id TEXT,
+                heat INTEGER,
                 FOREIGN KEY (year_id) REFERENCES years(id)
             );",
             [],
         )
         .unwrap();
+        add_column_if_missing(conn, "events", "scoring", "TEXT").unwrap();
+        add_column_if_missing(conn, "events", "multiplier", "INTEGER NOT NULL DEFAULT 1").unwrap();
+        add_column_if_missing(conn, "events", "revision", "INTEGER NOT NULL DEFAULT 0").unwrap();
+        add_column_if_missing(
+            conn,
+            "events",
+            "status",
+            "TEXT NOT NULL DEFAULT 'scheduled'",
+        )
+        .unwrap();
+        add_column_if_missing(conn, "events", "start_time", "TEXT").unwrap();
+        add_column_if_missing(conn, "events", "venue", "TEXT").unwrap();
+        add_column_if_missing(conn, "events", "duration", "INTEGER").unwrap();
+        add_column_if_missing(conn, "events", "final_id", "TEXT").unwrap();
+        add_column_if_missing(conn, "events", "heat", "INTEGER").unwrap();
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS forms (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                colour TEXT NOT NULL
+            );",
+            [],
+        )
+        .unwrap();
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS event_scores (
+                event_id TEXT NOT NULL,
+                form_id TEXT NOT NULL,
+                score_name TEXT NOT NULL,
+                points INTEGER NOT NULL,
+                updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                updated_by INTEGER,
+                updated_by_token INTEGER,
+                PRIMARY KEY (event_id, form_id),
+                FOREIGN KEY (event_id) REFERENCES events(id),
+                FOREIGN KEY (form_id) REFERENCES forms(id),
+                FOREIGN KEY (updated_by) REFERENCES users(id)
+            );",
+            [],
+        )
+        .unwrap();
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS score_changes (
+                id INTEGER PRIMARY KEY,
+                event_id TEXT NOT NULL,
+                form_id TEXT NOT NULL,
+                before_name TEXT,
+                before_points INTEGER,
+                after_name TEXT,
+                after_points INTEGER,
+                changed_by INTEGER,
+                changed_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                changed_by_token INTEGER,
+                FOREIGN KEY (changed_by) REFERENCES users(id)
+            );",
+            [],
+        )
+        .unwrap();
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS performances (
+                event_id TEXT NOT NULL,
+                form_id TEXT NOT NULL,
+                value REAL NOT NULL,
+                PRIMARY KEY (event_id, form_id),
+                FOREIGN KEY (event_id) REFERENCES events(id)
+            );",
+            [],
+        )
+        .unwrap();
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS competitors (
+                id INTEGER PRIMARY KEY,
+                name TEXT NOT NULL,
+                form_id TEXT NOT NULL,
+                year_id TEXT NOT NULL,
+                gender_id TEXT NOT NULL,
+                UNIQUE (name, form_id, year_id),
+                FOREIGN KEY (form_id) REFERENCES forms(id)
+            );",
+            [],
+        )
+        .unwrap();
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS event_entries (
+                event_id TEXT NOT NULL,
+                form_id TEXT NOT NULL,
+                competitor_id INTEGER NOT NULL,
+                PRIMARY KEY (event_id, form_id),
+                FOREIGN KEY (event_id) REFERENCES events(id),
+                FOREIGN KEY (form_id) REFERENCES forms(id),
+                FOREIGN KEY (competitor_id) REFERENCES competitors(id) ON DELETE CASCADE
+            );",
+            [],
+        )
+        .unwrap();
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS records (
+                id INTEGER PRIMARY KEY,
+                filter_key TEXT NOT NULL,
+                year_id TEXT NOT NULL,
+                gender_id TEXT NOT NULL,
+                value REAL NOT NULL,
+                holder TEXT NOT NULL,
+                set_in TEXT NOT NULL,
+                event_id TEXT,
+                form_id TEXT,
+                recorded_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                UNIQUE (filter_key, year_id, gender_id, value, holder, set_in)
+            );",
+            [],
+        )
+        .unwrap();
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS archived_events (
+                id INTEGER PRIMARY KEY,
+                event_id TEXT NOT NULL,
+                name TEXT NOT NULL,
+                year_id TEXT NOT NULL,
+                gender_id TEXT NOT NULL,
+                filter_key TEXT NOT NULL,
+                scores TEXT NOT NULL,
+                archived_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+            );",
+            [],
+        )
+        .unwrap();
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS config_versions (
+                id INTEGER PRIMARY KEY,
+                version TEXT NOT NULL,
+                applied_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                yaml_hash TEXT NOT NULL,
+                plan TEXT NOT NULL
+            );",
+            [],
+        )
+        .unwrap();
 
         conn.execute(
             "CREATE TABLE IF NOT EXISTS users (
@@ -54,8 +224,73 @@ pub async fn create_tables(pool: &Pool) -> Result<(), async_sqlite::Error> {
             [],
         )
         .unwrap();
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS api_tokens (
+                id INTEGER PRIMARY KEY,
+                name TEXT NOT NULL,
+                token_hash TEXT NOT NULL UNIQUE,
+                scopes TEXT NOT NULL,
+                year_ids TEXT NOT NULL DEFAULT '',
+                event_ids TEXT NOT NULL DEFAULT '',
+                expires_at TEXT,
+                created_by INTEGER,
+                created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                last_used_at TEXT,
+                FOREIGN KEY (created_by) REFERENCES users(id) ON DELETE SET NULL
+            );",
+            [],
+        )
+        .unwrap();
         Ok(())
     })
     .await?;
     Ok(())
 }
+
+/// Add a column to a table created by an older version of the app, if it's missing
+fn add_column_if_missing(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<(), async_sqlite::rusqlite::Error> {
+    let exists = conn
+        .prepare(&format!(
+            "SELECT 1 FROM pragma_table_info('{table}') WHERE name = ?1"
+        ))?
+        .exists([column])?;
+    if !exists {
+        conn.execute(
+            &format!("ALTER TABLE {table} ADD COLUMN {column} {definition};"),
+            [],
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_harness;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn add_column_if_missing_test() {
+        let db = test_harness::setup_db("db_add_column_if_missing").await;
+        let columns = db
+            .conn(|conn| {
+                conn.execute("CREATE TABLE legacy (id TEXT PRIMARY KEY);", [])?;
+                add_column_if_missing(conn, "legacy", "extra", "INTEGER NOT NULL DEFAULT 1")?;
+                add_column_if_missing(conn, "legacy", "extra", "INTEGER NOT NULL DEFAULT 1")?;
+                conn.query_row(
+                    "SELECT COUNT(*) FROM pragma_table_info('legacy')",
+                    [],
+                    |row| row.get::<_, i64>(0),
+                )
+            })
+            .await
+            .unwrap();
+        assert_eq!(columns, 2);
+    }
+}
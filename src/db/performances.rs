@@ -0,0 +1,126 @@
+use async_sqlite::rusqlite::Error as RusqliteError;
+use async_sqlite::{
+    rusqlite::{Row, Transaction},
+    Pool,
+};
+use log::debug;
+
+/// A raw performance (time in seconds, or distance/height in metres) for a form in an event
+#[derive(Clone, PartialEq, Debug)]
+pub struct Performances {
+    pub event_id: String,
+    pub form_id: String,
+    pub value: f64,
+}
+
+impl Performances {
+    fn map_from_row(row: &Row) -> Result<Self, RusqliteError> {
+        Ok(Self {
+            event_id: row.get(0)?,
+            form_id: row.get(1)?,
+            value: row.get(2)?,
+        })
+    }
+
+    /// Replace every performance recorded for an event, as part of a larger transaction
+    pub fn replace(
+        tx: &Transaction,
+        event_id: &str,
+        performances: Vec<(String, f64)>,
+    ) -> Result<(), RusqliteError> {
+        debug!("Setting Performances for Event with id {}", event_id);
+        tx.execute("DELETE FROM performances WHERE event_id = ?1;", [event_id])?;
+        for (form_id, value) in performances {
+            tx.execute(
+                "INSERT INTO performances(event_id, form_id, value) VALUES (?1, ?2, ?3);",
+                (event_id, form_id, value),
+            )?;
+        }
+        Ok(())
+    }
+
+    pub async fn all(pool: &Pool) -> Result<Vec<Self>, async_sqlite::Error> {
+        pool.conn(move |conn| {
+            let mut stmt = conn.prepare("SELECT * FROM performances")?;
+            let performance_iter = stmt.query_map([], Self::map_from_row)?;
+            let mut performances = Vec::new();
+
+            for performance in performance_iter {
+                performances.push(performance?);
+            }
+            Ok(performances)
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        db::{events::Events, years::Years},
+        test_harness,
+    };
+
+    use super::*;
+
+    async fn setup(name: &str) -> Pool {
+        let db = test_harness::setup_db(name).await;
+        assert!(Years::new("test".to_string(), "Test".to_string())
+            .insert(&db)
+            .await
+            .is_ok());
+        assert!(Events::new(
+            "test-test".to_string(),
+            "Test".to_string(),
+            "test".to_string(),
+            "mixed".to_string(),
+            "test".to_string()
+        )
+        .insert(&db)
+        .await
+        .is_ok());
+        db
+    }
+
+    async fn replace(
+        db: &Pool,
+        performances: Vec<(String, f64)>,
+    ) -> Result<(), async_sqlite::Error> {
+        db.conn_mut(move |conn| {
+            let tx = conn.transaction()?;
+            Performances::replace(&tx, "test-test", performances)?;
+            tx.commit()
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn replace_test() {
+        let db = setup("performances_replace").await;
+        assert!(
+            replace(&db, vec![("w".to_string(), 8.4), ("e".to_string(), 8.7)])
+                .await
+                .is_ok()
+        );
+        assert!(replace(&db, vec![("w".to_string(), 8.2)]).await.is_ok());
+        assert_eq!(
+            Performances::all(&db).await.unwrap(),
+            vec![Performances {
+                event_id: "test-test".to_string(),
+                form_id: "w".to_string(),
+                value: 8.2
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn all_test() {
+        let db = setup("performances_all").await;
+        assert!(
+            replace(&db, vec![("w".to_string(), 8.4), ("e".to_string(), 8.7)])
+                .await
+                .is_ok()
+        );
+        assert_eq!(Performances::all(&db).await.unwrap().len(), 2);
+    }
+}
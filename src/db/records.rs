@@ -0,0 +1,466 @@
+use async_sqlite::rusqlite::Error as RusqliteError;
+use async_sqlite::{
+    rusqlite::{OptionalExtension, Row, Transaction},
+    Pool,
+};
+use log::debug;
+
+use crate::{
+    configurator::parser::{Configuration, Measurement},
+    csv_import,
+    db::events::Events,
+};
+
+/// Columns read by `map_from_row`, in order
+const COLUMNS: &str =
+    "id, filter_key, year_id, gender_id, value, holder, set_in, event_id, form_id, recorded_at";
+
+/// A mark that was the best ever for an event in a year and gender when it was set.
+/// The current record is the best of them, and the rest are its history.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Records {
+    pub id: Option<i64>,
+    /// Config event id
+    pub filter_key: String,
+    pub year_id: String,
+    pub gender_id: String,
+    /// Seconds or metres, as performances are stored
+    pub value: f64,
+    pub holder: String,
+    /// The (calendar) year it was set in
+    pub set_in: String,
+    /// The event and form that set it, for records set at sports day
+    pub event_id: Option<String>,
+    pub form_id: Option<String>,
+    /// UTC, as `YYYY-MM-DD HH:MM:SS`
+    pub recorded_at: String,
+}
+
+impl Records {
+    pub fn new(
+        filter_key: String,
+        year_id: String,
+        gender_id: String,
+        value: f64,
+        holder: String,
+        set_in: String,
+    ) -> Self {
+        Self {
+            id: None,
+            filter_key,
+            year_id,
+            gender_id,
+            value,
+            holder,
+            set_in,
+            event_id: None,
+            form_id: None,
+            recorded_at: String::new(),
+        }
+    }
+
+    fn map_from_row(row: &Row) -> Result<Self, RusqliteError> {
+        Ok(Self {
+            id: row.get(0)?,
+            filter_key: row.get(1)?,
+            year_id: row.get(2)?,
+            gender_id: row.get(3)?,
+            value: row.get(4)?,
+            holder: row.get(5)?,
+            set_in: row.get(6)?,
+            event_id: row.get(7)?,
+            form_id: row.get(8)?,
+            recorded_at: row.get(9)?,
+        })
+    }
+
+    /// Read records from a CSV with `event`, `year`, `gender`, `mark`, `holder` and
+    /// `set_in` columns. Events and years can be given by id or name, and marks are
+    /// written as scorers enter them. Returns a message for every row that doesn't
+    /// match the config.
+    pub fn from_csv(config: &Configuration, csv: &str) -> Result<Vec<Self>, Vec<String>> {
+        let rows = csv_import::read(
+            csv,
+            &["event", "year", "gender", "mark", "holder", "set_in"],
+        )?;
+
+        let mut records = vec![];
+        let mut errors = vec![];
+        for row in rows {
+            let [event, year, gender, mark, holder, set_in] = row.values.as_slice() else {
+                unreachable!("read returns the columns asked for")
+            };
+            let config_event = config
+                .events
+                .iter()
+                .find(|e| &e.id == event || e.name.eq_ignore_ascii_case(event));
+            let year_id = config
+                .years
+                .iter()
+                .find(|y| &y.id == year || y.name.eq_ignore_ascii_case(year))
+                .map(|y| y.id.clone());
+            let gender_id = config
+                .genders
+                .iter()
+                .find(|g| g.eq_ignore_ascii_case(gender))
+                .cloned();
+
+            let mut problems = vec![];
+            let mut value = None;
+            match config_event.map(|e| e.measurement.as_ref()) {
+                None => problems.push(format!("unknown event \"{event}\"")),
+                Some(None) => problems.push(format!("\"{event}\" isn't measured")),
+                Some(Some(measurement)) => match measurement.parse(mark) {
+                    Ok(parsed) => value = Some(parsed),
+                    Err(e) => problems.push(e),
+                },
+            }
+            if year_id.is_none() {
+                problems.push(format!("unknown year \"{year}\""));
+            }
+            if gender_id.is_none() {
+                problems.push(format!("unknown gender \"{gender}\""));
+            }
+            if holder.is_empty() {
+                problems.push("the holder is empty".to_string());
+            }
+            match (config_event, year_id, gender_id, value) {
+                (Some(event), Some(year_id), Some(gender_id), Some(value))
+                    if problems.is_empty() =>
+                {
+                    records.push(Self::new(
+                        event.id.clone(),
+                        year_id,
+                        gender_id,
+                        value,
+                        holder.clone(),
+                        set_in.clone(),
+                    ))
+                }
+                _ => errors.push(format!("line {}: {}", row.line, problems.join(", "))),
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(records)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Add records, skipping any already stored. Returns the number of rows imported.
+    pub async fn import(pool: &Pool, records: Vec<Self>) -> Result<usize, async_sqlite::Error> {
+        pool.conn_mut(move |conn| {
+            debug!("Importing {} Records", records.len());
+            let tx = conn.transaction()?;
+            for record in records.iter() {
+                tx.execute(
+                    "INSERT OR IGNORE INTO records(filter_key, year_id, gender_id, value, holder, set_in)
+                        VALUES (?1, ?2, ?3, ?4, ?5, ?6);",
+                    (
+                        &record.filter_key,
+                        &record.year_id,
+                        &record.gender_id,
+                        record.value,
+                        &record.holder,
+                        &record.set_in,
+                    ),
+                )?;
+            }
+            tx.commit()?;
+            Ok(records.len())
+        })
+        .await
+    }
+
+    /// Recheck an event's performances against the record for its event, year and
+    /// gender, after its scores change. Records it set before are dropped, and its best
+    /// performance becomes the record if it beats the best of the rest. The holder is
+    /// the competitor entered for the form, or the form if nobody was.
+    /// It's set in `set_in`, or the current year if that's `None`.
+    /// Returns the record it set, unless it was already held with the same mark or
+    /// there was no record to beat, in which case the mark is only a baseline.
+    pub async fn update_for_event(
+        pool: &Pool,
+        event: Events,
+        measurement: Measurement,
+        set_in: Option<String>,
+    ) -> Result<Option<Self>, async_sqlite::Error> {
+        pool.conn_mut(move |conn| {
+            let tx = conn.transaction()?;
+            let previous = Self::set_by(&tx, &event.id)?;
+            tx.execute("DELETE FROM records WHERE event_id = ?1;", [&event.id])?;
+
+            let order = if measurement.lower_is_better() {
+                "ASC"
+            } else {
+                "DESC"
+            };
+            let best: Option<(String, f64)> = tx
+                .query_row(
+                    &format!(
+                        "SELECT form_id, value FROM performances WHERE event_id = ?1
+                            ORDER BY value {order} LIMIT 1"
+                    ),
+                    [&event.id],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .optional()?;
+            let record: Option<f64> = tx
+                .query_row(
+                    &format!(
+                        "SELECT value FROM records
+                            WHERE filter_key = ?1 AND year_id = ?2 AND gender_id = ?3
+                            ORDER BY value {order} LIMIT 1"
+                    ),
+                    [&event.filter_key, &event.year_id, &event.gender_id],
+                    |row| row.get(0),
+                )
+                .optional()?;
+
+            let Some((form_id, value)) = best else {
+                tx.commit()?;
+                return Ok(None);
+            };
+            let baseline = record.is_none();
+            let beaten = match record {
+                None => true,
+                Some(record) if measurement.lower_is_better() => value < record,
+                Some(record) => value > record,
+            };
+            if !beaten {
+                tx.commit()?;
+                return Ok(None);
+            }
+
+            debug!(
+                "Event with id {} set a record of {} for {}",
+                event.id, value, event.filter_key
+            );
+            tx.execute(
+                "INSERT INTO records(filter_key, year_id, gender_id, value, holder, set_in, event_id, form_id)
+                    SELECT ?1, ?2, ?3, ?4,
+                        COALESCE(
+                            (SELECT competitors.name FROM event_entries
+                                JOIN competitors ON competitors.id = event_entries.competitor_id
+                                WHERE event_id = ?6 AND event_entries.form_id = ?7),
+                            (SELECT name FROM forms WHERE id = ?7),
+                            ?7
+                        ),
+                        COALESCE(?5, strftime('%Y', 'now')), ?6, ?7;",
+                (
+                    &event.filter_key,
+                    &event.year_id,
+                    &event.gender_id,
+                    value,
+                    &set_in,
+                    &event.id,
+                    &form_id,
+                ),
+            )?;
+            let set = Self::set_by(&tx, &event.id)?.pop();
+            tx.commit()?;
+
+            let already_held = previous
+                .iter()
+                .any(|record| record.form_id.as_ref() == Some(&form_id) && record.value == value);
+            Ok(set.filter(|_| !already_held && !baseline))
+        })
+        .await
+    }
+
+    /// The records an event set
+    fn set_by(tx: &Transaction, event_id: &str) -> Result<Vec<Self>, RusqliteError> {
+        let mut stmt = tx.prepare(&format!(
+            "SELECT {COLUMNS} FROM records WHERE event_id = ?1"
+        ))?;
+        let rows = stmt.query_map([event_id], Self::map_from_row)?;
+        rows.collect()
+    }
+
+    /// Every record, grouped by event, year and gender, oldest first
+    pub async fn all(pool: &Pool) -> Result<Vec<Self>, async_sqlite::Error> {
+        pool.conn(move |conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {COLUMNS} FROM records ORDER BY filter_key, year_id, gender_id, id"
+            ))?;
+            let record_iter = stmt.query_map([], Self::map_from_row)?;
+            let mut records = Vec::new();
+
+            for record in record_iter {
+                records.push(record?);
+            }
+            Ok(records)
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        configurator::parser::Score,
+        db::{
+            event_scores::{EventScores, ScoreUpdate},
+            forms::Forms,
+            score_changes::ChangedBy,
+            years::Years,
+        },
+        test_harness,
+    };
+
+    use super::*;
+    use crate::test_harness::config;
+
+    #[test]
+    fn from_csv_test() {
+        let records = Records::from_csv(
+            &config(),
+            "event,year,gender,mark,holder,set_in\n60m,Year 9,boys,7.9,Sam Smith,2019\n",
+        )
+        .unwrap();
+        assert_eq!(
+            records,
+            vec![Records::new(
+                "60m".to_string(),
+                "y9".to_string(),
+                "boys".to_string(),
+                7.9,
+                "Sam Smith".to_string(),
+                "2019".to_string()
+            )]
+        );
+
+        assert_eq!(
+            Records::from_csv(
+                &config(),
+                "event,year,gender,mark,holder,set_in\negg_and_spoon,y9,boys,1,,2019\n60m,y9,boys,fast,Sam,2019\n"
+            )
+            .unwrap_err(),
+            vec![
+                "line 2: unknown event \"egg_and_spoon\", the holder is empty",
+                "line 3: \"fast\" is not a valid performance"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn update_for_event_test() {
+        let db = test_harness::setup_db("records_update_for_event").await;
+        assert!(Years::new("y9".to_string(), "Year 9".to_string())
+            .insert(&db)
+            .await
+            .is_ok());
+        let event = Events::new(
+            "y9-boys-60m".to_string(),
+            "60m".to_string(),
+            "y9".to_string(),
+            "boys".to_string(),
+            "60m".to_string(),
+        );
+        assert!(event.clone().insert(&db).await.is_ok());
+        assert!(Forms::sync(&db, config().forms).await.is_ok());
+        assert!(Records::import(
+            &db,
+            vec![Records::new(
+                "60m".to_string(),
+                "y9".to_string(),
+                "boys".to_string(),
+                8.0,
+                "Sam Smith".to_string(),
+                "2019".to_string()
+            )]
+        )
+        .await
+        .is_ok());
+
+        let run = |w: f64| {
+            let score = Score {
+                name: "1st".to_string(),
+                value: 20,
+                default: false,
+            };
+            EventScores::set_batch(
+                &db,
+                vec![ScoreUpdate {
+                    event_id: "y9-boys-60m".to_string(),
+                    scores: vec![("w".to_string(), score)],
+                    performances: Some(vec![("w".to_string(), w)]),
+                    entries: None,
+                    revision: None,
+                }],
+                ChangedBy::default(),
+            )
+        };
+        let update = || {
+            Records::update_for_event(
+                &db,
+                event.clone(),
+                Measurement::Time,
+                Some("2026".to_string()),
+            )
+        };
+
+        assert!(run(8.2).await.is_ok());
+        assert_eq!(update().await.unwrap(), None);
+
+        assert!(run(7.8).await.is_ok());
+        let set = update().await.unwrap().unwrap();
+        assert_eq!((set.value, set.holder.as_str()), (7.8, "Winston"));
+        // Saving the same mark again isn't another record
+        assert_eq!(update().await.unwrap(), None);
+        assert_eq!(Records::all(&db).await.unwrap().len(), 2);
+
+        // Correcting the mark drops the record it set
+        assert!(run(8.1).await.is_ok());
+        assert_eq!(update().await.unwrap(), None);
+        assert_eq!(Records::all(&db).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn first_mark_is_a_baseline_test() {
+        let db = test_harness::setup_db("records_first_mark_is_a_baseline").await;
+        assert!(Years::new("y9".to_string(), "Year 9".to_string())
+            .insert(&db)
+            .await
+            .is_ok());
+        let event = Events::new(
+            "y9-girls-60m".to_string(),
+            "60m".to_string(),
+            "y9".to_string(),
+            "girls".to_string(),
+            "60m".to_string(),
+        );
+        assert!(event.clone().insert(&db).await.is_ok());
+        assert!(Forms::sync(&db, config().forms).await.is_ok());
+        assert!(EventScores::set_batch(
+            &db,
+            vec![ScoreUpdate {
+                event_id: event.id.clone(),
+                scores: vec![],
+                performances: Some(vec![("w".to_string(), 8.2)]),
+                entries: None,
+                revision: None,
+            }],
+            ChangedBy::default(),
+        )
+        .await
+        .is_ok());
+
+        // Nothing is announced, but later events have a mark to beat
+        assert_eq!(
+            Records::update_for_event(&db, event, Measurement::Time, None)
+                .await
+                .unwrap(),
+            None
+        );
+        let records = Records::all(&db).await.unwrap();
+        assert_eq!(
+            records
+                .iter()
+                .map(|record| (record.gender_id.as_str(), record.value))
+                .collect::<Vec<_>>(),
+            vec![("girls", 8.2)]
+        );
+    }
+}
@@ -0,0 +1,392 @@
+use async_sqlite::rusqlite::Error as RusqliteError;
+use async_sqlite::{
+    rusqlite::{Row, ToSql, Transaction},
+    Pool,
+};
+use log::debug;
+
+/// One form's score changing in an event, kept so disputes can be settled later
+#[derive(Clone, PartialEq, Debug)]
+pub struct ScoreChanges {
+    pub id: i64,
+    pub event_id: String,
+    pub form_id: String,
+    /// The score before the change, `None` if the form had no score yet
+    pub before_name: Option<String>,
+    pub before_points: Option<i64>,
+    /// The score after the change, `None` if it was removed
+    pub after_name: Option<String>,
+    pub after_points: Option<i64>,
+    pub changed_by: Option<i64>,
+    /// Email of `changed_by`, if the user still exists
+    pub changed_by_email: Option<String>,
+    /// The API token the change was made with, if it wasn't made by hand
+    pub changed_by_token: Option<i64>,
+    /// Name of `changed_by_token`, if the token still exists
+    pub changed_by_token_name: Option<String>,
+    /// The server's local time, like the timetable, as `YYYY-MM-DD HH:MM:SS`
+    pub changed_at: String,
+}
+
+/// Who made a change: a user, and the API token they made it with, if any
+#[derive(Clone, Copy, Default, Debug)]
+pub struct ChangedBy {
+    pub user_id: Option<i64>,
+    pub token_id: Option<i64>,
+}
+
+impl ChangedBy {
+    /// A change made by hand
+    pub fn user(user_id: Option<i64>) -> Self {
+        Self {
+            user_id,
+            token_id: None,
+        }
+    }
+}
+
+/// Filters for listing score changes, all optional
+#[derive(Default, Debug)]
+pub struct ScoreChangesFilter {
+    pub event_id: Option<String>,
+    pub user_id: Option<i64>,
+    /// Only changes at or after this local time (`YYYY-MM-DD HH:MM` or `YYYY-MM-DDTHH:MM`)
+    pub from: Option<String>,
+    /// Only changes at or before this local time
+    pub to: Option<String>,
+}
+
+/// A point in the score history to restore scores to
+#[derive(Clone, Debug)]
+pub enum RestorePoint {
+    /// Just before a change, for that change's event only
+    BeforeChange(i64),
+    /// A local time (`YYYY-MM-DD HH:MM` or `YYYY-MM-DDTHH:MM`), for every event
+    AsOf(String),
+}
+
+/// The (score name, points) a form had at a restore point, `None` if it had no score
+#[derive(Clone, Debug)]
+pub struct RestoreTarget {
+    pub event_id: String,
+    pub form_id: String,
+    pub score: Option<(String, i64)>,
+}
+
+impl ScoreChanges {
+    fn map_from_row(row: &Row) -> Result<Self, RusqliteError> {
+        Ok(Self {
+            id: row.get(0)?,
+            event_id: row.get(1)?,
+            form_id: row.get(2)?,
+            before_name: row.get(3)?,
+            before_points: row.get(4)?,
+            after_name: row.get(5)?,
+            after_points: row.get(6)?,
+            changed_by: row.get(7)?,
+            changed_by_email: row.get(8)?,
+            changed_at: row.get(9)?,
+            changed_by_token: row.get(10)?,
+            changed_by_token_name: row.get(11)?,
+        })
+    }
+
+    /// Who made the change as shown on the admin page, naming the API token if one
+    /// was used
+    pub fn changed_by(&self) -> String {
+        let email = self.changed_by_email.clone().unwrap_or_default();
+        match (self.changed_by_token, &self.changed_by_token_name) {
+            (None, _) => email,
+            (Some(_), Some(name)) => format!("{email} via token \"{name}\""),
+            (Some(id), None) => format!("{email} via deleted token #{id}"),
+        }
+    }
+
+    /// The score before the change as shown on the admin page, e.g. "1st (20)"
+    pub fn before(&self) -> String {
+        describe(&self.before_name, self.before_points)
+    }
+
+    pub fn after(&self) -> String {
+        describe(&self.after_name, self.after_points)
+    }
+
+    /// Record a form's score changing, as part of the transaction that changes it
+    pub fn record(
+        tx: &Transaction,
+        event_id: &str,
+        form_id: &str,
+        before: Option<(String, i64)>,
+        after: Option<(String, i64)>,
+        changed_by: ChangedBy,
+    ) -> Result<(), RusqliteError> {
+        debug!(
+            "Recording Score change for {} in Event {}",
+            form_id, event_id
+        );
+        let (before_name, before_points) = before.unzip();
+        let (after_name, after_points) = after.unzip();
+        tx.execute(
+            "INSERT INTO score_changes(event_id, form_id, before_name, before_points, after_name, after_points, changed_by, changed_by_token)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8);",
+            (
+                event_id,
+                form_id,
+                before_name,
+                before_points,
+                after_name,
+                after_points,
+                changed_by.user_id,
+                changed_by.token_id,
+            ),
+        )?;
+        Ok(())
+    }
+
+    /// The score each (event, form) had at a restore point, for every form whose score
+    /// has changed since. That's the `before` of the first change after the point.
+    pub fn restore_targets(
+        tx: &Transaction,
+        point: &RestorePoint,
+    ) -> Result<Vec<RestoreTarget>, RusqliteError> {
+        let first_changes = match point {
+            RestorePoint::BeforeChange(_) => {
+                "SELECT MIN(id) FROM score_changes
+                    WHERE event_id = (SELECT event_id FROM score_changes WHERE id = ?1) AND id >= ?1
+                    GROUP BY form_id"
+            }
+            RestorePoint::AsOf(_) => {
+                "SELECT MIN(id) FROM score_changes WHERE changed_at > datetime(?1, 'utc')
+                    GROUP BY event_id, form_id"
+            }
+        };
+        let param: Box<dyn ToSql> = match point {
+            RestorePoint::BeforeChange(id) => Box::new(*id),
+            RestorePoint::AsOf(time) => Box::new(time.replace('T', " ")),
+        };
+        let mut stmt = tx.prepare(&format!(
+            "SELECT event_id, form_id, before_name, before_points FROM score_changes
+                WHERE id IN ({first_changes}) ORDER BY id"
+        ))?;
+        let rows = stmt.query_map([param.as_ref()], |row| {
+            let before_name: Option<String> = row.get(2)?;
+            let before_points: Option<i64> = row.get(3)?;
+            Ok(RestoreTarget {
+                event_id: row.get(0)?,
+                form_id: row.get(1)?,
+                score: before_name.zip(before_points),
+            })
+        })?;
+        rows.collect()
+    }
+
+    /// Changes matching the filter, newest first
+    pub async fn r#where(
+        pool: &Pool,
+        filter: ScoreChangesFilter,
+    ) -> Result<Vec<Self>, async_sqlite::Error> {
+        // datetime-local inputs use a T separator, SQLite timestamps a space
+        let from = filter.from.map(|from| from.replace('T', " "));
+        let to = filter.to.map(|to| to.replace('T', " "));
+        pool.conn(move |conn| {
+            let mut stmt = conn.prepare(
+                "SELECT score_changes.id, event_id, form_id, before_name, before_points,
+                        after_name, after_points, changed_by, users.email,
+                        datetime(changed_at, 'localtime'), changed_by_token, api_tokens.name
+                    FROM score_changes
+                        LEFT JOIN users ON users.id = score_changes.changed_by
+                        LEFT JOIN api_tokens ON api_tokens.id = score_changes.changed_by_token
+                    WHERE (?1 IS NULL OR event_id = ?1)
+                        AND (?2 IS NULL OR changed_by = ?2)
+                        AND (?3 IS NULL OR changed_at >= datetime(?3, 'utc'))
+                        AND (?4 IS NULL OR changed_at <= datetime(?4, 'utc'))
+                    ORDER BY score_changes.id DESC",
+            )?;
+            let change_iter = stmt.query_map(
+                (filter.event_id, filter.user_id, from, to),
+                Self::map_from_row,
+            )?;
+            let mut changes = Vec::new();
+
+            for change in change_iter {
+                changes.push(change?);
+            }
+            Ok(changes)
+        })
+        .await
+    }
+}
+
+fn describe(name: &Option<String>, points: Option<i64>) -> String {
+    match (name, points) {
+        (Some(name), Some(points)) => format!("{name} ({points})"),
+        _ => "-".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        db::{api_tokens::ApiTokens, events::Events, users::Users, years::Years},
+        test_harness,
+    };
+
+    use super::*;
+
+    async fn record(db: &Pool, event_id: &str, after: i64, changed_by: ChangedBy) {
+        let event_id = event_id.to_string();
+        assert!(db
+            .conn_mut(move |conn| {
+                let tx = conn.transaction()?;
+                ScoreChanges::record(
+                    &tx,
+                    &event_id,
+                    "w",
+                    None,
+                    Some(("1st".to_string(), after)),
+                    changed_by,
+                )?;
+                tx.commit()
+            })
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn where_test() {
+        let db = test_harness::setup_db("score_changes_where").await;
+        assert!(Years::new("y9".to_string(), "Year 9".to_string())
+            .insert(&db)
+            .await
+            .is_ok());
+        for id in ["y9-boys-60m", "y9-boys-200m"] {
+            assert!(Events::new(
+                id.to_string(),
+                id.to_string(),
+                "y9".to_string(),
+                "boys".to_string(),
+                "test".to_string()
+            )
+            .insert(&db)
+            .await
+            .is_ok());
+        }
+        assert!(Users::new("tutor@example.com".to_string(), false, true)
+            .insert(&db)
+            .await
+            .is_ok());
+
+        record(&db, "y9-boys-60m", 20, ChangedBy::user(Some(1))).await;
+        record(&db, "y9-boys-200m", 15, ChangedBy::user(Some(1))).await;
+        record(&db, "y9-boys-60m", 10, ChangedBy::default()).await;
+
+        let all = ScoreChanges::r#where(&db, ScoreChangesFilter::default())
+            .await
+            .unwrap();
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[0].after_points, Some(10));
+        assert_eq!(
+            all[2].changed_by_email,
+            Some("tutor@example.com".to_string())
+        );
+
+        let sixty = ScoreChanges::r#where(
+            &db,
+            ScoreChangesFilter {
+                event_id: Some("y9-boys-60m".to_string()),
+                user_id: Some(1),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(sixty.len(), 1);
+        assert_eq!(sixty[0].after_points, Some(20));
+
+        let future = ScoreChanges::r#where(
+            &db,
+            ScoreChangesFilter {
+                from: Some("2999-01-01T00:00".to_string()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert!(future.is_empty());
+
+        // Filters take the same local time the changes are shown in
+        let shown = ScoreChanges::r#where(
+            &db,
+            ScoreChangesFilter {
+                from: Some(all[0].changed_at.clone()),
+                to: Some(all[0].changed_at.clone()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert!(shown.iter().any(|change| change.id == all[0].id));
+    }
+
+    #[tokio::test]
+    async fn changed_by_token_test() {
+        let db = test_harness::setup_db("score_changes_token").await;
+        assert!(Years::new("y9".to_string(), "Year 9".to_string())
+            .insert(&db)
+            .await
+            .is_ok());
+        assert!(Events::new(
+            "y9-boys-60m".to_string(),
+            "60m".to_string(),
+            "y9".to_string(),
+            "boys".to_string(),
+            "test".to_string()
+        )
+        .insert(&db)
+        .await
+        .is_ok());
+        assert!(Users::new("admin@example.com".to_string(), true, true)
+            .insert(&db)
+            .await
+            .is_ok());
+        assert!(
+            ApiTokens::new("Timing".to_string(), vec![], vec![], vec![], None)
+                .create(&db, Some(1))
+                .await
+                .is_ok()
+        );
+        let token = ApiTokens::all(&db).await.unwrap().remove(0);
+
+        record(
+            &db,
+            "y9-boys-60m",
+            20,
+            ChangedBy {
+                user_id: token.created_by,
+                token_id: token.id,
+            },
+        )
+        .await;
+        record(&db, "y9-boys-60m", 15, ChangedBy::user(Some(1))).await;
+
+        let changes = ScoreChanges::r#where(&db, ScoreChangesFilter::default())
+            .await
+            .unwrap();
+        assert_eq!(changes[0].changed_by_token, None);
+        assert_eq!(changes[0].changed_by(), "admin@example.com");
+        assert_eq!(changes[1].changed_by_token, token.id);
+        assert_eq!(
+            changes[1].changed_by(),
+            "admin@example.com via token \"Timing\""
+        );
+
+        assert!(ApiTokens::delete(&db, token.id.unwrap()).await.is_ok());
+        let changes = ScoreChanges::r#where(&db, ScoreChangesFilter::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            changes[1].changed_by(),
+            format!("admin@example.com via deleted token #{}", token.id.unwrap())
+        );
+    }
+}
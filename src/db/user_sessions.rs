@@ -47,26 +47,28 @@ impl UserSessions {
         pool.conn(move |conn| {
             let mut stmt = conn.prepare("SELECT * FROM user_sessions WHERE id = ?1")?;
             let session = stmt
-                .query_one([cookie_session.clone()], |row| Self::map_from_row(row))
+                .query_one([cookie_session.clone()], Self::map_from_row)
                 .optional()?;
             match session {
                 Some(session) => {
                     log::debug!("DB Session ID: {} (cookie: {cookie_session})", session.id);
-                    return Ok(VerifiedSession {
+                    Ok(VerifiedSession {
                         _id: cookie_session,
+                        user_id: Some(session.user_id),
                         verified: true,
                         has_admin: session.has_admin,
                         has_set_score: session.has_set_score,
-                    });
+                    })
                 }
                 None => {
                     log::debug!("No Session found in db");
-                    return Ok(VerifiedSession {
+                    Ok(VerifiedSession {
                         _id: cookie_session,
+                        user_id: None,
                         verified: false,
                         has_admin: false,
                         has_set_score: false,
-                    });
+                    })
                 }
             }
         })
@@ -74,8 +76,10 @@ impl UserSessions {
     }
 }
 
+#[derive(Clone)]
 pub struct VerifiedSession {
     pub _id: String,
+    pub user_id: Option<i64>,
     pub verified: bool,
     pub has_admin: bool,
     pub has_set_score: bool,
@@ -109,7 +113,7 @@ mod tests {
         let verified_session = UserSessions::verify(&db, session.id.clone()).await;
         assert!(verified_session.is_ok());
         let verified = verified_session.unwrap();
-        assert_eq!(verified.verified, true);
+        assert!(verified.verified);
         assert_eq!(verified._id, session.id);
     }
 
@@ -123,6 +127,6 @@ mod tests {
         let verified_session = UserSessions::verify(&db, "HelloWorld".to_string()).await;
         assert!(verified_session.is_ok());
         let verified = verified_session.unwrap();
-        assert_eq!(verified.verified, false);
+        assert!(!verified.verified);
     }
 }
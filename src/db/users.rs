@@ -315,13 +315,12 @@ mod tests {
                 .await
                 .is_ok()
         );
-        assert_eq!(
-            Users::find_by_id(1, &db)
+        assert!(
+            !Users::find_by_id(1, &db)
                 .await
                 .unwrap()
                 .unwrap()
-                .has_set_score,
-            false
+                .has_set_score
         );
     }
 }
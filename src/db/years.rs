@@ -1,3 +1,4 @@
+use async_sqlite::rusqlite::{Connection, Error as RusqliteError};
 use async_sqlite::{rusqlite::Row, Pool};
 
 use crate::db::events::Events;
@@ -26,18 +27,23 @@ impl Years {
         })
     }
 
+    /// Tests set up rows with this; reconciliation uses `insert_in` inside its transaction
+    #[cfg(test)]
     pub async fn insert(self, pool: &Pool) -> Result<Self, async_sqlite::Error> {
-        let id = self.id.clone();
-        let name = self.name.clone();
-        pool.conn(move |conn| {
-            conn.execute("INSERT INTO years(id, name) VALUES (?1, ?2);", [id, name])
-                .unwrap();
-            Ok(())
-        })
-        .await?;
+        let year = self.clone();
+        pool.conn(move |conn| year.insert_in(conn)).await?;
         Ok(self)
     }
 
+    /// Insert the year on `conn`, which can be a transaction
+    pub fn insert_in(&self, conn: &Connection) -> Result<(), RusqliteError> {
+        conn.execute(
+            "INSERT INTO years(id, name) VALUES (?1, ?2);",
+            [&self.id, &self.name],
+        )?;
+        Ok(())
+    }
+
     pub async fn all(pool: &Pool) -> Result<Vec<Self>, async_sqlite::Error> {
         pool.conn(move |conn| {
             let mut stmt = conn.prepare("SELECT * FROM years")?;
@@ -54,28 +60,27 @@ impl Years {
         .await
     }
 
+    /// Insert an event into this year, whatever `year_id` it was built with
+    #[cfg(test)]
     pub async fn new_event(
         mut self,
         pool: &Pool,
-        id: String,
-        name: String,
-        gender_id: String,
-        filter_key: String,
-        scores: String,
+        mut event: Events,
     ) -> Result<Self, async_sqlite::Error> {
-        let event = Events::new(id, name, self.clone().id, gender_id, filter_key, scores);
+        event.year_id = self.id.clone();
         self.events.push(event.clone());
-        event.insert(&pool).await?;
+        event.insert(pool).await?;
 
         Ok(self)
     }
 
-    pub async fn delete_all(pool: &Pool) -> Result<(), async_sqlite::Error> {
-        pool.conn(move |conn| {
-            conn.execute("DELETE FROM years;", []).unwrap();
-            Ok(())
-        })
-        .await?;
+    pub fn update_name_in(conn: &Connection, id: &str, name: &str) -> Result<(), RusqliteError> {
+        conn.execute("UPDATE years SET name = ?1 WHERE id = ?2;", [name, id])?;
+        Ok(())
+    }
+
+    pub fn delete_in(conn: &Connection, id: &str) -> Result<(), RusqliteError> {
+        conn.execute("DELETE FROM years WHERE id = ?1;", [id])?;
         Ok(())
     }
 }
@@ -145,37 +150,52 @@ mod tests {
         let result = obj
             .new_event(
                 &db,
-                "test-test".to_string(),
-                "Test Event".to_string(),
-                "mixed".to_string(),
-                "test-test".to_string(),
-                "{}".to_string(),
+                Events::new(
+                    "test-test".to_string(),
+                    "Test Event".to_string(),
+                    String::new(),
+                    "mixed".to_string(),
+                    "test-test".to_string(),
+                ),
             )
             .await;
         assert!(result.is_ok());
         obj = result.unwrap();
         assert_eq!(obj.events.len(), 1);
+        assert_eq!(obj.events[0].year_id, "test-test");
     }
 
     #[tokio::test]
-    async fn delete_all_test() {
-        let db = test_harness::setup_db("years_delete_all").await;
+    async fn update_name_test() {
+        let db = test_harness::setup_db("years_update_name").await;
         assert!(Years::new("test-test".to_string(), "Test".to_string())
             .insert(&db)
             .await
             .is_ok());
-        assert!(Years::new("test-test2".to_string(), "Test 2".to_string())
-            .insert(&db)
+        assert!(db
+            .conn(|conn| Years::update_name_in(conn, "test-test", "Renamed"))
             .await
             .is_ok());
-        assert!(Years::new("test-test3".to_string(), "Test 3".to_string())
+        assert_eq!(Years::all(&db).await.unwrap()[0].name, "Renamed");
+    }
+
+    #[tokio::test]
+    async fn delete_test() {
+        let db = test_harness::setup_db("years_delete").await;
+        assert!(Years::new("test-test".to_string(), "Test".to_string())
             .insert(&db)
             .await
             .is_ok());
-        assert!(Years::new("test-test4".to_string(), "Test 4".to_string())
+        assert!(Years::new("test-test2".to_string(), "Test 2".to_string())
             .insert(&db)
             .await
             .is_ok());
-        assert!(Years::delete_all(&db).await.is_ok());
+        assert!(db
+            .conn(|conn| Years::delete_in(conn, "test-test"))
+            .await
+            .is_ok());
+        let years = Years::all(&db).await.unwrap();
+        assert_eq!(years.len(), 1);
+        assert_eq!(years[0].id, "test-test2");
     }
 }
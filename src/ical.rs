@@ -0,0 +1,130 @@
+//! iCalendar (RFC 5545) feeds of the sports day timetable
+
+/// One event in a calendar feed
+#[derive(Debug, Clone, PartialEq)]
+pub struct CalendarEvent {
+    /// Stable across feeds and reconciles, so calendar apps update events in place
+    pub uid: String,
+    pub summary: String,
+    /// Start time on the day as `HH:MM`
+    pub start: String,
+    /// Length in minutes, if known
+    pub duration: Option<i64>,
+    pub location: Option<String>,
+    pub description: Option<String>,
+}
+
+/// Render a calendar of events on `date` (`YYYY-MM-DD`). `stamp` is when the feed last
+/// changed, as `YYYY-MM-DD HH:MM:SS` in UTC. Times are left floating, so calendar apps
+/// show them as the time on the day wherever they are.
+pub fn calendar(name: &str, date: &str, stamp: &str, events: &[CalendarEvent]) -> String {
+    let date = date.replace('-', "");
+    let stamp = format!("{}Z", stamp.replace(['-', ':'], "").replace(' ', "T"));
+
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//sportsday-scoreboard//schedule//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+        format!("X-WR-CALNAME:{}", escape(name)),
+    ];
+    for event in events {
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!("UID:{}", event.uid));
+        lines.push(format!("DTSTAMP:{stamp}"));
+        lines.push(format!("DTSTART:{date}T{}00", event.start.replace(':', "")));
+        if let Some(duration) = event.duration {
+            lines.push(format!("DURATION:PT{duration}M"));
+        }
+        lines.push(format!("SUMMARY:{}", escape(&event.summary)));
+        if let Some(location) = &event.location {
+            lines.push(format!("LOCATION:{}", escape(location)));
+        }
+        if let Some(description) = &event.description {
+            lines.push(format!("DESCRIPTION:{}", escape(description)));
+        }
+        lines.push("END:VEVENT".to_string());
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    lines
+        .iter()
+        .map(|line| fold(line))
+        .collect::<Vec<_>>()
+        .join("\r\n")
+        + "\r\n"
+}
+
+/// Escape a TEXT value
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Split a content line into lines of at most 75 octets, continuing each with a space
+fn fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_events_on_the_day() {
+        let feed = calendar(
+            "Year 9",
+            "2026-07-10",
+            "2026-07-01 12:00:00",
+            &[CalendarEvent {
+                uid: "y9-boys-60m@sportsday".to_string(),
+                summary: "Year 9 boys 60m".to_string(),
+                start: "09:30".to_string(),
+                duration: Some(10),
+                location: Some("Track, lane 1".to_string()),
+                description: None,
+            }],
+        );
+        assert_eq!(
+            feed,
+            [
+                "BEGIN:VCALENDAR",
+                "VERSION:2.0",
+                "PRODID:-//sportsday-scoreboard//schedule//EN",
+                "CALSCALE:GREGORIAN",
+                "X-WR-CALNAME:Year 9",
+                "BEGIN:VEVENT",
+                "UID:y9-boys-60m@sportsday",
+                "DTSTAMP:20260701T120000Z",
+                "DTSTART:20260710T093000",
+                "DURATION:PT10M",
+                "SUMMARY:Year 9 boys 60m",
+                "LOCATION:Track\\, lane 1",
+                "END:VEVENT",
+                "END:VCALENDAR",
+                ""
+            ]
+            .join("\r\n")
+        );
+    }
+
+    #[test]
+    fn folds_long_lines() {
+        let line = format!("DESCRIPTION:{}", "é".repeat(40));
+        let folded = fold(&line);
+        assert!(folded.split("\r\n").all(|line| line.len() <= 75));
+        assert_eq!(folded.replace("\r\n ", ""), line);
+    }
+}
@@ -0,0 +1,26 @@
+import { Controller } from "@hotwired/stimulus";
+
+export default class extends Controller {
+  static override targets = ["output"];
+  static override values = {
+    channel: String,
+    duration: Number,
+  };
+
+  override connect() {
+    document.addEventListener("wsmessage", (e: any) => {
+      if (e.detail.channel != this.channelValue) return;
+      // Show each announcement for a while, newest first
+      const announcement = document.createElement("div");
+      announcement.innerHTML = e.detail.data;
+      this.outputTarget.prepend(announcement);
+      setTimeout(() => announcement.remove(), this.durationValue);
+    });
+  }
+
+  declare readonly hasOutputTarget: boolean;
+  declare readonly outputTarget: HTMLDivElement;
+
+  declare channelValue: string;
+  declare durationValue: number;
+}
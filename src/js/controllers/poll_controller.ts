@@ -0,0 +1,29 @@
+import { Controller } from "@hotwired/stimulus";
+
+export default class extends Controller {
+  static override values = {
+    url: String,
+    interval: Number,
+  };
+
+  override connect() {
+    this.timer = setInterval(async () => {
+      try {
+        const res = await fetch(this.urlValue);
+        if (res.ok) {
+          this.element.innerHTML = await res.text();
+        }
+      } catch (e) {
+        console.log(`Polling ${this.urlValue} failed: `, e);
+      }
+    }, this.intervalValue);
+  }
+
+  override disconnect() {
+    clearInterval(this.timer);
+  }
+
+  declare timer: ReturnType<typeof setInterval>;
+  declare urlValue: string;
+  declare intervalValue: number;
+}
@@ -17,7 +17,11 @@ export default class extends Controller {
         this.lockValue = String(true);
         console.log("Score Manager: Obtained Lock");
         const data = JSON.parse(this.containerTarget.innerText);
-        data[e.detail.event_id] = e.detail.scores;
+        data[e.detail.event_id] = {
+          revision: e.detail.revision,
+          scores: e.detail.scores,
+          competitors: e.detail.competitors,
+        };
         this.containerTarget.innerText = JSON.stringify(data);
         this.lockValue = String(false);
         console.log("Score Manager: Released Lock");
@@ -61,13 +65,18 @@ export default class extends Controller {
         method: "POST",
         body: this.containerTarget.innerText,
       });
-      if (res.status == 204) {
+      if (res.ok) {
         this.containerTarget.innerText = "{}";
         document.dispatchEvent(
           new CustomEvent("scoreUpdateSubmitted", {
             detail: {},
           }),
         );
+        document.dispatchEvent(
+          new CustomEvent("scoresSaved", {
+            detail: await res.json(),
+          }),
+        );
         document.dispatchEvent(
           new CustomEvent("updateStatus", {
             detail: {
@@ -75,6 +84,57 @@ export default class extends Controller {
             },
           }),
         );
+      } else if (res.status == 400) {
+        const body: { errors: { field: string; message: string }[] } =
+          await res.json();
+        console.log(`Set Scores rejected:`, body.errors);
+        const p = document.createElement("p");
+        p.style.color = "red";
+        p.innerText = `Scores not saved: ${body.errors
+          .map((error) => `${error.field}: ${error.message}`)
+          .join(", ")}`;
+        document.dispatchEvent(
+          new CustomEvent("updateStatus", {
+            detail: {
+              status: p.outerHTML,
+            },
+          }),
+        );
+      } else if (res.status == 409) {
+        // Nothing was saved. Drop the conflicting events so the rest are sent again,
+        // and let their rows show what they have now.
+        const body: {
+          conflicts: {
+            event_id: string;
+            revision: number;
+            status: string;
+            values: Record<string, string>;
+            competitors: Record<string, number>;
+          }[];
+        } = await res.json();
+        console.log(`Set Scores conflicted:`, body.conflicts);
+        const data = JSON.parse(this.containerTarget.innerText);
+        body.conflicts.forEach((current) => {
+          delete data[current.event_id];
+          document.dispatchEvent(
+            new CustomEvent("scoreConflict", {
+              detail: { current },
+            }),
+          );
+        });
+        this.containerTarget.innerText = JSON.stringify(data);
+        const p = document.createElement("p");
+        p.style.color = "red";
+        p.innerText = `Someone else changed the scores for ${body.conflicts
+          .map((current) => current.event_id)
+          .join(", ")}. Their scores are shown, check them and set again.`;
+        document.dispatchEvent(
+          new CustomEvent("updateStatus", {
+            detail: {
+              status: p.outerHTML,
+            },
+          }),
+        );
       } else {
         console.log(`Set Scores failed with status ${res.status}:`, res.body);
         document.dispatchEvent(
@@ -1,9 +1,19 @@
 import { Controller } from "@hotwired/stimulus";
 
+type CurrentScores = {
+  event_id: string;
+  revision: number;
+  status: string;
+  values: Record<string, string>;
+  competitors: Record<string, number>;
+};
+
 export default class extends Controller {
-  static override targets = ["field", "form", "button"];
+  static override targets = ["field", "competitor", "form", "button"];
   static override values = {
     status: String,
+    revision: Number,
+    eventStatus: String,
   };
 
   override connect() {
@@ -14,6 +24,54 @@ export default class extends Controller {
         this.statusValue = "completed";
       }
     });
+
+    // Someone (maybe us) saved scores, keep rows without unsent changes up to date
+    document.addEventListener("wsmessage", (e: any) => {
+      if (e.detail.channel != "set_scores") return;
+      const changed: CurrentScores[] = JSON.parse(e.detail.data);
+      changed.forEach((current) => {
+        if (this.statusValue != "pending") this.refresh(current);
+      });
+    });
+    document.addEventListener("scoresSaved", (e: any) => {
+      e.detail.saved.forEach((current: CurrentScores) => this.refresh(current));
+    });
+
+    document.addEventListener("scoreConflict", (e: any) => {
+      const current: CurrentScores = e.detail.current;
+      if (current.event_id != this.formTarget.id) return;
+      console.log("Setting Button Target to Red");
+      this.refresh(current);
+      this.buttonTarget.style.backgroundColor = "red";
+      this.buttonTarget.title =
+        "Someone else changed these scores, the latest are shown. Check them and set again.";
+      this.statusValue = "conflict";
+    });
+  }
+
+  refresh(current: CurrentScores) {
+    if (current.event_id != this.formTarget.id) return;
+    if (current.revision <= this.revisionValue) return;
+    if (current.status != this.eventStatusValue) {
+      // The status decides what the row offers, so reload once unsent scores are saved
+      console.log(`${current.event_id} is now ${current.status}, reloading`);
+      document.dispatchEvent(
+        new CustomEvent("doSafeScoreRedirect", {
+          detail: { params: location.search },
+        }),
+      );
+      return;
+    }
+    console.log(`Refreshing ${current.event_id} to revision ${current.revision}`);
+    this.revisionValue = current.revision;
+    this.fieldTargets.forEach((field) => {
+      const value = current.values[field.getAttribute("data-form-id")!];
+      field.value = value ?? (field instanceof HTMLSelectElement ? "0" : "");
+    });
+    this.competitorTargets.forEach((select) => {
+      const competitor = current.competitors[select.getAttribute("data-form-id")!];
+      select.value = competitor?.toString() ?? "";
+    });
   }
 
   submit() {
@@ -23,23 +81,35 @@ export default class extends Controller {
       let form_id = val.id.split("-")[3];
       obj[form_id!] = val.value;
     });
+    const competitors: Record<string, string> = {};
+    this.competitorTargets.forEach((select) => {
+      competitors[select.getAttribute("data-form-id")!] = select.value;
+    });
 
     document.dispatchEvent(
       new CustomEvent("scoreUpdate", {
-        detail: { event_id: this.formTarget.id, scores: obj },
+        detail: {
+          event_id: this.formTarget.id,
+          revision: this.revisionValue,
+          scores: obj,
+          competitors,
+        },
       }),
     );
 
     if (this.hasButtonTarget) {
       console.log("Setting Button Target to Yellow");
       this.buttonTarget.style.backgroundColor = "yellow";
+      this.buttonTarget.title = "";
       this.statusValue = "pending";
     }
   }
 
   declare readonly hasFieldTarget: boolean;
   declare readonly fieldTarget: HTMLInputElement;
-  declare readonly fieldTargets: HTMLSelectElement[];
+  declare readonly fieldTargets: (HTMLSelectElement | HTMLInputElement)[];
+
+  declare readonly competitorTargets: HTMLSelectElement[];
 
   declare readonly hasFormTarget: boolean;
   declare readonly formTarget: HTMLInputElement;
@@ -51,4 +121,10 @@ export default class extends Controller {
 
   declare statusValue: string;
   declare readonly hasStatusValue: boolean;
+
+  declare revisionValue: number;
+  declare readonly hasRevisionValue: boolean;
+
+  declare eventStatusValue: string;
+  declare readonly hasEventStatusValue: boolean;
 }
@@ -1,22 +1,29 @@
-use std::io::{Error, ErrorKind};
+use std::io::Error;
 
 use actix::{Actor, Addr};
 use actix_files::Files;
 use actix_web::{middleware as ActixMiddleware, web, App, HttpServer};
 use async_sqlite::PoolBuilder;
-use log::debug;
 
 use crate::{
-    configurator::parser::Configuration,
-    middleware::authentication::{AuthConfig, Authentication},
+    configurator::reload::{SharedConfig, CONFIG_PATH},
+    db::api_tokens::Scope,
+    middleware::{
+        api_token::ApiTokenAuthentication,
+        authentication::{AuthConfig, Authentication},
+    },
     websocket::ChannelsActor,
 };
 
+mod cli;
 mod configurator;
+mod csv_import;
 mod db;
+mod ical;
 mod middleware;
 mod prometheus;
 mod routes;
+mod scoring;
 mod templates;
 mod utils;
 mod websocket;
@@ -29,6 +36,17 @@ async fn main() -> std::io::Result<()> {
     // Init Logging/Environment
     dotenv::dotenv().ok();
     env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match cli::Command::from_args(&args) {
+        Ok(cli::Command::Serve) => {}
+        Ok(command) => std::process::exit(cli::run(command).await?),
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(2);
+        }
+    }
+
     let host = std::env::var("HOST").unwrap_or_else(|_| "127.0.0.1".to_string());
     let port = std::env::var("PORT")
         .unwrap_or_else(|_| "3000".to_string())
@@ -47,10 +65,7 @@ async fn main() -> std::io::Result<()> {
         }
         Err(e) => {
             log::error!("Error estalishing DB pool {e}");
-            return Err(Error::new(
-                ErrorKind::Other,
-                "database pool could not be established",
-            ));
+            return Err(Error::other("database pool could not be established"));
         }
     };
 
@@ -58,7 +73,7 @@ async fn main() -> std::io::Result<()> {
         Ok(_) => log::info!("Ran Migrations"),
         Err(e) => {
             log::error!("Database Migrations failed {e}");
-            return Err(Error::new(ErrorKind::Other, "database migrations failed"));
+            return Err(Error::other("database migrations failed"));
         }
     }
 
@@ -69,22 +84,28 @@ async fn main() -> std::io::Result<()> {
         .unwrap();
 
     // Create the Plan & Run it
-    let config = match configurator::parser::Configuration::from_yaml_file("./config.yaml") {
+    let config_yaml = std::fs::read_to_string(CONFIG_PATH)?;
+    let config = match configurator::parser::Configuration::from_yaml_str(&config_yaml) {
         Ok(config) => {
-            let plan = configurator::build::build_plan(config.clone());
-            // Check if the version has already been built
-            if std::fs::exists("./version.txt").unwrap() {
-                if std::fs::read_to_string("./version.txt").unwrap() == config.get_version() {
-                    debug!("Config Version matches DB, not rebuilding");
-                } else {
-                    debug!("Config Version doesn't match DB, rebuilding");
-                    configurator::run::run(plan, &pool).await.unwrap();
-                    std::fs::write("./version.txt", config.get_version())?;
+            if let Err(errors) = config.validate() {
+                eprintln!("Error validating config: {}", errors);
+                std::process::exit(1);
+            }
+            match db::event_scores::EventScores::import_blobs(&pool, &config).await {
+                Ok(0) => {}
+                Ok(imported) => log::info!("Imported {imported} scores from events.scores"),
+                Err(e) => {
+                    log::error!("Importing events.scores failed {e}");
+                    return Err(Error::other("database migrations failed"));
+                }
+            }
+            match configurator::run::apply(&config, &config_yaml, &pool).await {
+                Ok(Some(diff)) => log_plan_diff(diff),
+                Ok(None) => {}
+                Err(e) => {
+                    log::error!("Error applying config: {e}");
+                    std::process::exit(1);
                 }
-            } else {
-                debug!("Version state doesn't exist, rebuilding");
-                configurator::run::run(plan, &pool).await.unwrap();
-                std::fs::write("./version.txt", config.get_version())?;
             }
             config
         }
@@ -94,7 +115,9 @@ async fn main() -> std::io::Result<()> {
         }
     };
 
+    let config = SharedConfig::new(config);
     let ws_channels: Addr<ChannelsActor> = ChannelsActor::new().start();
+    configurator::reload::watch(config.clone(), pool.clone(), ws_channels.clone());
 
     HttpServer::new(move || {
         App::new()
@@ -114,19 +137,80 @@ async fn main() -> std::io::Result<()> {
             .service(Files::new("assets/", "assets/"))
             .service(routes::index::get)
             .service(routes::scoreboard::get)
+            .service(routes::records::get)
+            .service(routes::awards::get)
+            .service(routes::api::docs::openapi_json)
+            .service(routes::api::docs::get)
+            .service(
+                web::scope("/api/v1")
+                    .service(routes::api::years)
+                    .service(routes::api::forms)
+                    .service(routes::api::events::list)
+                    .service(routes::api::events::get)
+                    .service(routes::api::events::scores)
+                    .service(routes::api::standings)
+                    .service(routes::api::awards)
+                    .service(
+                        web::scope("/scores")
+                            .wrap(ApiTokenAuthentication::new(Scope::ScoresWrite))
+                            .service(routes::api::scores::post),
+                    ),
+            )
             .service(routes::results::get)
+            .service(routes::results::get_csv)
+            .service(routes::results::get_xlsx)
+            .service(routes::schedule::get)
+            .service(routes::schedule::ics)
+            .service(routes::schedule::feed_ics)
+            .service(routes::schedule::now_next_panel)
             .service(routes::ws::get)
             .service(routes::oauth::callback_get)
             .service(
                 web::scope("/set_scores")
                     .wrap(Authentication::new(AuthConfig::require_set_score()))
                     .service(routes::set_scores::get)
-                    .service(routes::set_scores::post),
+                    .service(routes::set_scores::post)
+                    .service(routes::set_scores::status),
             )
             .service(
                 web::scope("/admin")
                     .wrap(Authentication::new(AuthConfig::require_admin()))
                     .service(routes::admin::get)
+                    .service(routes::admin::archived_events)
+                    .service(routes::admin::list_events)
+                    .service(routes::admin::event_status)
+                    .service(routes::admin::score_changes)
+                    .service(routes::admin::revert_score_change)
+                    .service(routes::admin::restore_scores_as_of)
+                    .service(routes::admin::reload_config)
+                    .service(
+                        web::scope("/competitors")
+                            .service(routes::admin::competitors::list)
+                            .service(routes::admin::competitors::import)
+                            .service(routes::admin::competitors::delete),
+                    )
+                    .service(
+                        web::scope("/api_tokens")
+                            .service(routes::admin::api_tokens::list)
+                            .service(routes::admin::api_tokens::create)
+                            .service(routes::admin::api_tokens::delete),
+                    )
+                    .service(
+                        web::scope("/records")
+                            .service(routes::admin::records::get)
+                            .service(routes::admin::records::import),
+                    )
+                    .service(
+                        web::scope("/score_import")
+                            .service(routes::admin::score_import::get)
+                            .service(routes::admin::score_import::preview)
+                            .service(routes::admin::score_import::import),
+                    )
+                    .service(
+                        web::scope("/config_versions")
+                            .service(routes::admin::config_versions::list)
+                            .service(routes::admin::config_versions::show),
+                    )
                     .service(
                         web::scope("/users")
                             .service(routes::admin::users::list)
@@ -143,9 +227,18 @@ async fn main() -> std::io::Result<()> {
     Ok(())
 }
 
+fn log_plan_diff(diff: configurator::diff::PlanDiff) {
+    if diff.is_empty() {
+        log::info!("Database already matches config, nothing to reconcile");
+    }
+    for change in diff.changes.iter() {
+        log::info!("{change}");
+    }
+}
+
 struct AppState {
     client: reqwest::Client,
-    config: Configuration,
+    config: SharedConfig,
     oauth_creds: OauthCreds,
     pool: async_sqlite::Pool,
 }
@@ -0,0 +1,137 @@
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+
+use actix_web::{
+    body::EitherBody,
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header,
+    Error, HttpMessage, HttpResponse,
+};
+
+use crate::{
+    db::api_tokens::{ApiTokens, Scope},
+    routes::api::ApiError,
+};
+
+/// API token authentication middleware. Requests need an unexpired token with the
+/// scope as `Authorization: Bearer <token>`, which is stored in the request
+/// extensions for handlers to check what else it's limited to.
+pub struct ApiTokenAuthentication {
+    scope: Scope,
+}
+
+impl ApiTokenAuthentication {
+    pub fn new(scope: Scope) -> Self {
+        Self { scope }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for ApiTokenAuthentication
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = ApiTokenAuthenticationMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ApiTokenAuthenticationMiddleware {
+            service: Rc::new(service),
+            scope: self.scope,
+        }))
+    }
+}
+
+pub struct ApiTokenAuthenticationMiddleware<S> {
+    service: Rc<S>,
+    scope: Scope,
+}
+
+impl<S, B> Service<ServiceRequest> for ApiTokenAuthenticationMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let scope = self.scope;
+
+        Box::pin(async move {
+            let unauthorized = |error: &str| {
+                HttpResponse::Unauthorized()
+                    .append_header((header::WWW_AUTHENTICATE, "Bearer"))
+                    .json(ApiError {
+                        error: error.to_string(),
+                    })
+                    .map_into_right_body()
+            };
+
+            let token = req
+                .headers()
+                .get(header::AUTHORIZATION)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.strip_prefix("Bearer "))
+                .map(|token| token.trim().to_string());
+            let Some(token) = token else {
+                log::debug!("No bearer token found");
+                return Ok(req.into_response(unauthorized("A bearer token is required")));
+            };
+
+            let pool = match req.app_data::<actix_web::web::Data<crate::AppState>>() {
+                Some(state) => state.pool.clone(),
+                None => {
+                    log::error!("Could not get database pool from app state");
+                    return Ok(req.into_response(
+                        HttpResponse::InternalServerError()
+                            .body("Internal server error")
+                            .map_into_right_body(),
+                    ));
+                }
+            };
+
+            let token = match ApiTokens::verify(&pool, &token).await {
+                Ok(Some(token)) => token,
+                Ok(None) => {
+                    log::debug!("Unknown or expired API token");
+                    return Ok(req.into_response(unauthorized("Unknown or expired token")));
+                }
+                Err(e) => {
+                    log::error!("Error verifying API token: {}", e);
+                    return Ok(req.into_response(
+                        HttpResponse::InternalServerError()
+                            .body("Internal server error")
+                            .map_into_right_body(),
+                    ));
+                }
+            };
+
+            if !token.scopes.contains(&scope) {
+                log::debug!("API token {} does not have {}", token.name, scope.as_str());
+                return Ok(req.into_response(
+                    HttpResponse::Forbidden()
+                        .json(ApiError {
+                            error: format!("The token needs the {} scope", scope.as_str()),
+                        })
+                        .map_into_right_body(),
+                ));
+            }
+
+            req.extensions_mut().insert(token);
+
+            let res = service.call(req).await?;
+            Ok(res.map_into_left_body())
+        })
+    }
+}
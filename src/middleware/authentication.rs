@@ -12,7 +12,7 @@ use actix_web::{
 use crate::db::user_sessions::UserSessions;
 
 /// Configuration for the authentication middleware
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct AuthConfig {
     /// Require the user to have `has_admin` permission
     pub require_admin: bool,
@@ -20,15 +20,6 @@ pub struct AuthConfig {
     pub require_set_score: bool,
 }
 
-impl Default for AuthConfig {
-    fn default() -> Self {
-        Self {
-            require_admin: false,
-            require_set_score: false,
-        }
-    }
-}
-
 impl AuthConfig {
     /// Create a new AuthConfig requiring admin permissions
     pub fn require_admin() -> Self {
@@ -66,6 +66,7 @@ where
                 != Some(&header::HeaderValue::from_static(
                     "text/html; charset=utf-8",
                 ))
+                && !headers.contains_key(header::CACHE_CONTROL)
             {
                 headers.insert(
                     header::CACHE_CONTROL,
@@ -1,2 +1,3 @@
+pub mod api_token;
 pub mod authentication;
 pub mod headers;
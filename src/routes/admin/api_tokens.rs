@@ -0,0 +1,204 @@
+use actix_web::{get, post, web, HttpResponse};
+use askama::Template;
+
+use crate::{
+    configurator::parser::{is_date, minutes_of_day, Configuration},
+    db::{
+        api_tokens::{split_ids, ApiTokens, Scope},
+        events::Events,
+        user_sessions::VerifiedSession,
+    },
+    templates::AdminApiTokensTemplate,
+    AppState,
+};
+
+async fn render(
+    state: &AppState,
+    created: Option<String>,
+    errors: Vec<String>,
+) -> AdminApiTokensTemplate {
+    AdminApiTokensTemplate {
+        tokens: ApiTokens::all(&state.pool).await.unwrap(),
+        created,
+        errors,
+    }
+}
+
+#[get("")]
+pub async fn list(state: web::Data<AppState>) -> HttpResponse {
+    HttpResponse::Ok().body(
+        render(&state, None, vec![])
+            .await
+            .render()
+            .expect("Template should be valid"),
+    )
+}
+
+/// Make a token, showing it once on the page
+#[post("")]
+pub async fn create(
+    state: web::Data<AppState>,
+    form: web::Form<CreateProps>,
+    session: web::ReqData<VerifiedSession>,
+) -> HttpResponse {
+    let events = Events::all(&state.pool).await.unwrap();
+    let token = match token_from_form(&state.config.get(), &events, form.into_inner()) {
+        Ok(token) => token,
+        Err(errors) => {
+            return HttpResponse::BadRequest().body(
+                render(&state, None, errors)
+                    .await
+                    .render()
+                    .expect("Template should be valid"),
+            )
+        }
+    };
+    let created = token.create(&state.pool, session.user_id).await.unwrap();
+
+    HttpResponse::Ok().body(
+        render(&state, Some(created), vec![])
+            .await
+            .render()
+            .expect("Template should be valid"),
+    )
+}
+
+#[post("/{id}/delete")]
+pub async fn delete(state: web::Data<AppState>, path: web::Path<i64>) -> HttpResponse {
+    ApiTokens::delete(&state.pool, path.into_inner())
+        .await
+        .unwrap();
+
+    HttpResponse::Found()
+        .append_header(("Location", "/admin/api_tokens"))
+        .finish()
+}
+
+#[derive(serde::Deserialize)]
+pub struct CreateProps {
+    name: String,
+    scores_write: Option<String>,
+    /// Comma separated year ids
+    years: String,
+    /// Comma separated event ids or config event ids
+    events: String,
+    /// From a datetime-local input, so `YYYY-MM-DDTHH:MM`
+    expires_at: String,
+}
+
+/// Check a new token's limits against the config and events
+fn token_from_form(
+    config: &Configuration,
+    events: &[Events],
+    form: CreateProps,
+) -> Result<ApiTokens, Vec<String>> {
+    let mut errors = vec![];
+    let name = form.name.trim().to_string();
+    if name.is_empty() {
+        errors.push("Give the token a name".to_string());
+    }
+    let scopes: Vec<Scope> = [(Scope::ScoresWrite, &form.scores_write)]
+        .into_iter()
+        .filter(|(_, checked)| checked.as_deref() == Some("on"))
+        .map(|(scope, _)| scope)
+        .collect();
+    if scopes.is_empty() {
+        errors.push("Pick at least one scope".to_string());
+    }
+    let year_ids = split_ids(&form.years);
+    for year_id in year_ids.iter() {
+        if !config.years.iter().any(|year| &year.id == year_id) {
+            errors.push(format!("Unknown year \"{year_id}\""));
+        }
+    }
+    let event_ids = split_ids(&form.events);
+    for event_id in event_ids.iter() {
+        if !events
+            .iter()
+            .any(|event| &event.id == event_id || &event.filter_key == event_id)
+        {
+            errors.push(format!("Unknown event \"{event_id}\""));
+        }
+    }
+    let expires_at = match form.expires_at.trim() {
+        "" => None,
+        expires_at => match expires_at.split_once('T') {
+            // datetime-local inputs use a T separator, SQLite timestamps a space
+            Some((date, time)) if is_date(date) && minutes_of_day(time).is_some() => {
+                Some(format!("{date} {time}:00"))
+            }
+            _ => {
+                errors.push(format!(
+                    "Expiry must be a date and time like 2026-07-01T17:30, got \"{expires_at}\""
+                ));
+                None
+            }
+        },
+    };
+
+    if errors.is_empty() {
+        Ok(ApiTokens::new(
+            name, scopes, year_ids, event_ids, expires_at,
+        ))
+    } else {
+        Err(errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_harness::config;
+
+    fn form(name: &str, scores_write: bool, years: &str, events: &str) -> CreateProps {
+        CreateProps {
+            name: name.to_string(),
+            scores_write: scores_write.then(|| "on".to_string()),
+            years: years.to_string(),
+            events: events.to_string(),
+            expires_at: "2026-07-01T17:30".to_string(),
+        }
+    }
+
+    #[test]
+    fn token_limits_are_checked_against_the_config() {
+        let events = vec![Events::new(
+            "y9-boys-60m".to_string(),
+            "60m".to_string(),
+            "y9".to_string(),
+            "boys".to_string(),
+            "60m".to_string(),
+        )];
+        let token = token_from_form(
+            &config(),
+            &events,
+            form(" Timing ", true, "y9, y10", "60m,y9-boys-60m"),
+        )
+        .unwrap();
+        assert_eq!(token.name, "Timing");
+        assert_eq!(token.scopes, vec![Scope::ScoresWrite]);
+        assert_eq!(token.year_ids, vec!["y9", "y10"]);
+        assert_eq!(token.expires_at.as_deref(), Some("2026-07-01 17:30:00"));
+
+        assert_eq!(
+            token_from_form(&config(), &events, form("", false, "y7", "relay")).unwrap_err(),
+            vec![
+                "Give the token a name",
+                "Pick at least one scope",
+                "Unknown year \"y7\"",
+                "Unknown event \"relay\"",
+            ]
+        );
+
+        for expires_at in ["2026-07-01T17", "2026-07-01 17:30", "2026-13-01T17:30"] {
+            let mut form = form("Timing", true, "", "");
+            form.expires_at = expires_at.to_string();
+            assert_eq!(
+                token_from_form(&config(), &events, form).unwrap_err(),
+                vec![format!(
+                    "Expiry must be a date and time like 2026-07-01T17:30, got \"{expires_at}\""
+                )]
+            );
+        }
+    }
+}
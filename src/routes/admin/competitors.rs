@@ -0,0 +1,59 @@
+use actix_web::{get, post, web, HttpResponse};
+use askama::Template;
+
+use crate::{db::competitors::Competitors, templates::AdminCompetitorsTemplate, AppState};
+
+#[get("")]
+pub async fn list(state: web::Data<AppState>) -> HttpResponse {
+    let competitors = Competitors::all(&state.pool).await.unwrap();
+
+    HttpResponse::Ok().body(
+        AdminCompetitorsTemplate {
+            competitors,
+            csv: String::new(),
+            errors: vec![],
+        }
+        .render()
+        .expect("Template should be valid"),
+    )
+}
+
+/// Import competitors from a CSV. Nothing is imported if any row is invalid.
+#[post("")]
+pub async fn import(state: web::Data<AppState>, form: web::Form<ImportProps>) -> HttpResponse {
+    let competitors = match Competitors::from_csv(&state.config.get(), &form.csv) {
+        Ok(competitors) => competitors,
+        Err(errors) => {
+            return HttpResponse::BadRequest().body(
+                AdminCompetitorsTemplate {
+                    competitors: Competitors::all(&state.pool).await.unwrap(),
+                    csv: form.into_inner().csv,
+                    errors,
+                }
+                .render()
+                .expect("Template should be valid"),
+            )
+        }
+    };
+    Competitors::import(&state.pool, competitors).await.unwrap();
+
+    HttpResponse::Found()
+        .append_header(("Location", "/admin/competitors"))
+        .finish()
+}
+
+#[post("/{id}/delete")]
+pub async fn delete(state: web::Data<AppState>, path: web::Path<i64>) -> HttpResponse {
+    Competitors::delete(&state.pool, path.into_inner())
+        .await
+        .unwrap();
+
+    HttpResponse::Found()
+        .append_header(("Location", "/admin/competitors"))
+        .finish()
+}
+
+#[derive(serde::Deserialize)]
+pub struct ImportProps {
+    csv: String,
+}
@@ -0,0 +1,46 @@
+use actix_web::{get, web, HttpResponse};
+use askama::Template;
+
+use crate::{
+    db,
+    templates::{AdminConfigVersionsListTemplate, AdminConfigVersionsShowTemplate},
+    AppState,
+};
+
+#[get("")]
+pub async fn list(state: web::Data<AppState>) -> HttpResponse {
+    let versions = db::config_versions::ConfigVersions::all(&state.pool)
+        .await
+        .unwrap();
+
+    HttpResponse::Ok().body(
+        AdminConfigVersionsListTemplate { versions }
+            .render()
+            .expect("Template should be valid"),
+    )
+}
+
+#[get("/{id}")]
+pub async fn show(state: web::Data<AppState>, params: web::Path<PathProps>) -> HttpResponse {
+    let version = match db::config_versions::ConfigVersions::find_by_id(params.id, &state.pool)
+        .await
+        .unwrap()
+    {
+        Some(version) => version,
+        None => return HttpResponse::NotFound().body("Config version not found"),
+    };
+    let plan = serde_json::from_str::<serde_json::Value>(version.plan.as_str())
+        .and_then(|plan| serde_json::to_string_pretty(&plan))
+        .unwrap_or_else(|_| version.plan.clone());
+
+    HttpResponse::Ok().body(
+        AdminConfigVersionsShowTemplate { version, plan }
+            .render()
+            .expect("Template should be valid"),
+    )
+}
+
+#[derive(serde::Deserialize)]
+struct PathProps {
+    id: i64,
+}
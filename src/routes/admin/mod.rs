@@ -1,9 +1,29 @@
+pub mod api_tokens;
+pub mod competitors;
+pub mod config_versions;
+pub mod records;
+pub mod score_import;
 pub mod users;
 
-use actix_web::{get, HttpResponse};
+use actix_web::{get, post, web, HttpResponse};
 use askama::Template;
 
-use crate::templates::AdminIndexTemplate;
+use crate::{
+    configurator::reload,
+    db::{
+        self,
+        event_scores::EventScores,
+        score_changes::{RestorePoint, ScoreChanges, ScoreChangesFilter},
+        user_sessions::VerifiedSession,
+    },
+    routes::set_scores::{change_status, StatusProps},
+    templates::{
+        AdminArchivedEventsTemplate, AdminEventsTemplate, AdminIndexTemplate,
+        AdminScoreChangesTemplate,
+    },
+    websocket::ChannelsActor,
+    AppState,
+};
 
 #[get("")]
 pub async fn get() -> HttpResponse {
@@ -13,3 +33,161 @@ pub async fn get() -> HttpResponse {
             .expect("Template should be valid"),
     )
 }
+
+#[get("/archived_events")]
+pub async fn archived_events(state: web::Data<AppState>) -> HttpResponse {
+    let events = db::archived_events::ArchivedEvents::all(&state.pool)
+        .await
+        .unwrap();
+
+    HttpResponse::Ok().body(
+        AdminArchivedEventsTemplate { events }
+            .render()
+            .expect("Template should be valid"),
+    )
+}
+
+#[get("/events")]
+pub async fn list_events(state: web::Data<AppState>) -> HttpResponse {
+    let events = db::events::Events::all(&state.pool).await.unwrap();
+
+    HttpResponse::Ok().body(
+        AdminEventsTemplate { events }
+            .render()
+            .expect("Template should be valid"),
+    )
+}
+
+#[post("/events/{event_id}/status")]
+pub async fn event_status(
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+    form: web::Form<StatusProps>,
+    channels: web::Data<actix::Addr<ChannelsActor>>,
+) -> HttpResponse {
+    match change_status(&state, &channels, path.into_inner(), form.status, true).await {
+        Ok(()) => HttpResponse::Found()
+            .append_header(("Location", "/admin/events"))
+            .finish(),
+        Err(response) => response,
+    }
+}
+
+#[get("/score_changes")]
+pub async fn score_changes(
+    state: web::Data<AppState>,
+    params: web::Query<ScoreChangesParams>,
+) -> HttpResponse {
+    let params = params.into_inner();
+    // Empty filter inputs are submitted as empty strings
+    let non_empty = |value: Option<String>| value.filter(|value| !value.is_empty());
+    let filter = ScoreChangesFilter {
+        event_id: non_empty(params.event.clone()),
+        user_id: non_empty(params.user.clone()).and_then(|user| user.parse().ok()),
+        from: non_empty(params.from.clone()),
+        to: non_empty(params.to.clone()),
+    };
+    let changes = ScoreChanges::r#where(&state.pool, filter).await.unwrap();
+    let events = db::events::Events::all(&state.pool).await.unwrap();
+    let users = db::users::Users::all(&state.pool).await.unwrap();
+
+    HttpResponse::Ok().body(
+        AdminScoreChangesTemplate {
+            changes,
+            events,
+            users,
+            event: params.event.unwrap_or_default(),
+            user: params.user.unwrap_or_default(),
+            from: params.from.unwrap_or_default(),
+            to: params.to.unwrap_or_default(),
+        }
+        .render()
+        .expect("Template should be valid"),
+    )
+}
+
+#[derive(serde::Deserialize)]
+pub struct ScoreChangesParams {
+    event: Option<String>,
+    user: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+}
+
+#[post("/score_changes/{id}/revert")]
+pub async fn revert_score_change(
+    state: web::Data<AppState>,
+    path: web::Path<i64>,
+    session: web::ReqData<VerifiedSession>,
+    channels: web::Data<actix::Addr<ChannelsActor>>,
+) -> HttpResponse {
+    restore_scores(
+        &state,
+        RestorePoint::BeforeChange(path.into_inner()),
+        &session,
+        &channels,
+    )
+    .await
+}
+
+#[post("/score_changes/restore")]
+pub async fn restore_scores_as_of(
+    state: web::Data<AppState>,
+    form: web::Form<RestoreProps>,
+    session: web::ReqData<VerifiedSession>,
+    channels: web::Data<actix::Addr<ChannelsActor>>,
+) -> HttpResponse {
+    if form.as_of.is_empty() {
+        return HttpResponse::BadRequest().body("Pick a time to restore scores to");
+    }
+    restore_scores(
+        &state,
+        RestorePoint::AsOf(form.into_inner().as_of),
+        &session,
+        &channels,
+    )
+    .await
+}
+
+#[derive(serde::Deserialize)]
+pub struct RestoreProps {
+    as_of: String,
+}
+
+/// Restore scores to a point in their history and push the new scores to every screen
+async fn restore_scores(
+    state: &AppState,
+    point: RestorePoint,
+    session: &VerifiedSession,
+    channels: &actix::Addr<ChannelsActor>,
+) -> HttpResponse {
+    let restored = match EventScores::restore(&state.pool, point.clone(), session.user_id).await {
+        Ok(restored) => restored,
+        Err(e) => {
+            log::error!("Error restoring scores to {point:?}: {e}");
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    log::info!("Restored scores for {} events to {point:?}", restored.len());
+
+    let config = state.config.get();
+    crate::utils::publish_scores(&state.pool, &config, channels, &restored).await;
+    crate::utils::check_records(&state.pool, &config, channels, &restored).await;
+
+    HttpResponse::Found()
+        .append_header(("Location", "/admin/score_changes"))
+        .finish()
+}
+
+#[post("/reload_config")]
+pub async fn reload_config(
+    state: web::Data<AppState>,
+    channels: web::Data<actix::Addr<ChannelsActor>>,
+) -> HttpResponse {
+    match reload::reload(&state.config, &state.pool, &channels).await {
+        Ok(_) => HttpResponse::Found()
+            .append_header(("Location", "/admin"))
+            .finish(),
+        Err(e) => HttpResponse::BadRequest().body(format!("Config reload failed: {e}")),
+    }
+}
@@ -0,0 +1,44 @@
+use actix_web::{get, post, web, HttpResponse};
+use askama::Template;
+
+use crate::{db::records::Records, templates::AdminRecordsTemplate, AppState};
+
+#[get("")]
+pub async fn get() -> HttpResponse {
+    HttpResponse::Ok().body(
+        AdminRecordsTemplate {
+            csv: String::new(),
+            errors: vec![],
+        }
+        .render()
+        .expect("Template should be valid"),
+    )
+}
+
+/// Seed records from a CSV. Nothing is imported if any row is invalid.
+#[post("")]
+pub async fn import(state: web::Data<AppState>, form: web::Form<ImportProps>) -> HttpResponse {
+    let records = match Records::from_csv(&state.config.get(), &form.csv) {
+        Ok(records) => records,
+        Err(errors) => {
+            return HttpResponse::BadRequest().body(
+                AdminRecordsTemplate {
+                    csv: form.into_inner().csv,
+                    errors,
+                }
+                .render()
+                .expect("Template should be valid"),
+            )
+        }
+    };
+    Records::import(&state.pool, records).await.unwrap();
+
+    HttpResponse::Found()
+        .append_header(("Location", "/records"))
+        .finish()
+}
+
+#[derive(serde::Deserialize)]
+pub struct ImportProps {
+    csv: String,
+}
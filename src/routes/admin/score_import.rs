@@ -0,0 +1,433 @@
+use std::collections::HashMap;
+
+use actix_web::{get, post, web, HttpResponse};
+use askama::Template;
+use serde_json::{json, Map, Value};
+
+use crate::{
+    configurator::parser::Configuration,
+    csv_import,
+    db::{
+        competitors::Competitors,
+        event_scores::{BatchOutcome, EventScores, ScoreUpdate},
+        events::Events,
+        score_changes::ChangedBy,
+        user_sessions::VerifiedSession,
+    },
+    routes::set_scores::{validate, Submission},
+    templates::{AdminScoreImportTemplate, ImportedEvent},
+    utils::{self, CurrentScores},
+    websocket::ChannelsActor,
+    AppState,
+};
+
+#[get("")]
+pub async fn get() -> HttpResponse {
+    HttpResponse::Ok().body(
+        AdminScoreImportTemplate {
+            csv: String::new(),
+            revisions: String::new(),
+            errors: vec![],
+            preview: vec![],
+        }
+        .render()
+        .expect("Template should be valid"),
+    )
+}
+
+/// Check a CSV of scores and show what importing it would save
+#[post("/preview")]
+pub async fn preview(state: web::Data<AppState>, form: web::Form<ImportProps>) -> HttpResponse {
+    let csv = form.into_inner().csv;
+    let (updates, events) = match check(&state, &csv).await {
+        Ok(checked) => checked,
+        Err(errors) => return rejected(csv, errors),
+    };
+    let config = state.config.get();
+    let saved: HashMap<(String, String), EventScores> = EventScores::all(&state.pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|score| ((score.event_id.clone(), score.form_id.clone()), score))
+        .collect();
+    let revisions = updates
+        .iter()
+        .map(|update| {
+            format!(
+                "{}={}",
+                update.event_id,
+                update.revision.unwrap_or_default()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    let preview = updates
+        .into_iter()
+        .map(|update| {
+            let event = &events[&update.event_id];
+            ImportedEvent {
+                name: event.name.clone(),
+                year_id: event.year_id.clone(),
+                gender_id: event.gender_id.clone(),
+                scores: update
+                    .scores
+                    .into_iter()
+                    .map(|(form_id, score)| {
+                        let replaces = saved
+                            .get(&(update.event_id.clone(), form_id.clone()))
+                            .filter(|saved| {
+                                saved.score_name != score.name || saved.points != score.value
+                            })
+                            .map(|saved| format!("{} ({})", saved.score_name, saved.points));
+                        let form_name = config
+                            .forms
+                            .iter()
+                            .find(|form| form.id == form_id)
+                            .map_or(form_id, |form| form.name.clone());
+                        (form_name, score, replaces)
+                    })
+                    .collect(),
+            }
+        })
+        .collect();
+    HttpResponse::Ok().body(
+        AdminScoreImportTemplate {
+            csv,
+            revisions,
+            errors: vec![],
+            preview,
+        }
+        .render()
+        .expect("Template should be valid"),
+    )
+}
+
+/// Save a previewed CSV of scores in one go, then push the new scores to every screen.
+/// Nothing is saved if any row is invalid or an event has changed since the preview.
+#[post("")]
+pub async fn import(
+    state: web::Data<AppState>,
+    form: web::Form<ImportProps>,
+    session: web::ReqData<VerifiedSession>,
+    channels: web::Data<actix::Addr<ChannelsActor>>,
+) -> HttpResponse {
+    let ImportProps { csv, revisions } = form.into_inner();
+    let (mut updates, events) = match check(&state, &csv).await {
+        Ok(checked) => checked,
+        Err(errors) => return rejected(csv, errors),
+    };
+    // Save against the revisions that were previewed, so scores set in the meantime
+    // aren't overwritten
+    let previewed: HashMap<&str, i64> = revisions
+        .split(',')
+        .filter_map(|revision| revision.split_once('='))
+        .filter_map(|(event_id, revision)| Some((event_id, revision.parse().ok()?)))
+        .collect();
+    for update in updates.iter_mut() {
+        if let Some(revision) = previewed.get(update.event_id.as_str()) {
+            update.revision = Some(*revision);
+        }
+    }
+
+    let event_ids: Vec<String> = updates
+        .iter()
+        .map(|update| update.event_id.clone())
+        .collect();
+    match EventScores::set_batch(&state.pool, updates, ChangedBy::user(session.user_id)).await {
+        Ok(BatchOutcome::Saved) => {}
+        Ok(BatchOutcome::Stale(stale)) => {
+            let errors = stale
+                .iter()
+                .map(|event_id| {
+                    let name = events.get(event_id).map_or(event_id.as_str(), |e| &e.name);
+                    format!(
+                        "{name} ({event_id}) has changed since the preview, check the CSV again"
+                    )
+                })
+                .collect();
+            return rejected(csv, errors);
+        }
+        Err(e) => {
+            log::error!("Error importing scores: {e}");
+            return HttpResponse::InternalServerError().finish();
+        }
+    }
+
+    let config = state.config.get();
+    utils::publish_scores(&state.pool, &config, &channels, &event_ids).await;
+    utils::check_records(&state.pool, &config, &channels, &event_ids).await;
+    HttpResponse::Found()
+        .append_header(("Location", "/results"))
+        .finish()
+}
+
+#[derive(serde::Deserialize)]
+pub struct ImportProps {
+    csv: String,
+    /// `event id=revision` pairs for the events shown in the preview
+    #[serde(default)]
+    revisions: String,
+}
+
+fn rejected(csv: String, errors: Vec<String>) -> HttpResponse {
+    HttpResponse::BadRequest().body(
+        AdminScoreImportTemplate {
+            csv,
+            revisions: String::new(),
+            errors,
+            preview: vec![],
+        }
+        .render()
+        .expect("Template should be valid"),
+    )
+}
+
+/// Check a CSV of scores the same way scores set by scorers are checked, returning
+/// the updates to save with the events they're for, or a message for every problem
+async fn check(
+    state: &AppState,
+    csv: &str,
+) -> Result<(Vec<ScoreUpdate>, HashMap<String, Events>), Vec<String>> {
+    let config = state.config.get();
+    let all_events = Events::all(&state.pool).await.unwrap();
+    let entrants = utils::entrants(
+        &config,
+        &all_events,
+        &EventScores::all(&state.pool).await.unwrap(),
+    );
+    let events: HashMap<String, Events> = all_events
+        .into_iter()
+        .map(|event| (event.id.clone(), event))
+        .collect();
+    let competitors = Competitors::all(&state.pool).await.unwrap();
+
+    let (mut submission, lines) = submission_from_csv(&config, &events, csv)?;
+    let listed: Vec<String> = submission
+        .as_object()
+        .map(|events| events.keys().cloned().collect())
+        .unwrap_or_default();
+    keep_unlisted(
+        &mut submission,
+        &utils::current_scores(&state.pool, &config, &listed).await,
+    );
+    let submission_check = Submission {
+        events: &events,
+        entrants: &entrants,
+        competitors: &competitors,
+    };
+    match validate(&config, &submission_check, &submission.to_string()) {
+        Ok(updates) => Ok((updates, events)),
+        Err(errors) => Err(errors
+            .into_iter()
+            .map(|error| match lines.get(&error.field) {
+                Some(line) => format!("line {line}: {}", error.message),
+                None => format!("{}: {}", error.field, error.message),
+            })
+            .collect()),
+    }
+}
+
+/// Turn a CSV with `event`, `form` and `placing` columns into a set-scores submission,
+/// along with the line each event and form came from. `event` is an event id, or a
+/// config event id or name when the optional `year` and `gender` columns are given.
+/// `placing` is the name or value of a score, or a performance for measured events.
+fn submission_from_csv(
+    config: &Configuration,
+    events: &HashMap<String, Events>,
+    csv: &str,
+) -> Result<(Value, HashMap<String, usize>), Vec<String>> {
+    let rows =
+        csv_import::read_with_optional(csv, &["event", "form", "placing"], &["year", "gender"])?;
+
+    let mut submission = Map::new();
+    let mut lines = HashMap::new();
+    let mut errors = vec![];
+    for row in rows {
+        let [event, form, placing, year, gender] = row.values.as_slice() else {
+            unreachable!("read_with_optional returns the columns asked for")
+        };
+        let found = if year.is_empty() && gender.is_empty() {
+            events.get(event)
+        } else {
+            let config_event = config
+                .events
+                .iter()
+                .find(|e| &e.id == event || e.name.eq_ignore_ascii_case(event));
+            let year_id = config
+                .years
+                .iter()
+                .find(|y| &y.id == year || y.name.eq_ignore_ascii_case(year));
+            // Heats have to be given by their event id
+            config_event.zip(year_id).and_then(|(config_event, year)| {
+                events.values().find(|e| {
+                    e.filter_key == config_event.id
+                        && e.year_id == year.id
+                        && e.gender_id.eq_ignore_ascii_case(gender)
+                        && e.heat.is_none()
+                })
+            })
+        };
+        let Some(found) = found else {
+            errors.push(format!("line {}: unknown event \"{event}\"", row.line));
+            continue;
+        };
+        let Some(form_id) = config
+            .forms
+            .iter()
+            .find(|f| &f.id == form || f.name.eq_ignore_ascii_case(form))
+            .map(|f| f.id.clone())
+        else {
+            errors.push(format!("line {}: unknown form \"{form}\"", row.line));
+            continue;
+        };
+
+        let field = format!("{}.{form_id}", found.id);
+        if let Some(line) = lines.get(&field) {
+            errors.push(format!(
+                "line {}: {form} is already placed in {} on line {line}",
+                row.line, found.name
+            ));
+            continue;
+        }
+        lines.insert(field, row.line);
+        lines.entry(found.id.clone()).or_insert(row.line);
+
+        let measured = config
+            .event_for_filter_key(&found.filter_key)
+            .is_some_and(|e| e.measurement.is_some());
+        let value = match measured {
+            true => placing.clone(),
+            false => config
+                .scores_for(found.scoring.as_deref())
+                .iter()
+                .find(|score| score.name.eq_ignore_ascii_case(placing))
+                .map_or(placing.clone(), |score| score.value.to_string()),
+        };
+        let scored = submission
+            .entry(found.id.clone())
+            .or_insert_with(|| json!({ "revision": found.revision, "scores": {} }));
+        scored["scores"][form_id] = Value::String(value);
+    }
+
+    if errors.is_empty() {
+        Ok((Value::Object(submission), lines))
+    } else {
+        Err(errors)
+    }
+}
+
+/// Fill in the forms a submission leaves out of an event with what they have now, so
+/// importing some forms of an event doesn't reset the rest
+fn keep_unlisted(submission: &mut Value, current: &[CurrentScores]) {
+    for event in current {
+        let Some(scores) = submission
+            .get_mut(&event.event_id)
+            .and_then(|event| event["scores"].as_object_mut())
+        else {
+            continue;
+        };
+        for (form_id, value) in event.values.iter() {
+            scores
+                .entry(form_id.clone())
+                .or_insert_with(|| Value::String(value.clone()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{db::events::EventStatus, test_harness::config};
+
+    fn events() -> HashMap<String, Events> {
+        ["60m", "javelin"]
+            .iter()
+            .map(|id| {
+                let mut event = Events::new(
+                    format!("y9-boys-{id}"),
+                    id.to_string(),
+                    "y9".to_string(),
+                    "boys".to_string(),
+                    id.to_string(),
+                );
+                event.revision = 3;
+                (event.id.clone(), event)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn rows_become_a_submission() {
+        let config = config();
+        let first = config.scores[0].clone();
+        let form = config.forms[0].clone();
+        let csv = format!(
+            "event,year,gender,form,placing\n\
+             y9-boys-60m,,,{},8.4\n\
+             javelin,y9,Boys,{},{}\n",
+            form.id,
+            form.name,
+            first.name.to_uppercase()
+        );
+        let (submission, lines) = submission_from_csv(&config, &events(), &csv).unwrap();
+        assert_eq!(
+            submission,
+            json!({
+                "y9-boys-60m": { "revision": 3, "scores": { &form.id: "8.4" } },
+                "y9-boys-javelin": { "revision": 3, "scores": { &form.id: first.value.to_string() } }
+            })
+        );
+        assert_eq!(lines[&format!("y9-boys-javelin.{}", form.id)], 3);
+        assert_eq!(lines["y9-boys-60m"], 2);
+    }
+
+    #[test]
+    fn every_bad_row_is_reported() {
+        let form = config().forms[0].id.clone();
+        let csv = format!(
+            "event,form,placing\n\
+             y9-boys-nope,{form},1st\n\
+             y9-boys-60m,nope,8.4\n\
+             y9-boys-60m,{form},8.4\n\
+             y9-boys-60m,{form},8.5\n"
+        );
+        assert_eq!(
+            submission_from_csv(&config(), &events(), &csv).unwrap_err(),
+            vec![
+                "line 2: unknown event \"y9-boys-nope\"".to_string(),
+                "line 3: unknown form \"nope\"".to_string(),
+                format!("line 5: {form} is already placed in 60m on line 4"),
+            ]
+        );
+    }
+
+    #[test]
+    fn unlisted_forms_keep_their_scores() {
+        let current = |event_id: &str, values: &[(&str, &str)]| CurrentScores {
+            event_id: event_id.to_string(),
+            revision: 3,
+            status: EventStatus::InProgress,
+            values: values
+                .iter()
+                .map(|(form_id, value)| (form_id.to_string(), value.to_string()))
+                .collect(),
+            competitors: HashMap::new(),
+        };
+        let mut submission = json!({
+            "y9-boys-60m": { "revision": 3, "scores": { "w": "8.2" } }
+        });
+        keep_unlisted(
+            &mut submission,
+            &[
+                current("y9-boys-60m", &[("w", "8.40"), ("e", "8.90")]),
+                current("y9-boys-javelin", &[("w", "20")]),
+            ],
+        );
+        assert_eq!(
+            submission,
+            json!({
+                "y9-boys-60m": { "revision": 3, "scores": { "w": "8.2", "e": "8.90" } }
+            })
+        );
+    }
+}
@@ -0,0 +1,165 @@
+use actix_web::{get, HttpResponse};
+use askama::Template;
+use serde_json::Value;
+use utoipa::OpenApi;
+
+use crate::{routes::api::ApiDoc, templates::ApiDocsTemplate};
+
+/// The OpenAPI document, as read by companion apps
+#[get("/api/openapi.json")]
+pub async fn openapi_json() -> HttpResponse {
+    HttpResponse::Ok().json(ApiDoc::openapi())
+}
+
+/// The OpenAPI document as a page
+#[get("/api/docs")]
+pub async fn get() -> HttpResponse {
+    let doc = serde_json::to_value(ApiDoc::openapi()).expect("the API doc should serialize");
+    HttpResponse::Ok().body(
+        ApiDocsTemplate {
+            operations: operations(&doc),
+            schemas: schemas(&doc),
+        }
+        .render()
+        .expect("Template should be valid"),
+    )
+}
+
+pub struct DocsOperation {
+    pub method: String,
+    pub path: String,
+    pub summary: String,
+    pub description: String,
+    pub parameters: Vec<DocsField>,
+    /// (status, description, body type)
+    pub responses: Vec<(String, String, String)>,
+}
+
+pub struct DocsSchema {
+    pub name: String,
+    pub description: String,
+    pub fields: Vec<DocsField>,
+}
+
+/// A parameter of an operation or a property of a schema
+pub struct DocsField {
+    pub name: String,
+    /// Where a parameter goes, or the type of a property
+    pub kind: String,
+    pub required: bool,
+    pub description: String,
+}
+
+fn text(value: &Value, key: &str) -> String {
+    value[key].as_str().unwrap_or_default().to_string()
+}
+
+fn operations(doc: &Value) -> Vec<DocsOperation> {
+    let mut operations = vec![];
+    for (path, item) in doc["paths"].as_object().into_iter().flatten() {
+        for (method, operation) in item.as_object().into_iter().flatten() {
+            operations.push(DocsOperation {
+                method: method.to_uppercase(),
+                path: path.clone(),
+                summary: text(operation, "summary"),
+                description: text(operation, "description"),
+                parameters: operation["parameters"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .map(|parameter| DocsField {
+                        name: text(parameter, "name"),
+                        kind: format!(
+                            "{}, {}",
+                            text(parameter, "in"),
+                            type_name(&parameter["schema"])
+                        ),
+                        required: parameter["required"].as_bool().unwrap_or_default(),
+                        description: text(parameter, "description"),
+                    })
+                    .collect(),
+                responses: operation["responses"]
+                    .as_object()
+                    .into_iter()
+                    .flatten()
+                    .map(|(status, response)| {
+                        (
+                            status.clone(),
+                            text(response, "description"),
+                            type_name(&response["content"]["application/json"]["schema"]),
+                        )
+                    })
+                    .collect(),
+            });
+        }
+    }
+    operations
+}
+
+fn schemas(doc: &Value) -> Vec<DocsSchema> {
+    doc["components"]["schemas"]
+        .as_object()
+        .into_iter()
+        .flatten()
+        .map(|(name, schema)| {
+            let required: Vec<&str> = schema["required"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+                .collect();
+            DocsSchema {
+                name: name.clone(),
+                description: text(schema, "description"),
+                fields: schema["properties"]
+                    .as_object()
+                    .into_iter()
+                    .flatten()
+                    .map(|(field, property)| DocsField {
+                        name: field.clone(),
+                        kind: type_name(property),
+                        required: required.contains(&field.as_str()),
+                        description: text(property, "description"),
+                    })
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+/// A short name for a schema, e.g. `Award`, `[Award]` or `string | null`
+fn type_name(schema: &Value) -> String {
+    if let Some(reference) = schema["$ref"].as_str() {
+        return reference.rsplit('/').next().unwrap_or_default().to_string();
+    }
+    if let Some(variants) = schema["oneOf"].as_array() {
+        return variants
+            .iter()
+            .map(type_name)
+            .collect::<Vec<_>>()
+            .join(" | ");
+    }
+    if let Some(values) = schema["enum"].as_array() {
+        return values
+            .iter()
+            .map(|value| value.to_string())
+            .collect::<Vec<_>>()
+            .join(" | ");
+    }
+    let types: Vec<&str> = match &schema["type"] {
+        Value::String(kind) => vec![kind.as_str()],
+        Value::Array(kinds) => kinds.iter().filter_map(Value::as_str).collect(),
+        _ => vec![],
+    };
+    types
+        .into_iter()
+        .map(|kind| match kind {
+            "array" => format!("[{}]", type_name(&schema["items"])),
+            "object" if schema["additionalProperties"].is_object() => {
+                format!("{{string: {}}}", type_name(&schema["additionalProperties"]))
+            }
+            kind => kind.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(" | ")
+}
@@ -0,0 +1,238 @@
+use actix_web::{get, web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    configurator::parser::Heat,
+    db::{
+        event_entries::EventEntries,
+        event_scores::EventScores,
+        events::{EventStatus, Events},
+        performances::Performances,
+    },
+    routes::api::{paginated, ApiError, Page, Pagination},
+    scoring, AppState,
+};
+
+/// The same filters as the set-scores page
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct EventsFilter {
+    /// Year id
+    year: Option<String>,
+    /// Config event id, shared by the event in every year and gender
+    activity: Option<String>,
+    /// Gender id
+    group: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ApiEvent {
+    pub id: String,
+    pub name: String,
+    pub year_id: String,
+    pub gender_id: String,
+    pub activity_id: String,
+    /// Scoring scheme id, or `None` for the global scores
+    pub scoring: Option<String>,
+    pub multiplier: i64,
+    pub revision: i64,
+    pub status: EventStatus,
+    /// `HH:MM`, in the server's local time
+    pub start: Option<String>,
+    pub venue: Option<String>,
+    /// Minutes
+    pub duration: Option<i64>,
+    pub heat: Option<Heat>,
+}
+
+impl From<Events> for ApiEvent {
+    fn from(event: Events) -> Self {
+        Self {
+            id: event.id,
+            name: event.name,
+            year_id: event.year_id,
+            gender_id: event.gender_id,
+            activity_id: event.filter_key,
+            scoring: event.scoring,
+            multiplier: event.multiplier,
+            revision: event.revision,
+            status: event.status,
+            start: event.timeslot.start,
+            venue: event.timeslot.venue,
+            duration: event.timeslot.duration,
+            heat: event.heat,
+        }
+    }
+}
+
+/// A form's result in an event
+#[derive(Serialize, ToSchema)]
+pub struct ApiScore {
+    pub form_id: String,
+    /// Name of the awarded score, e.g. "1st"
+    pub score: String,
+    /// The placing shown on results, e.g. "=1st", for forms that placed
+    pub placing: Option<String>,
+    /// Points before the event's multiplier is applied
+    pub base_points: i64,
+    /// Points counted towards the standings
+    pub points: i64,
+    /// The performance in seconds or metres, for measured events
+    pub performance: Option<f64>,
+    /// The performance formatted as on results, e.g. "12.30s"
+    pub performance_label: Option<String>,
+    pub competitor: Option<ApiCompetitor>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ApiCompetitor {
+    pub id: i64,
+    pub name: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ApiEventScores {
+    pub event_id: String,
+    pub revision: i64,
+    pub status: EventStatus,
+    pub scores: Vec<ApiScore>,
+}
+
+/// Events, filtered like the set-scores page
+#[utoipa::path(
+    operation_id = "list_events",
+    tag = "events",
+    params(EventsFilter, Pagination),
+    responses(
+        (status = 200, body = Page<ApiEvent>),
+        (status = 400, description = "The page is out of range", body = ApiError)
+    )
+)]
+#[get("/events")]
+pub async fn list(
+    state: web::Data<AppState>,
+    filter: web::Query<EventsFilter>,
+    pagination: web::Query<Pagination>,
+) -> HttpResponse {
+    let filter = filter.into_inner();
+    let events = Events::r#where(&state.pool, filter.year, filter.activity, filter.group)
+        .await
+        .unwrap();
+    paginated(
+        events.into_iter().map(ApiEvent::from).collect(),
+        &pagination,
+    )
+}
+
+async fn find(state: &AppState, id: &str) -> Option<Events> {
+    Events::all(&state.pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .find(|event| event.id == id)
+}
+
+/// One event
+#[utoipa::path(
+    operation_id = "get_event",
+    tag = "events",
+    params(("id" = String, Path, description = "Event id, e.g. y9-boys-60m")),
+    responses(
+        (status = 200, body = ApiEvent),
+        (status = 404, description = "There's no event with the id", body = ApiError)
+    )
+)]
+#[get("/events/{id}")]
+pub async fn get(state: web::Data<AppState>, path: web::Path<String>) -> HttpResponse {
+    let id = path.into_inner();
+    match find(&state, &id).await {
+        Some(event) => HttpResponse::Ok().json(ApiEvent::from(event)),
+        None => ApiError::not_found(format!("no event with id {id}")),
+    }
+}
+
+/// Every form's result in an event, best placed first
+#[utoipa::path(
+    operation_id = "get_event_scores",
+    tag = "events",
+    params(("id" = String, Path, description = "Event id, e.g. y9-boys-60m")),
+    responses(
+        (status = 200, body = ApiEventScores),
+        (status = 404, description = "There's no event with the id", body = ApiError)
+    )
+)]
+#[get("/events/{id}/scores")]
+pub async fn scores(state: web::Data<AppState>, path: web::Path<String>) -> HttpResponse {
+    let id = path.into_inner();
+    let Some(event) = find(&state, &id).await else {
+        return ApiError::not_found(format!("no event with id {id}"));
+    };
+    let config = state.config.get();
+    let measurement = config
+        .event_for_filter_key(&event.filter_key)
+        .and_then(|config_event| config_event.measurement.clone());
+    let event_scores: Vec<EventScores> = EventScores::all(&state.pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .filter(|score| score.event_id == event.id)
+        .collect();
+    let performances: Vec<Performances> = Performances::all(&state.pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .filter(|performance| performance.event_id == event.id)
+        .collect();
+    let entries: Vec<EventEntries> = EventEntries::all(&state.pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .filter(|entry| entry.event_id == event.id)
+        .collect();
+
+    let points: Vec<(String, i64)> = event_scores
+        .iter()
+        .map(|score| (score.form_id.clone(), score.points))
+        .collect();
+    let placings = scoring::standings(&points, config.scores_for(event.scoring.as_deref()));
+    let mut scores: Vec<ApiScore> = event_scores
+        .into_iter()
+        .map(|score| {
+            let performance = performances
+                .iter()
+                .find(|performance| performance.form_id == score.form_id)
+                .map(|performance| performance.value);
+            ApiScore {
+                placing: placings
+                    .iter()
+                    .find(|placing| placing.form_id == score.form_id)
+                    .map(|placing| placing.label()),
+                base_points: score.points,
+                points: score.points * event.multiplier,
+                performance,
+                performance_label: measurement
+                    .as_ref()
+                    .zip(performance)
+                    .map(|(measurement, value)| measurement.format(value)),
+                competitor: entries
+                    .iter()
+                    .find(|entry| entry.form_id == score.form_id)
+                    .map(|entry| ApiCompetitor {
+                        id: entry.competitor_id,
+                        name: entry.competitor_name.clone(),
+                    }),
+                score: score.score_name,
+                form_id: score.form_id,
+            }
+        })
+        .collect();
+    scores.sort_by_key(|score| std::cmp::Reverse(score.base_points));
+
+    HttpResponse::Ok().json(ApiEventScores {
+        event_id: event.id,
+        revision: event.revision,
+        status: event.status,
+        scores,
+    })
+}
@@ -0,0 +1,243 @@
+//! JSON versions of the public pages, for other tools to read. Field names under
+//! `/api/v1` only ever gain new fields; anything else gets a new version.
+
+pub mod docs;
+pub mod events;
+pub mod scores;
+
+use actix_web::{get, web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use utoipa::{
+    openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+    IntoParams, Modify, OpenApi, ToSchema,
+};
+
+use crate::{
+    configurator::parser::{Form, Year},
+    utils::{self, Award, Standings},
+    AppState,
+};
+
+const DEFAULT_PER_PAGE: usize = 50;
+const MAX_PER_PAGE: usize = 200;
+
+/// `?page=` and `?per_page=` for list endpoints, counting pages from 1
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Pagination {
+    /// Defaults to 1
+    page: Option<usize>,
+    /// Defaults to 50, at most 200
+    per_page: Option<usize>,
+}
+
+/// One page of a list
+#[derive(Serialize, Debug, PartialEq, ToSchema)]
+pub struct Page<T> {
+    pub data: Vec<T>,
+    pub page: usize,
+    pub per_page: usize,
+    /// How many items there are across every page
+    pub total: usize,
+}
+
+impl<T> Page<T> {
+    /// The page of `items` asked for, or a message if the page is out of range
+    pub fn of(items: Vec<T>, pagination: &Pagination) -> Result<Self, String> {
+        let page = pagination.page.unwrap_or(1);
+        let per_page = pagination.per_page.unwrap_or(DEFAULT_PER_PAGE);
+        if page < 1 {
+            return Err("page starts from 1".to_string());
+        }
+        if !(1..=MAX_PER_PAGE).contains(&per_page) {
+            return Err(format!("per_page must be between 1 and {MAX_PER_PAGE}"));
+        }
+        let total = items.len();
+        Ok(Self {
+            data: items
+                .into_iter()
+                .skip((page - 1) * per_page)
+                .take(per_page)
+                .collect(),
+            page,
+            per_page,
+            total,
+        })
+    }
+}
+
+/// The body of a 4xx response
+#[derive(Serialize, ToSchema)]
+pub struct ApiError {
+    pub error: String,
+}
+
+impl ApiError {
+    pub fn bad_request(error: String) -> HttpResponse {
+        HttpResponse::BadRequest().json(Self { error })
+    }
+
+    pub fn not_found(error: String) -> HttpResponse {
+        HttpResponse::NotFound().json(Self { error })
+    }
+}
+
+/// Respond with a page of `items`, or a 400 if the page is out of range
+pub fn paginated<T: Serialize>(items: Vec<T>, pagination: &Pagination) -> HttpResponse {
+    match Page::of(items, pagination) {
+        Ok(page) => HttpResponse::Ok().json(page),
+        Err(error) => ApiError::bad_request(error),
+    }
+}
+
+/// Every year
+#[utoipa::path(
+    operation_id = "list_years",
+    tag = "config",
+    params(Pagination),
+    responses(
+        (status = 200, body = Page<Year>),
+        (status = 400, description = "The page is out of range", body = ApiError)
+    )
+)]
+#[get("/years")]
+pub async fn years(state: web::Data<AppState>, pagination: web::Query<Pagination>) -> HttpResponse {
+    paginated(state.config.get().years.clone(), &pagination)
+}
+
+/// Every form, in scoreboard order
+#[utoipa::path(
+    operation_id = "list_forms",
+    tag = "config",
+    params(Pagination),
+    responses(
+        (status = 200, body = Page<Form>),
+        (status = 400, description = "The page is out of range", body = ApiError)
+    )
+)]
+#[get("/forms")]
+pub async fn forms(state: web::Data<AppState>, pagination: web::Query<Pagination>) -> HttpResponse {
+    paginated(state.config.get().forms.clone(), &pagination)
+}
+
+/// Points for each form in each year and their totals, as on the scoreboard
+#[utoipa::path(
+    operation_id = "get_standings",
+    tag = "results",
+    responses((status = 200, body = Standings))
+)]
+#[get("/standings")]
+pub async fn standings(state: web::Data<AppState>) -> HttpResponse {
+    HttpResponse::Ok().json(utils::load_standings(&state.pool, &state.config.get()).await)
+}
+
+/// The individual awards, as on `/awards`
+#[utoipa::path(
+    operation_id = "list_awards",
+    tag = "results",
+    responses((status = 200, body = Vec<Award>))
+)]
+#[get("/awards")]
+pub async fn awards(state: web::Data<AppState>) -> HttpResponse {
+    HttpResponse::Ok().json(utils::load_awards(&state.pool, &state.config.get()).await)
+}
+
+/// The endpoints under `/api/v1`
+#[derive(OpenApi)]
+#[openapi(paths(
+    years,
+    forms,
+    events::list,
+    events::get,
+    events::scores,
+    scores::post,
+    standings,
+    awards
+))]
+struct V1Doc;
+
+/// Adds the bearer token that writes need
+struct ApiTokenAuth;
+
+impl Modify for ApiTokenAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        openapi
+            .components
+            .get_or_insert_default()
+            .add_security_scheme(
+                "api_token",
+                SecurityScheme::Http(
+                    HttpBuilder::new()
+                        .scheme(HttpAuthScheme::Bearer)
+                        .description(Some("A token made by an admin at /admin/api_tokens"))
+                        .build(),
+                ),
+            );
+    }
+}
+
+/// The OpenAPI document for every API version
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Sports Day Scoreboard API"),
+    nest((path = "/api/v1", api = V1Doc)),
+    modifiers(&ApiTokenAuth)
+)]
+pub struct ApiDoc;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pages_are_counted_from_one() {
+        let pagination = |page, per_page| Pagination {
+            page: Some(page),
+            per_page: Some(per_page),
+        };
+        assert_eq!(
+            Page::of(vec![1, 2, 3, 4, 5], &pagination(2, 2)),
+            Ok(Page {
+                data: vec![3, 4],
+                page: 2,
+                per_page: 2,
+                total: 5
+            })
+        );
+        assert_eq!(
+            Page::of(vec![1, 2], &pagination(3, 2)).map(|page| page.data),
+            Ok(vec![])
+        );
+        assert!(Page::of(vec![1], &pagination(0, 2)).is_err());
+        assert!(Page::of(vec![1], &pagination(1, MAX_PER_PAGE + 1)).is_err());
+    }
+
+    #[test]
+    fn openapi_lists_every_endpoint() {
+        let doc = ApiDoc::openapi();
+        assert_eq!(
+            doc.paths.paths.keys().collect::<Vec<_>>(),
+            vec![
+                "/api/v1/awards",
+                "/api/v1/events",
+                "/api/v1/events/{id}",
+                "/api/v1/events/{id}/scores",
+                "/api/v1/forms",
+                "/api/v1/scores",
+                "/api/v1/standings",
+                "/api/v1/years",
+            ]
+        );
+        let schemas = doc.components.unwrap().schemas;
+        for schema in [
+            "Page_ApiEvent",
+            "ApiEventScores",
+            "Standings",
+            "Award",
+            "SubmittedEvent",
+            "SubmittedValue",
+        ] {
+            assert!(schemas.contains_key(schema), "missing {schema}");
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use actix_web::{post, web, HttpResponse};
+
+use crate::{
+    db::{
+        api_tokens::{ApiTokens, Scope},
+        score_changes::ChangedBy,
+    },
+    routes::{
+        api::ApiError,
+        set_scores::{
+            self, SubmissionConflicts, SubmissionErrors, SubmissionSaved, SubmittedEvent,
+        },
+    },
+    websocket::ChannelsActor,
+    AppState,
+};
+
+/// Submit scores with an API token
+///
+/// Takes the same body as the set-scores page, an object of event ids to the scores
+/// submitted for them. Every event must be one the token is limited to.
+#[utoipa::path(
+    post,
+    path = "/scores",
+    operation_id = "submit_scores",
+    tag = "scores",
+    request_body(content = BTreeMap<String, SubmittedEvent>, content_type = "application/json"),
+    security(("api_token" = [])),
+    responses(
+        (status = 200, body = SubmissionSaved),
+        (status = 400, description = "The submission doesn't match the events", body = SubmissionErrors),
+        (status = 401, description = "The token is missing, unknown or expired", body = ApiError),
+        (status = 403, description = "The token can't set scores for an event", body = SubmissionErrors),
+        (status = 409, description = "An event has changed since its revision", body = SubmissionConflicts)
+    )
+)]
+#[post("")]
+pub async fn post(
+    state: web::Data<AppState>,
+    body: String,
+    token: web::ReqData<ApiTokens>,
+    channels: web::Data<actix::Addr<ChannelsActor>>,
+) -> HttpResponse {
+    // Recorded as the admin who made the token, noting the token so its changes can
+    // be told apart
+    let changed_by = ChangedBy {
+        user_id: token.created_by,
+        token_id: token.id,
+    };
+    set_scores::submit(&state, &channels, &body, changed_by, |event| {
+        token.allows(Scope::ScoresWrite, event)
+    })
+    .await
+}
@@ -0,0 +1,19 @@
+use actix_web::{get, web, HttpResponse};
+use askama::Template;
+
+use crate::{db::years::Years, templates::AwardsTemplate, utils, AppState};
+
+#[get("/awards")]
+pub async fn get(state: web::Data<AppState>) -> HttpResponse {
+    let config = state.config.get();
+
+    HttpResponse::Ok().body(
+        AwardsTemplate {
+            awards: utils::load_awards(&state.pool, &config).await,
+            years: Years::all(&state.pool).await.unwrap(),
+            forms: config.forms.clone(),
+        }
+        .render()
+        .expect("Template should be valid"),
+    )
+}
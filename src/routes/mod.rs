@@ -1,7 +1,11 @@
 pub mod admin;
+pub mod api;
+pub mod awards;
 pub mod index;
 pub mod oauth;
+pub mod records;
 pub mod results;
+pub mod schedule;
 pub mod scoreboard;
 pub mod set_scores;
 pub mod ws;
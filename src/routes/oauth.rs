@@ -71,7 +71,7 @@ pub async fn callback_get(
     // We want to loop through and find a college email if possible, otherwise faillback to the first
     let user_emails = emails_req_json
         .iter()
-        .filter(|email| email.verified == true)
+        .filter(|email| email.verified)
         .cloned()
         .collect::<Vec<GithubUserEmailsResBody>>();
     let mut user_email = user_emails[0].email.clone();
@@ -0,0 +1,88 @@
+use actix_web::{get, web, HttpResponse};
+use askama::Template;
+
+use crate::{db::records::Records, templates::RecordsTemplate, AppState};
+
+#[get("/records")]
+pub async fn get(state: web::Data<AppState>) -> HttpResponse {
+    let config = state.config.get();
+    let mut entries: Vec<RecordsEntry> = Vec::new();
+
+    // Records come grouped by event, year and gender, oldest first
+    for record in Records::all(&state.pool).await.unwrap() {
+        let Some(measurement) = config
+            .event_for_filter_key(&record.filter_key)
+            .and_then(|event| event.measurement.clone())
+        else {
+            continue;
+        };
+        let mark = RecordMark {
+            value: record.value,
+            mark: measurement.format(record.value),
+            holder: record.holder,
+            set_in: record.set_in,
+        };
+        if let Some(entry) = entries.last_mut().filter(|entry| {
+            entry.filter_key == record.filter_key
+                && entry.year_id == record.year_id
+                && entry.group == record.gender_id
+        }) {
+            let beaten = match measurement.lower_is_better() {
+                true => mark.value < entry.current.value,
+                false => mark.value > entry.current.value,
+            };
+            if beaten {
+                entry
+                    .history
+                    .push(std::mem::replace(&mut entry.current, mark));
+            } else {
+                entry.history.push(mark);
+            }
+            continue;
+        }
+
+        let config_event = config.event_for_filter_key(&record.filter_key);
+        entries.push(RecordsEntry {
+            event: config_event
+                .map(|event| event.name.clone())
+                .unwrap_or_else(|| record.filter_key.clone()),
+            year: config
+                .years
+                .iter()
+                .find(|year| year.id == record.year_id)
+                .map(|year| year.name.clone())
+                .unwrap_or_else(|| record.year_id.clone()),
+            filter_key: record.filter_key,
+            year_id: record.year_id,
+            group: record.gender_id,
+            current: mark,
+            history: vec![],
+        });
+    }
+
+    HttpResponse::Ok().body(
+        RecordsTemplate { records: entries }
+            .render()
+            .expect("Template should be valid"),
+    )
+}
+
+/// The record for an event in a year and gender, and the marks it beat
+pub struct RecordsEntry {
+    pub event: String,
+    pub year: String,
+    pub group: String,
+    filter_key: String,
+    year_id: String,
+    pub current: RecordMark,
+    /// Marks that were the record at some point, oldest first
+    pub history: Vec<RecordMark>,
+}
+
+pub struct RecordMark {
+    value: f64,
+    /// Formatted for the event's measurement
+    pub mark: String,
+    pub holder: String,
+    pub set_in: String,
+}
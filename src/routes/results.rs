@@ -1,43 +1,442 @@
-use actix_web::{get, web, HttpResponse};
-use askama::Template;
-use serde_json::Value;
+use std::collections::HashMap;
 
-use crate::{configurator::parser::Year, db, templates::ResultsTemplate, AppState};
+use crate::{
+    configurator::parser::{Configuration, Form},
+    db,
+    db::events::EventStatus,
+    scoring,
+    templates::ResultsTemplate,
+    utils::{self, Standings},
+    AppState,
+};
+use actix_web::{get, http::header, web, HttpResponse};
+use askama::Template;
+use async_sqlite::Pool;
+use rust_xlsxwriter::{Format, Workbook, Worksheet, XlsxError};
 
 #[get("/results")]
 pub async fn get(state: web::Data<AppState>) -> HttpResponse {
-    let events = db::events::Events::all(&state.pool).await.unwrap();
+    let config = state.config.get();
+
+    HttpResponse::Ok().body(
+        ResultsTemplate {
+            forms: config.forms.clone(),
+            events: results_events(&state.pool, &config).await,
+        }
+        .render()
+        .expect("Template should be valid"),
+    )
+}
+
+/// The results as a spreadsheet, with a row for each event and a score and points
+/// column for each form
+#[get("/results.csv")]
+pub async fn get_csv(state: web::Data<AppState>) -> HttpResponse {
+    let config = state.config.get();
+    let events = results_events(&state.pool, &config).await;
+
+    HttpResponse::Ok()
+        .content_type("text/csv; charset=utf-8")
+        .append_header((
+            header::CONTENT_DISPOSITION,
+            "attachment; filename=\"results.csv\"",
+        ))
+        .append_header((header::CACHE_CONTROL, "no-cache"))
+        .body(results_csv(&config, &events))
+}
+
+/// The results as a workbook, with a sheet for each year and a sheet of totals
+/// matching the scoreboard
+#[get("/results.xlsx")]
+pub async fn get_xlsx(state: web::Data<AppState>) -> HttpResponse {
+    let config = state.config.get();
+    let events = results_events(&state.pool, &config).await;
+    let standings = utils::load_standings(&state.pool, &config).await;
+
+    match results_xlsx(&config, &events, &standings) {
+        Ok(xlsx) => HttpResponse::Ok()
+            .content_type("application/vnd.openxmlformats-officedocument.spreadsheetml.sheet")
+            .append_header((
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"results.xlsx\"",
+            ))
+            .append_header((header::CACHE_CONTROL, "no-cache"))
+            .body(xlsx),
+        Err(e) => {
+            log::error!("Error writing results workbook: {e}");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+/// A form's score in an event as shown on results: its placing, or the name of the
+/// score it got if it didn't place
+fn score_label(event: &ResultsEvent, form: &Form) -> String {
+    event
+        .placings
+        .get(&form.id)
+        .or_else(|| event.score_names.get(&form.id))
+        .cloned()
+        .unwrap_or_default()
+}
+
+fn results_csv(config: &Configuration, events: &[ResultsEvent]) -> String {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    let mut header = vec![
+        "Year".to_string(),
+        "Event".to_string(),
+        "Gender".to_string(),
+        "Status".to_string(),
+    ];
+    for form in config.forms.iter() {
+        header.push(form.name.clone());
+        header.push(format!("{} points", form.name));
+    }
+    writer.write_record(&header).expect("writing to memory");
+
+    for event in events.iter() {
+        let mut row = vec![
+            event.year.clone(),
+            event.name.clone(),
+            event.group.clone(),
+            event.status.label().to_string(),
+        ];
+        for form in config.forms.iter() {
+            row.push(score_label(event, form));
+            row.push(
+                event
+                    .points
+                    .get(&form.id)
+                    .copied()
+                    .unwrap_or_default()
+                    .to_string(),
+            );
+        }
+        writer.write_record(&row).expect("writing to memory");
+    }
+    String::from_utf8(writer.into_inner().expect("writing to memory"))
+        .expect("the CSV should be UTF-8")
+}
+
+fn results_xlsx(
+    config: &Configuration,
+    events: &[ResultsEvent],
+    standings: &Standings,
+) -> Result<Vec<u8>, XlsxError> {
+    let bold = Format::new().set_bold();
+    let mut workbook = Workbook::new();
+
+    let mut sheet_names = vec!["Totals".to_string()];
+    for year in config.years.iter() {
+        let name = sheet_name(&year.name, &sheet_names);
+        let sheet = workbook.add_worksheet();
+        sheet.set_name(&name)?;
+        sheet_names.push(name);
+        let mut header = vec![
+            "Event".to_string(),
+            "Gender".to_string(),
+            "Status".to_string(),
+        ];
+        for form in config.forms.iter() {
+            header.push(form.name.clone());
+            header.push(format!("{} points", form.name));
+        }
+        write_row(sheet, 0, &header, &bold)?;
+
+        let year_events = events.iter().filter(|event| event.year_id == year.id);
+        for (row, event) in (1..).zip(year_events) {
+            sheet.write_string(row, 0, &event.name)?;
+            sheet.write_string(row, 1, &event.group)?;
+            sheet.write_string(row, 2, event.status.label())?;
+            for (column, form) in (3..).step_by(2).zip(config.forms.iter()) {
+                sheet.write_string(row, column, score_label(event, form))?;
+                sheet.write_number(
+                    row,
+                    column + 1,
+                    event.points.get(&form.id).copied().unwrap_or_default() as f64,
+                )?;
+            }
+        }
+        sheet.autofit();
+    }
+
+    let sheet = workbook.add_worksheet();
+    sheet.set_name("Totals")?;
+    let mut header = vec!["Year".to_string()];
+    header.extend(config.forms.iter().map(|form| form.name.clone()));
+    header.push("Total".to_string());
+    write_row(sheet, 0, &header, &bold)?;
+    let total_column = config.forms.len() as u16 + 1;
+    for (row, year) in (1..).zip(config.years.iter()) {
+        sheet.write_string(row, 0, &year.name)?;
+        let year_scores = standings.scores.get(&year.id);
+        for (column, form) in (1..).zip(config.forms.iter()) {
+            let points = year_scores
+                .and_then(|scores| scores.get(&form.id))
+                .copied()
+                .unwrap_or_default();
+            sheet.write_number(row, column, points as f64)?;
+        }
+        let total = standings
+            .year_totals
+            .get(&year.id)
+            .copied()
+            .unwrap_or_default();
+        sheet.write_number_with_format(row, total_column, total as f64, &bold)?;
+    }
+    let row = config.years.len() as u32 + 1;
+    sheet.write_string_with_format(row, 0, "Total", &bold)?;
+    for (column, form) in (1..).zip(config.forms.iter()) {
+        let total = standings
+            .form_totals
+            .get(&form.id)
+            .copied()
+            .unwrap_or_default();
+        sheet.write_number_with_format(row, column, total as f64, &bold)?;
+    }
+    sheet.write_number_with_format(row, total_column, standings.grand_total as f64, &bold)?;
+    sheet.autofit();
+
+    workbook.save_to_buffer()
+}
+
+fn write_row(
+    sheet: &mut Worksheet,
+    row: u32,
+    values: &[String],
+    format: &Format,
+) -> Result<(), XlsxError> {
+    for (column, value) in (0..).zip(values.iter()) {
+        sheet.write_string_with_format(row, column, value, format)?;
+    }
+    Ok(())
+}
+
+/// Excel sheet names can't be over 31 characters, use any of `[]:*?/\` or match
+/// another sheet's name ignoring case, so clashes with `taken` get a " (2)" suffix
+fn sheet_name(name: &str, taken: &[String]) -> String {
+    let valid: Vec<char> = name
+        .chars()
+        .map(|c| if "[]:*?/\\".contains(c) { '-' } else { c })
+        .collect();
+    let is_taken = |candidate: &str| {
+        taken
+            .iter()
+            .any(|name| name.to_lowercase() == candidate.to_lowercase())
+    };
+    let mut candidate: String = valid.iter().take(31).collect();
+    let mut copy = 2;
+    while is_taken(&candidate) {
+        let suffix = format!(" ({copy})");
+        let base: String = valid.iter().take(31 - suffix.len()).collect();
+        candidate = format!("{}{suffix}", base.trim_end());
+        copy += 1;
+    }
+    candidate
+}
+
+/// Every event's results, as on `/results`
+async fn results_events(pool: &Pool, config: &Configuration) -> Vec<ResultsEvent> {
+    let events = db::events::Events::all(pool).await.unwrap();
+    let performances = db::performances::Performances::all(pool).await.unwrap();
+    let event_scores = db::event_scores::EventScores::all(pool).await.unwrap();
+    let entries = db::event_entries::EventEntries::all(pool).await.unwrap();
+    let records = db::records::Records::all(pool).await.unwrap();
+    let entrants = utils::entrants(config, &events, &event_scores);
     let mut results_events: Vec<ResultsEvent> = Vec::new();
 
     for event in events.iter() {
+        let measurement = config
+            .event_for_filter_key(&event.filter_key)
+            .and_then(|config_event| config_event.measurement.clone());
+        let event_performances = match measurement {
+            Some(measurement) => performances
+                .iter()
+                .filter(|performance| performance.event_id == event.id)
+                .map(|performance| {
+                    (
+                        performance.form_id.clone(),
+                        measurement.format(performance.value),
+                    )
+                })
+                .collect(),
+            None => HashMap::new(),
+        };
+        let points: Vec<(String, i64)> = event_scores
+            .iter()
+            .filter(|score| score.event_id == event.id)
+            .map(|score| (score.form_id.clone(), score.points))
+            .collect();
+        let score_names = event_scores
+            .iter()
+            .filter(|score| score.event_id == event.id)
+            .map(|score| (score.form_id.clone(), score.score_name.clone()))
+            .collect();
+        let placings = scoring::standings(&points, config.scores_for(event.scoring.as_deref()))
+            .into_iter()
+            .map(|placing| (placing.form_id.clone(), placing.label()))
+            .collect();
+        // A heat's entrants who are also in its final went through
+        let qualified = match &event.heat {
+            Some(heat) => entrants
+                .get(&heat.final_id)
+                .into_iter()
+                .flatten()
+                .filter(|form_id| {
+                    entrants
+                        .get(&event.id)
+                        .is_some_and(|forms| forms.contains(form_id))
+                })
+                .cloned()
+                .collect(),
+            None => vec![],
+        };
         results_events.push(ResultsEvent {
             name: event.name.clone(),
-            year: state
-                .config
+            year_id: event.year_id.clone(),
+            year: config
                 .years
                 .iter()
-                .filter(|year| year.id == event.year_id)
-                .collect::<Vec<&Year>>()[0]
-                .name
-                .clone(),
+                .find(|year| year.id == event.year_id)
+                .map_or_else(|| event.year_id.clone(), |year| year.name.clone()),
             group: event.gender_id.clone(),
-            scores: serde_json::from_str::<Value>(event.scores.as_str()).unwrap(),
+            status: event.status,
+            multiplier: event.multiplier,
+            points: points
+                .into_iter()
+                .map(|(form_id, points)| (form_id, points * event.multiplier))
+                .collect(),
+            performances: event_performances,
+            placings,
+            score_names,
+            competitors: entries
+                .iter()
+                .filter(|entry| entry.event_id == event.id)
+                .map(|entry| (entry.form_id.clone(), entry.competitor_name.clone()))
+                .collect(),
+            qualified,
+            // A baseline set where there was no record isn't marked as beating one
+            records: records
+                .iter()
+                .filter(|record| record.event_id.as_ref() == Some(&event.id))
+                .filter(|record| {
+                    records.iter().any(|earlier| {
+                        earlier.id < record.id
+                            && earlier.filter_key == record.filter_key
+                            && earlier.year_id == record.year_id
+                            && earlier.gender_id == record.gender_id
+                    })
+                })
+                .filter_map(|record| record.form_id.clone())
+                .collect(),
         });
     }
-
-    HttpResponse::Ok().body(
-        ResultsTemplate {
-            forms: state.config.forms.clone(),
-            events: results_events,
-        }
-        .render()
-        .expect("Template should be valid"),
-    )
+    results_events
 }
 
 pub struct ResultsEvent {
     pub name: String,
+    pub year_id: String,
     pub year: String,
     pub group: String,
-    pub scores: Value,
+    pub status: EventStatus,
+    pub multiplier: i64,
+    /// Points keyed by form id, with the event's multiplier applied
+    pub points: HashMap<String, i64>,
+    /// Formatted performances keyed by form id, for measured events
+    pub performances: HashMap<String, String>,
+    /// Placing labels (e.g. "=1st") keyed by form id, for forms that placed
+    pub placings: HashMap<String, String>,
+    /// Name of the score each form was awarded, keyed by form id
+    pub score_names: HashMap<String, String>,
+    /// Name of the competitor who placed for each form, keyed by form id
+    pub competitors: HashMap<String, String>,
+    /// Ids of the forms that went through to the final, for heats
+    pub qualified: Vec<String>,
+    /// Ids of the forms whose performance set a record
+    pub records: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_harness::config;
+
+    fn sixty_metres() -> ResultsEvent {
+        let by_form = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(form_id, value)| (form_id.to_string(), value.to_string()))
+                .collect()
+        };
+        ResultsEvent {
+            name: "60m".to_string(),
+            year_id: "y9".to_string(),
+            year: "Year 9".to_string(),
+            group: "boys".to_string(),
+            status: EventStatus::Final,
+            multiplier: 2,
+            points: HashMap::from([("w".to_string(), 40), ("e".to_string(), 0)]),
+            performances: HashMap::new(),
+            placings: by_form(&[("w", "1st")]),
+            score_names: by_form(&[("w", "1st"), ("e", "DNF")]),
+            competitors: HashMap::new(),
+            qualified: vec![],
+            records: vec![],
+        }
+    }
+
+    #[test]
+    fn results_csv_has_a_score_and_points_per_form() {
+        let csv = results_csv(&config(), &[sixty_metres()]);
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next(),
+            Some("Year,Event,Gender,Status,Winston,Winston points,Ennis,Ennis points,Sharman,Sharman points,Turing,Turing points")
+        );
+        assert_eq!(
+            lines.next(),
+            Some("Year 9,60m,boys,Final,1st,40,DNF,0,,0,,0")
+        );
+        assert_eq!(lines.next(), None);
+    }
+
+    #[test]
+    fn results_xlsx_is_a_workbook() {
+        let standings = Standings {
+            scores: HashMap::from([("y9".to_string(), HashMap::from([("w".to_string(), 40)]))]),
+            year_totals: HashMap::from([("y9".to_string(), 40)]),
+            form_totals: HashMap::from([("w".to_string(), 40)]),
+            grand_total: 40,
+        };
+        let xlsx = results_xlsx(&config(), &[sixty_metres()], &standings).unwrap();
+        assert!(xlsx.starts_with(b"PK"));
+    }
+
+    #[test]
+    fn sheet_names_are_made_valid() {
+        assert_eq!(sheet_name("Year 7/8", &[]), "Year 7-8");
+        assert_eq!(sheet_name(&"x".repeat(40), &[]).len(), 31);
+        assert_eq!(sheet_name("totals", &["Totals".to_string()]), "totals (2)");
+    }
+
+    #[test]
+    fn results_xlsx_names_clashing_years_apart() {
+        let mut config = config();
+        config.years[0].name = "Year 9 Upper School Sports Day North".to_string();
+        config.years[1].name = "Year 9 Upper School Sports Day South".to_string();
+        let standings = Standings {
+            scores: HashMap::new(),
+            year_totals: HashMap::new(),
+            form_totals: HashMap::new(),
+            grand_total: 0,
+        };
+        assert!(results_xlsx(&config, &[], &standings).is_ok());
+
+        let first = sheet_name(&config.years[0].name, &[]);
+        assert_eq!(
+            sheet_name(&config.years[1].name, &[first]),
+            "Year 9 Upper School Sports (2)"
+        );
+    }
 }
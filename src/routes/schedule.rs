@@ -0,0 +1,117 @@
+use actix_web::{get, web, HttpResponse};
+use askama::Template;
+
+use crate::{
+    db::{config_versions::ConfigVersions, event_scores::EventScores, events::Events},
+    ical::{self, CalendarEvent},
+    templates::ScheduleTemplate,
+    utils, AppState,
+};
+
+#[get("/schedule")]
+pub async fn get(state: web::Data<AppState>) -> HttpResponse {
+    let config = state.config.get();
+    let events = Events::scheduled(&state.pool).await.unwrap();
+    let now_next = utils::render_now_next(&state.pool, &config).await;
+
+    HttpResponse::Ok().body(
+        ScheduleTemplate {
+            events,
+            years: config.years.clone(),
+            now_next,
+        }
+        .render()
+        .expect("Template should be valid"),
+    )
+}
+
+/// The now/next panel on its own, for pages to refresh it as the day goes on
+#[get("/schedule/now_next")]
+pub async fn now_next_panel(state: web::Data<AppState>) -> HttpResponse {
+    HttpResponse::Ok().body(utils::render_now_next(&state.pool, &state.config.get()).await)
+}
+
+#[get("/schedule.ics")]
+pub async fn ics(state: web::Data<AppState>) -> HttpResponse {
+    calendar_feed(&state, None).await
+}
+
+/// A feed of one year's events, or of every event with a form's scores in them
+#[get("/schedule/{feed}.ics")]
+pub async fn feed_ics(state: web::Data<AppState>, path: web::Path<String>) -> HttpResponse {
+    calendar_feed(&state, Some(path.into_inner())).await
+}
+
+async fn calendar_feed(state: &AppState, feed: Option<String>) -> HttpResponse {
+    let config = state.config.get();
+    let Some(date) = &config.date else {
+        return HttpResponse::NotFound()
+            .body("Set the date of sports day in config.yaml to get a calendar");
+    };
+    let year = feed
+        .as_ref()
+        .and_then(|feed| config.years.iter().find(|year| &year.id == feed));
+    let form = feed
+        .as_ref()
+        .and_then(|feed| config.forms.iter().find(|form| &form.id == feed));
+    if feed.is_some() && year.is_none() && form.is_none() {
+        return HttpResponse::NotFound().body("No calendar for that year or form");
+    }
+
+    let scores = match form {
+        Some(_) => EventScores::all(&state.pool).await.unwrap(),
+        None => vec![],
+    };
+    let events: Vec<CalendarEvent> = Events::scheduled(&state.pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .filter(|event| year.is_none_or(|year| year.id == event.year_id))
+        .map(|event| {
+            let year_name = config
+                .years
+                .iter()
+                .find(|year| year.id == event.year_id)
+                .map(|year| year.name.as_str())
+                .unwrap_or(&event.year_id);
+            let mut description = event.status.label().to_string();
+            if let Some(form) = form {
+                if let Some(score) = scores
+                    .iter()
+                    .find(|score| score.event_id == event.id && score.form_id == form.id)
+                {
+                    description.push_str(&format!(
+                        "\n{}: {} ({} points)",
+                        form.name,
+                        score.score_name,
+                        score.points * event.multiplier
+                    ));
+                }
+            }
+            CalendarEvent {
+                uid: format!("{}@sportsday", event.id),
+                summary: format!("{year_name} {} {}", event.gender_id, event.name),
+                start: event.timeslot.start.unwrap_or_default(),
+                duration: event.timeslot.duration,
+                location: event.timeslot.venue,
+                description: Some(description),
+            }
+        })
+        .collect();
+
+    let name = match (year, form) {
+        (Some(year), _) => format!("Sports Day: {}", year.name),
+        (_, Some(form)) => format!("Sports Day: {}", form.name),
+        _ => "Sports Day".to_string(),
+    };
+    // Timetables only change when the config is reconciled
+    let stamp = ConfigVersions::latest(&state.pool)
+        .await
+        .unwrap()
+        .and_then(|version| version.applied_at)
+        .unwrap_or_else(|| format!("{date} 00:00:00"));
+
+    HttpResponse::Ok()
+        .content_type("text/calendar; charset=utf-8")
+        .body(ical::calendar(&name, date, &stamp, &events))
+}
@@ -5,8 +5,10 @@ use crate::{templates::ScoreboardTemplate, utils, AppState};
 
 #[get("/scoreboard")]
 pub async fn get(state: web::Data<AppState>) -> HttpResponse {
-    let scores = utils::render_scoreboard(state).await;
-    let html = ScoreboardTemplate { scores }
+    let config = state.config.get();
+    let scores = utils::render_scoreboard(&state.pool, &config).await;
+    let now_next = utils::render_now_next(&state.pool, &config).await;
+    let html = ScoreboardTemplate { scores, now_next }
         .render()
         .expect("template should be valid");
 
@@ -1,16 +1,35 @@
+use std::collections::{BTreeMap, HashMap};
+
 use actix_web::{get, post, web, HttpResponse};
 use askama::Template;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use utoipa::ToSchema;
 
 use crate::{
-    db::{self, events::Events},
+    configurator::parser::{Configuration, Form, Measurement},
+    db::{
+        competitors::Competitors,
+        event_entries::EventEntries,
+        event_scores::{BatchOutcome, EventScores, ScoreUpdate},
+        events::{EventStatus, Events},
+        performances::Performances,
+        score_changes::ChangedBy,
+        user_sessions::VerifiedSession,
+    },
+    scoring,
     templates::SetScoresTemplate,
-    websocket::{ChannelsActor, Publish},
+    utils::{self, current_scores, CurrentScores, NewRecord},
+    websocket::ChannelsActor,
     AppState,
 };
 
 #[get("")]
-pub async fn get(state: web::Data<AppState>, params: web::Query<Params>) -> HttpResponse {
+pub async fn get(
+    state: web::Data<AppState>,
+    params: web::Query<Params>,
+    session: web::ReqData<VerifiedSession>,
+) -> HttpResponse {
     let events = Events::r#where(
         &state.pool,
         params.year.clone(),
@@ -19,14 +38,66 @@ pub async fn get(state: web::Data<AppState>, params: web::Query<Params>) -> Http
     )
     .await
     .unwrap();
+    let config = state.config.get();
+
+    let mut measurements: HashMap<String, Measurement> = HashMap::new();
+    for event in config.events.iter() {
+        if let Some(measurement) = &event.measurement {
+            measurements.insert(event.id.clone(), measurement.clone());
+        }
+    }
+
+    let mut performances: HashMap<String, HashMap<String, String>> = HashMap::new();
+    for performance in Performances::all(&state.pool).await.unwrap() {
+        let Some(event) = events.iter().find(|event| event.id == performance.event_id) else {
+            continue;
+        };
+        if let Some(measurement) = measurements.get(&event.filter_key) {
+            performances
+                .entry(performance.event_id)
+                .or_default()
+                .insert(performance.form_id, measurement.format(performance.value));
+        }
+    }
+
+    let event_scores = EventScores::all(&state.pool).await.unwrap();
+    let entrants = utils::entrants(
+        &config,
+        &Events::all(&state.pool).await.unwrap(),
+        &event_scores,
+    );
+    let mut points: HashMap<String, Vec<(String, i64)>> = HashMap::new();
+    for score in event_scores {
+        points
+            .entry(score.event_id)
+            .or_default()
+            .push((score.form_id, score.points));
+    }
+
+    let mut entries: HashMap<String, HashMap<String, i64>> = HashMap::new();
+    for entry in EventEntries::all(&state.pool).await.unwrap() {
+        entries
+            .entry(entry.event_id)
+            .or_default()
+            .insert(entry.form_id, entry.competitor_id);
+    }
+
     HttpResponse::Ok().body(
         SetScoresTemplate {
             events,
-            activity_types: state.config.events.clone(),
-            year_types: state.config.years.clone(),
-            group_types: state.config.genders.clone(),
-            forms: state.config.forms.clone(),
-            scores: state.config.scores.clone(),
+            points,
+            competitors: Competitors::all(&state.pool).await.unwrap(),
+            entries,
+            entrants,
+            measurements,
+            performances,
+            activity_types: config.events.clone(),
+            year_types: config.years.clone(),
+            group_types: config.genders.clone(),
+            forms: config.forms.clone(),
+            scores: config.scores.clone(),
+            scoring_schemes: config.scoring_schemes.clone(),
+            is_admin: session.has_admin,
         }
         .render()
         .expect("Template should be valid"),
@@ -37,25 +108,429 @@ pub async fn get(state: web::Data<AppState>, params: web::Query<Params>) -> Http
 pub async fn post(
     state: web::Data<AppState>,
     body: String,
+    session: web::ReqData<VerifiedSession>,
     channels: web::Data<actix::Addr<ChannelsActor>>,
 ) -> HttpResponse {
-    let body: Value = serde_json::from_str(body.as_str()).unwrap();
+    submit(
+        &state,
+        &channels,
+        &body,
+        ChangedBy::user(session.user_id),
+        |_| true,
+    )
+    .await
+}
 
-    for events in body.as_object().unwrap() {
-        let event_id = events.0;
-        let event_scores = events.1;
-        db::events::Events::set_scores(&state.pool, event_id.to_owned(), event_scores.to_owned())
-            .await
-            .unwrap();
+/// Validate and save a score submission made by `changed_by`, then push the new scores
+/// to every screen. Events that `allowed` refuses are rejected with a 403.
+pub async fn submit(
+    state: &AppState,
+    channels: &actix::Addr<ChannelsActor>,
+    body: &str,
+    changed_by: ChangedBy,
+    allowed: impl Fn(&Events) -> bool,
+) -> HttpResponse {
+    let config = state.config.get();
+    let all_events = Events::all(&state.pool).await.unwrap();
+    let entrants = utils::entrants(
+        &config,
+        &all_events,
+        &EventScores::all(&state.pool).await.unwrap(),
+    );
+    let events: HashMap<String, Events> = all_events
+        .into_iter()
+        .map(|event| (event.id.clone(), event))
+        .collect();
+
+    let competitors = Competitors::all(&state.pool).await.unwrap();
+    let submission = Submission {
+        events: &events,
+        entrants: &entrants,
+        competitors: &competitors,
+    };
+    let updates = match validate(&config, &submission, body) {
+        Ok(updates) => updates,
+        Err(errors) => return HttpResponse::BadRequest().json(SubmissionErrors { errors }),
+    };
+    let forbidden: Vec<FieldError> = updates
+        .iter()
+        .filter(|update| !events.get(&update.event_id).is_some_and(&allowed))
+        .map(|update| FieldError {
+            field: update.event_id.clone(),
+            message: "You can't set scores for this event".to_string(),
+        })
+        .collect();
+    if !forbidden.is_empty() {
+        return HttpResponse::Forbidden().json(SubmissionErrors { errors: forbidden });
+    }
+    let event_ids: Vec<String> = updates
+        .iter()
+        .map(|update| update.event_id.clone())
+        .collect();
+    match EventScores::set_batch(&state.pool, updates, changed_by).await {
+        Ok(BatchOutcome::Saved) => {}
+        Ok(BatchOutcome::Stale(stale)) => {
+            return HttpResponse::Conflict().json(SubmissionConflicts {
+                conflicts: current_scores(&state.pool, &config, &stale).await,
+            })
+        }
+        Err(e) => {
+            log::error!("Error saving scores: {e}");
+            return HttpResponse::InternalServerError().finish();
+        }
     }
 
-    let scores = crate::utils::render_scoreboard(state).await;
-    channels.do_send(Publish {
-        channel: "scores".to_string(),
-        payload: scores,
-    });
+    let saved = utils::publish_scores(&state.pool, &config, channels, &event_ids).await;
+    let records = utils::check_records(&state.pool, &config, channels, &event_ids).await;
+    HttpResponse::Ok().json(SubmissionSaved { saved, records })
+}
 
-    HttpResponse::NoContent().finish()
+#[post("/{event_id}/status")]
+pub async fn status(
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+    form: web::Form<StatusProps>,
+    session: web::ReqData<VerifiedSession>,
+    channels: web::Data<actix::Addr<ChannelsActor>>,
+) -> HttpResponse {
+    match change_status(
+        &state,
+        &channels,
+        path.into_inner(),
+        form.status,
+        session.has_admin,
+    )
+    .await
+    {
+        Ok(()) => HttpResponse::Found()
+            .append_header(("Location", "/set_scores"))
+            .finish(),
+        Err(response) => response,
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct StatusProps {
+    pub status: EventStatus,
+}
+
+/// Move an event to another status and push the change to every screen.
+/// Only admins can lock an event as final or move it back out of final.
+pub async fn change_status(
+    state: &AppState,
+    channels: &actix::Addr<ChannelsActor>,
+    event_id: String,
+    to: EventStatus,
+    is_admin: bool,
+) -> Result<(), HttpResponse> {
+    let Some(event) = Events::all(&state.pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .find(|event| event.id == event_id)
+    else {
+        return Err(HttpResponse::NotFound().body("Unknown event"));
+    };
+    if !event.status.can_become(to) {
+        return Err(HttpResponse::BadRequest().body(format!(
+            "{} events can't become {}",
+            event.status.label(),
+            to.label()
+        )));
+    }
+    if event.status.needs_admin_to_become(to) && !is_admin {
+        return Err(HttpResponse::Forbidden().body("Only admins can lock or unlock final results"));
+    }
+    match Events::set_status(&state.pool, event_id.clone(), event.status, to).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return Err(HttpResponse::Conflict()
+                .body("The event's status has already changed, reload and try again"))
+        }
+        Err(e) => {
+            log::error!("Error changing the status of {event_id}: {e}");
+            return Err(HttpResponse::InternalServerError().finish());
+        }
+    }
+
+    utils::publish_scores(&state.pool, &state.config.get(), channels, &[event_id]).await;
+    Ok(())
+}
+
+/// A problem with one part of a score submission
+#[derive(Serialize, Debug, PartialEq, ToSchema)]
+pub struct FieldError {
+    /// The event id, or `<event id>.<form id>` for a single form's score
+    pub field: String,
+    pub message: String,
+}
+
+/// The body of a 400 or 403 response to a rejected submission
+#[derive(Serialize, ToSchema)]
+pub struct SubmissionErrors {
+    errors: Vec<FieldError>,
+}
+
+/// The body of a 409 response to a submission made against old revisions, holding
+/// what the changed events have now
+#[derive(Serialize, ToSchema)]
+pub struct SubmissionConflicts {
+    conflicts: Vec<CurrentScores>,
+}
+
+/// The body of a response to a saved submission
+#[derive(Serialize, ToSchema)]
+pub struct SubmissionSaved {
+    saved: Vec<CurrentScores>,
+    /// Records broken by the submission
+    records: Vec<NewRecord>,
+}
+
+/// The scores submitted for one event, keyed by event id in a submission
+#[derive(Deserialize, ToSchema)]
+pub struct SubmittedEvent {
+    /// The revision the scores were loaded at
+    revision: i64,
+    /// Each form's score value, or its performance in measured events. Values are
+    /// checked form by form, so every bad one is reported.
+    #[schema(value_type = BTreeMap<String, SubmittedValue>)]
+    scores: BTreeMap<String, Value>,
+    /// Who placed for each form, replacing the event's entries. An empty or null
+    /// competitor clears the form's entry.
+    #[serde(default)]
+    #[schema(value_type = Option<BTreeMap<String, SubmittedValue>>)]
+    competitors: Option<BTreeMap<String, Value>>,
+}
+
+/// A score, performance or competitor id, given as a string or a number
+#[derive(Deserialize, ToSchema)]
+#[serde(untagged)]
+// Only describes the body, the values themselves are checked as JSON
+#[allow(dead_code)]
+pub enum SubmittedValue {
+    Text(String),
+    Number(f64),
+}
+
+/// What a score submission is checked against
+pub struct Submission<'a> {
+    pub events: &'a HashMap<String, Events>,
+    /// The forms that can be scored in events run in rounds, from `utils::entrants`
+    pub entrants: &'a HashMap<String, Vec<String>>,
+    pub competitors: &'a [Competitors],
+}
+
+/// Check a submission of
+/// `{ event id: { "revision": loaded revision, "scores": { form id: score or performance } } }`
+/// against the events and config, returning every problem found or the updates to store.
+/// An event can also give `"competitors": { form id: competitor id }` to replace who
+/// placed for each form.
+pub fn validate(
+    config: &Configuration,
+    submission: &Submission,
+    body: &str,
+) -> Result<Vec<ScoreUpdate>, Vec<FieldError>> {
+    let Submission {
+        events,
+        entrants,
+        competitors,
+    } = submission;
+    let submission = match serde_json::from_str::<BTreeMap<String, Value>>(body) {
+        Ok(submission) => submission,
+        Err(e) => {
+            return Err(vec![FieldError {
+                field: String::new(),
+                message: format!("expected an object of event ids to scores: {e}"),
+            }])
+        }
+    };
+
+    let mut updates = vec![];
+    let mut errors = vec![];
+    for (event_id, submitted) in submission.into_iter() {
+        let error = |field: String, message: String| FieldError { field, message };
+        let Some(event) = events.get(&event_id) else {
+            errors.push(error(event_id.clone(), "unknown event".to_string()));
+            continue;
+        };
+        if event.status == EventStatus::Final {
+            errors.push(error(
+                event_id.clone(),
+                "results are final, an admin has to unlock the event to change them".to_string(),
+            ));
+            continue;
+        }
+        let submitted = match serde_json::from_value::<SubmittedEvent>(submitted) {
+            Ok(submitted) => submitted,
+            Err(e) => {
+                errors.push(error(
+                    event_id.clone(),
+                    format!("expected the revision the scores were loaded at and an object of form ids to scores: {e}"),
+                ));
+                continue;
+            }
+        };
+        let entries = match &submitted.competitors {
+            None => None,
+            Some(picked) => match validate_entries(event, competitors, picked) {
+                Ok(entries) => Some(entries),
+                Err(mut entry_errors) => {
+                    errors.append(&mut entry_errors);
+                    continue;
+                }
+            },
+        };
+
+        let scheme = event.scoring.as_deref();
+        let scores = config.scores_for(scheme);
+        let tie_rule = config.tie_rule_for(scheme);
+        let measurement = config
+            .event_for_filter_key(&event.filter_key)
+            .and_then(|event| event.measurement.as_ref());
+
+        let mut field_errors = vec![];
+        let mut performances = vec![];
+        let mut picked = vec![];
+        for (form_id, value) in submitted.scores.iter() {
+            let field = format!("{event_id}.{form_id}");
+            if !config.forms.iter().any(|form| &form.id == form_id) {
+                field_errors.push(error(field, "unknown form".to_string()));
+                continue;
+            }
+            if entrants
+                .get(&event_id)
+                .is_some_and(|forms| !forms.contains(form_id))
+            {
+                let message = match event.heat {
+                    Some(_) => "doesn't run in this heat",
+                    None => "hasn't qualified for the final",
+                };
+                field_errors.push(error(field, message.to_string()));
+                continue;
+            }
+            let value = match value {
+                Value::String(value) => value.trim().to_string(),
+                Value::Number(value) => value.to_string(),
+                value => {
+                    field_errors.push(error(field, format!("{value} is not a score")));
+                    continue;
+                }
+            };
+            match measurement {
+                Some(measurement) => {
+                    if value.is_empty() {
+                        continue;
+                    }
+                    match measurement.parse(&value) {
+                        Ok(performance) => performances.push((form_id.clone(), performance)),
+                        Err(e) => field_errors.push(error(field, e)),
+                    }
+                }
+                None => match value.parse::<i64>() {
+                    Ok(points) if scores.iter().any(|score| score.value == points) => {
+                        picked.push((form_id.clone(), points))
+                    }
+                    _ => field_errors.push(error(
+                        field,
+                        format!("{value} is not one of the configured scores"),
+                    )),
+                },
+            }
+        }
+        if !field_errors.is_empty() {
+            errors.append(&mut field_errors);
+            continue;
+        }
+
+        // Forms that don't run in a round aren't given a score in it
+        let forms: Vec<Form> = config
+            .forms
+            .iter()
+            .filter(|form| {
+                entrants
+                    .get(&event_id)
+                    .is_none_or(|forms| forms.contains(&form.id))
+            })
+            .cloned()
+            .collect();
+        let placings = match measurement {
+            Some(measurement) => scoring::place(measurement, &performances, scores, tie_rule),
+            None => scoring::place_by_score(&picked, scores, tie_rule),
+        };
+        match placings {
+            Ok(placings) => updates.push(ScoreUpdate {
+                event_id: event_id.clone(),
+                scores: scoring::scores_from_placings(&forms, &placings, scores),
+                performances: measurement.map(|_| performances),
+                entries,
+                revision: Some(submitted.revision),
+            }),
+            Err(placing_errors) => errors.extend(placing_errors.into_iter().map(|e| {
+                let field = match e.form_id {
+                    Some(form_id) => format!("{event_id}.{form_id}"),
+                    None => event_id.clone(),
+                };
+                error(field, e.message)
+            })),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(updates)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Check the competitors picked for each form in an event. Each has to be registered
+/// for that form and the event's year; an empty pick clears the form's entry.
+fn validate_entries(
+    event: &Events,
+    competitors: &[Competitors],
+    picked: &BTreeMap<String, Value>,
+) -> Result<Vec<(String, i64)>, Vec<FieldError>> {
+    let mut entries = vec![];
+    let mut errors = vec![];
+    for (form_id, competitor_id) in picked.iter() {
+        let field = format!("{}.{form_id}.competitor", event.id);
+        let competitor_id = match competitor_id {
+            Value::Null => continue,
+            Value::String(id) if id.is_empty() => continue,
+            Value::String(id) => id.parse::<i64>().ok(),
+            Value::Number(id) => id.as_i64(),
+            _ => None,
+        };
+        let Some(competitor) = competitor_id.and_then(|id| {
+            competitors
+                .iter()
+                .find(|competitor| competitor.id == Some(id))
+        }) else {
+            errors.push(FieldError {
+                field,
+                message: "unknown competitor".to_string(),
+            });
+            continue;
+        };
+        if &competitor.form_id != form_id || competitor.year_id != event.year_id {
+            errors.push(FieldError {
+                field,
+                message: format!(
+                    "{} doesn't compete for this form in {}",
+                    competitor.name, event.year_id
+                ),
+            });
+            continue;
+        }
+        entries.push((
+            form_id.clone(),
+            competitor.id.expect("stored competitors have ids"),
+        ));
+    }
+    if errors.is_empty() {
+        Ok(entries)
+    } else {
+        Err(errors)
+    }
 }
 
 #[derive(serde::Deserialize)]
@@ -64,3 +539,222 @@ struct Params {
     activity: Option<String>,
     group: Option<String>,
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::configurator::parser::TieRule;
+
+    use super::*;
+    use crate::test_harness::config;
+
+    fn events() -> HashMap<String, Events> {
+        ["60m", "javelin"]
+            .iter()
+            .map(|id| {
+                let event = Events::new(
+                    format!("y9-boys-{id}"),
+                    id.to_string(),
+                    "y9".to_string(),
+                    "boys".to_string(),
+                    id.to_string(),
+                );
+                (event.id.clone(), event)
+            })
+            .collect()
+    }
+
+    fn check(
+        config: &Configuration,
+        events: &HashMap<String, Events>,
+        body: &str,
+    ) -> Result<Vec<ScoreUpdate>, Vec<FieldError>> {
+        let submission = Submission {
+            events,
+            entrants: &HashMap::new(),
+            competitors: &[],
+        };
+        validate(config, &submission, body)
+    }
+
+    fn fields(result: Result<Vec<ScoreUpdate>, Vec<FieldError>>) -> Vec<String> {
+        result
+            .unwrap_err()
+            .into_iter()
+            .map(|error| error.field)
+            .collect()
+    }
+
+    #[test]
+    fn malformed_body_is_rejected() {
+        assert_eq!(fields(check(&config(), &events(), "[1, 2")), vec![""]);
+        assert_eq!(
+            fields(check(&config(), &events(), r#"{"y9-boys-60m": 20}"#)),
+            vec!["y9-boys-60m"]
+        );
+        assert_eq!(
+            fields(check(
+                &config(),
+                &events(),
+                r#"{"y9-boys-60m": {"scores": {"w": "8.4"}}}"#
+            )),
+            vec!["y9-boys-60m"]
+        );
+    }
+
+    #[test]
+    fn every_problem_is_reported() {
+        let body = r#"{
+            "y9-boys-nope": { "revision": 0, "scores": { "w": "20" } },
+            "y9-boys-60m": { "revision": 0, "scores": { "w": "8.4", "x": "9.1", "e": "fast" } },
+            "y9-boys-javelin": { "revision": 0, "scores": { "w": "31", "e": true } }
+        }"#;
+        let config = config();
+        assert_eq!(
+            fields(check(&config, &events(), body)),
+            vec![
+                "y9-boys-60m.e",
+                "y9-boys-60m.x",
+                "y9-boys-javelin.e",
+                "y9-boys-javelin.w",
+                "y9-boys-nope"
+            ]
+        );
+    }
+
+    #[test]
+    fn valid_submission_becomes_updates() {
+        let config = config();
+        let body = r#"{
+            "y9-boys-60m": { "revision": 3, "scores": { "w": "8.4", "e": 8.1, "s": "" } },
+            "y9-boys-javelin": { "revision": 0, "scores": { "w": "15", "e": 20 } }
+        }"#;
+        let updates = check(&config, &events(), body).unwrap();
+        assert_eq!(updates.len(), 2);
+
+        let sixty = &updates[0];
+        assert_eq!(sixty.event_id, "y9-boys-60m");
+        assert_eq!(sixty.revision, Some(3));
+        assert_eq!(
+            sixty.performances,
+            Some(vec![("e".to_string(), 8.1), ("w".to_string(), 8.4)])
+        );
+        let points = |update: &ScoreUpdate, form_id: &str| {
+            update
+                .scores
+                .iter()
+                .find(|(id, _)| id == form_id)
+                .map(|(_, score)| score.value)
+        };
+        assert_eq!(points(sixty, "e"), Some(20));
+        assert_eq!(points(sixty, "w"), Some(15));
+        assert_eq!(points(sixty, "s"), Some(0));
+
+        let javelin = &updates[1];
+        assert_eq!(javelin.performances, None);
+        assert_eq!(points(javelin, "e"), Some(20));
+        assert_eq!(points(javelin, "w"), Some(15));
+    }
+
+    #[test]
+    fn final_events_are_locked() {
+        let mut events = events();
+        events.get_mut("y9-boys-60m").unwrap().status = EventStatus::Final;
+        let body = r#"{ "y9-boys-60m": { "revision": 0, "scores": { "w": "8.4" } } }"#;
+        assert_eq!(fields(check(&config(), &events, body)), vec!["y9-boys-60m"]);
+    }
+
+    #[test]
+    fn picked_competitors_must_place_for_the_form() {
+        let competitor = |id: i64, form_id: &str, year_id: &str| Competitors {
+            id: Some(id),
+            name: format!("Competitor {id}"),
+            form_id: form_id.to_string(),
+            year_id: year_id.to_string(),
+            gender_id: "boys".to_string(),
+        };
+        let competitors = vec![
+            competitor(1, "w", "y9"),
+            competitor(2, "e", "y9"),
+            competitor(3, "s", "y10"),
+        ];
+        let events = events();
+        let submission = Submission {
+            events: &events,
+            entrants: &HashMap::new(),
+            competitors: &competitors,
+        };
+        let body = r#"{ "y9-boys-60m": {
+            "revision": 0,
+            "scores": { "w": "8.4" },
+            "competitors": { "w": "1", "e": 1, "s": 3, "t": 9 }
+        } }"#;
+        assert_eq!(
+            fields(validate(&config(), &submission, body)),
+            vec![
+                "y9-boys-60m.e.competitor",
+                "y9-boys-60m.s.competitor",
+                "y9-boys-60m.t.competitor"
+            ]
+        );
+
+        let body = r#"{ "y9-boys-60m": {
+            "revision": 0,
+            "scores": { "w": "8.4" },
+            "competitors": { "w": "1", "e": "" }
+        } }"#;
+        let updates = validate(&config(), &submission, body).unwrap();
+        assert_eq!(updates[0].entries, Some(vec![("w".to_string(), 1)]));
+        let body = r#"{ "y9-boys-60m": { "revision": 0, "scores": { "w": "8.4" } } }"#;
+        let updates = validate(&config(), &submission, body).unwrap();
+        assert_eq!(updates[0].entries, None);
+    }
+
+    #[test]
+    fn only_entrants_are_scored_in_rounds() {
+        let events = events();
+        let entrants = HashMap::from([(
+            "y9-boys-60m".to_string(),
+            vec!["w".to_string(), "e".to_string()],
+        )]);
+        let submission = Submission {
+            events: &events,
+            entrants: &entrants,
+            competitors: &[],
+        };
+        let body = r#"{ "y9-boys-60m": { "revision": 0, "scores": { "w": "8.4", "s": "8.1" } } }"#;
+        assert_eq!(
+            fields(validate(&config(), &submission, body)),
+            vec!["y9-boys-60m.s"]
+        );
+
+        let body = r#"{ "y9-boys-60m": { "revision": 0, "scores": { "w": "8.4" } } }"#;
+        let updates = validate(&config(), &submission, body).unwrap();
+        let scored: Vec<&str> = updates[0]
+            .scores
+            .iter()
+            .map(|(id, _)| id.as_str())
+            .collect();
+        assert_eq!(scored, vec!["w", "e"]);
+    }
+
+    #[test]
+    fn placings_skipped_by_a_tie_are_refused() {
+        let body = r#"{ "y9-boys-javelin": { "revision": 0, "scores": { "w": "20", "e": 20, "s": "15" } } }"#;
+        let errors = check(&config(), &events(), body).unwrap_err();
+        assert_eq!(
+            errors,
+            vec![FieldError {
+                field: "y9-boys-javelin.s".to_string(),
+                message: "there's no 2nd because e, w are tied for 1st".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn ties_rejected_by_the_tie_rule_are_reported() {
+        let mut config = config();
+        config.tie_rule = TieRule::Countback;
+        let body = r#"{ "y9-boys-60m": { "revision": 0, "scores": { "w": "8.4", "e": "8.4" } } }"#;
+        assert_eq!(fields(check(&config, &events(), body)), vec!["y9-boys-60m"]);
+    }
+}
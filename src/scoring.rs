@@ -0,0 +1,630 @@
+use std::cmp::{Ordering, Reverse};
+
+use serde_json::{Map, Value};
+
+use crate::configurator::parser::{Form, Measurement, Score, TieRule};
+
+/// A form's finishing position in an event and the score it earns
+#[derive(Debug, Clone, PartialEq)]
+pub struct Placing {
+    pub form_id: String,
+    /// 1-based finishing position, shared by tied forms
+    pub position: usize,
+    /// Whether another form shares this position
+    pub tied: bool,
+    /// The placing's score, with its value adjusted by the tie rule
+    pub score: Score,
+}
+
+impl Placing {
+    /// The placing as shown on results, e.g. "=1st" for a tie
+    pub fn label(&self) -> String {
+        if self.tied {
+            format!("={}", self.score.name)
+        } else {
+            self.score.name.clone()
+        }
+    }
+}
+
+/// Why forms couldn't be placed in an event
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlacingError {
+    /// The form whose score can't be given, or `None` if the problem is with the event
+    pub form_id: Option<String>,
+    pub message: String,
+}
+
+impl Measurement {
+    /// Whether a lower performance beats a higher one (e.g. times)
+    pub fn lower_is_better(&self) -> bool {
+        match self {
+            Measurement::Time => true,
+            Measurement::Distance | Measurement::Height => false,
+        }
+    }
+
+    /// Parse a performance as entered by a scorer. Times are seconds, optionally as
+    /// `m:ss.ss`; distances and heights are metres, optionally suffixed with `m`.
+    pub fn parse(&self, input: &str) -> Result<f64, String> {
+        let input = input.trim();
+        let value = match self {
+            Measurement::Time => match input.trim_end_matches('s').split_once(':') {
+                Some((minutes, seconds)) => {
+                    let minutes = minutes.parse::<u32>().map_err(|_| invalid(input))?;
+                    let seconds = seconds.parse::<f64>().map_err(|_| invalid(input))?;
+                    if !(0.0..60.0).contains(&seconds) {
+                        return Err(invalid(input));
+                    }
+                    minutes as f64 * 60.0 + seconds
+                }
+                None => input
+                    .trim_end_matches('s')
+                    .parse::<f64>()
+                    .map_err(|_| invalid(input))?,
+            },
+            Measurement::Distance | Measurement::Height => input
+                .trim_end_matches('m')
+                .parse::<f64>()
+                .map_err(|_| invalid(input))?,
+        };
+        if !value.is_finite() || value <= 0.0 {
+            return Err(invalid(input));
+        }
+        Ok(value)
+    }
+
+    /// A hint for scorers on how to enter a performance
+    pub fn hint(&self) -> &'static str {
+        match self {
+            Measurement::Time => "secs or m:ss",
+            Measurement::Distance | Measurement::Height => "metres",
+        }
+    }
+
+    /// Format a performance for display
+    pub fn format(&self, value: f64) -> String {
+        match self {
+            Measurement::Time if value >= 60.0 => {
+                let minutes = (value / 60.0).floor();
+                format!("{}:{:05.2}", minutes, value - minutes * 60.0)
+            }
+            Measurement::Time => format!("{value:.2}s"),
+            Measurement::Distance | Measurement::Height => format!("{value:.2}m"),
+        }
+    }
+}
+
+fn invalid(input: &str) -> String {
+    format!("\"{input}\" is not a valid performance")
+}
+
+/// The score given to forms that didn't place
+pub fn default_score(scores: &[Score]) -> Score {
+    scores
+        .iter()
+        .find(|score| score.default)
+        .cloned()
+        .unwrap_or(Score {
+            name: "Nothing".to_string(),
+            value: 0,
+            default: true,
+        })
+}
+
+/// The scores that can be earned by placing, best first
+pub fn placing_scores(scores: &[Score]) -> Vec<Score> {
+    let mut placing: Vec<Score> = scores
+        .iter()
+        .filter(|score| !score.default)
+        .cloned()
+        .collect();
+    placing.sort_by_key(|score| Reverse(score.value));
+    placing
+}
+
+/// Award the configured scores to groups of forms, best group first, where the
+/// forms in a group are tied. Positions are shared by ties and the following
+/// positions skipped (1st, =2nd, =2nd, 4th). Forms placed beyond the number of
+/// placing scores get the default score, so ties among them don't matter.
+pub fn award(
+    groups: Vec<Vec<String>>,
+    scores: &[Score],
+    tie_rule: &TieRule,
+) -> Result<Vec<Placing>, Vec<PlacingError>> {
+    let placing = placing_scores(scores);
+    let fallback = default_score(scores);
+    let score_at = |position: usize| {
+        placing
+            .get(position - 1)
+            .cloned()
+            .unwrap_or_else(|| fallback.clone())
+    };
+
+    let mut placings = vec![];
+    let mut position = 1;
+    for group in groups.into_iter().filter(|group| !group.is_empty()) {
+        let tied = group.len() > 1;
+        let mut score = score_at(position);
+        if tied && position <= placing.len() {
+            match tie_rule {
+                TieRule::Countback => {
+                    return Err(vec![PlacingError {
+                        form_id: None,
+                        message: format!(
+                            "{} are tied for {}, break the tie on countback",
+                            group.join(", "),
+                            score.name
+                        ),
+                    }])
+                }
+                TieRule::SharedAverage => {
+                    let total: i64 = (position..position + group.len())
+                        .map(|covered| score_at(covered).value)
+                        .sum();
+                    score.value = (total as f64 / group.len() as f64).round() as i64;
+                }
+                TieRule::SharedFull => {}
+            }
+        }
+        let size = group.len();
+        for form_id in group {
+            placings.push(Placing {
+                form_id,
+                position,
+                tied,
+                score: score.clone(),
+            });
+        }
+        position += size;
+    }
+    Ok(placings)
+}
+
+/// Rank forms by their performances and award the configured scores in order,
+/// treating equal performances as ties
+pub fn place(
+    measurement: &Measurement,
+    performances: &[(String, f64)],
+    scores: &[Score],
+    tie_rule: &TieRule,
+) -> Result<Vec<Placing>, Vec<PlacingError>> {
+    let mut ranked = performances.to_vec();
+    ranked.sort_by(|(_, a), (_, b)| {
+        let order = a.partial_cmp(b).unwrap_or(Ordering::Equal);
+        if measurement.lower_is_better() {
+            order
+        } else {
+            order.reverse()
+        }
+    });
+
+    let mut groups: Vec<(f64, Vec<String>)> = vec![];
+    for (form_id, value) in ranked {
+        match groups.last_mut() {
+            Some((group_value, group)) if (*group_value - value).abs() < 1e-9 => {
+                group.push(form_id)
+            }
+            _ => groups.push((value, vec![form_id])),
+        }
+    }
+    award(
+        groups.into_iter().map(|(_, group)| group).collect(),
+        scores,
+        tie_rule,
+    )
+}
+
+/// Rank forms by the placing score a scorer picked for each (by value) and award
+/// scores under the tie rule. Forms given the default score are left unplaced.
+/// Picking a placing that a tie has skipped (2nd after two forms share 1st), or one
+/// below a placing nobody was given (2nd with no 1st), is refused rather than
+/// quietly changed.
+pub fn place_by_score(
+    picked: &[(String, i64)],
+    scores: &[Score],
+    tie_rule: &TieRule,
+) -> Result<Vec<Placing>, Vec<PlacingError>> {
+    let placing = placing_scores(scores);
+    let mut groups: Vec<Vec<String>> = vec![vec![]; placing.len()];
+    for (form_id, value) in picked.iter() {
+        if let Some(index) = placing.iter().position(|score| score.value == *value) {
+            groups[index].push(form_id.clone());
+        }
+    }
+
+    let mut errors = vec![];
+    let mut position = 1;
+    let mut last_tie: Option<(&[String], &Score)> = None;
+    for (index, group) in groups.iter().enumerate() {
+        if group.is_empty() {
+            continue;
+        }
+        let message = match (position.cmp(&(index + 1)), last_tie) {
+            (Ordering::Greater, Some((tied, tied_score))) => Some(format!(
+                "there's no {} because {} are tied for {}",
+                placing[index].name,
+                tied.join(", "),
+                tied_score.name
+            )),
+            (Ordering::Less, _) => Some(format!(
+                "there's no {} because nobody is placed {}",
+                placing[index].name,
+                placing[position - 1].name
+            )),
+            _ => None,
+        };
+        if let Some(message) = message {
+            errors.extend(group.iter().map(|form_id| PlacingError {
+                form_id: Some(form_id.clone()),
+                message: message.clone(),
+            }));
+        }
+        if group.len() > 1 {
+            last_tie = Some((group, &placing[index]));
+        }
+        // Carry on from the placing picked, so one gap is only reported once
+        position = position.max(index + 1) + group.len();
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    award(groups, scores, tie_rule)
+}
+
+/// Recover placings from the points stored for an event, where equal points mean a tie.
+/// Forms on the default score are left unplaced.
+pub fn standings(points: &[(String, i64)], scores: &[Score]) -> Vec<Placing> {
+    let placing = placing_scores(scores);
+    let fallback = default_score(scores);
+    let mut ranked: Vec<(String, i64)> = points
+        .iter()
+        .filter(|(_, value)| *value != fallback.value)
+        .cloned()
+        .collect();
+    ranked.sort_by_key(|(_, value)| Reverse(*value));
+
+    let mut placings: Vec<Placing> = vec![];
+    for (i, (form_id, value)) in ranked.iter().enumerate() {
+        let position = match placings.last() {
+            Some(previous) if previous.score.value == *value => previous.position,
+            _ => i + 1,
+        };
+        let mut score = placing
+            .get(position - 1)
+            .cloned()
+            .unwrap_or_else(|| fallback.clone());
+        score.value = *value;
+        placings.push(Placing {
+            form_id: form_id.clone(),
+            position,
+            tied: ranked.iter().filter(|(_, other)| other == value).count() > 1,
+            score,
+        });
+    }
+    placings
+}
+
+/// The forms that go through from a heat: those placed within the first `qualifiers`
+/// positions, including every form tied at the cut-off
+pub fn qualifiers(points: &[(String, i64)], scores: &[Score], qualifiers: i64) -> Vec<String> {
+    standings(points, scores)
+        .into_iter()
+        .filter(|placing| placing.position as i64 <= qualifiers)
+        .map(|placing| placing.form_id)
+        .collect()
+}
+
+/// Whoever has the most points, with everyone tied for it. Nobody wins on no points.
+pub fn top_scorers<T: Clone>(totals: &[(T, i64)]) -> Vec<T> {
+    let Some(most) = totals.iter().map(|(_, points)| *points).max() else {
+        return vec![];
+    };
+    if most <= 0 {
+        return vec![];
+    }
+    totals
+        .iter()
+        .filter(|(_, points)| *points == most)
+        .map(|(id, _)| id.clone())
+        .collect()
+}
+
+/// Read the points in a legacy `events.scores` map, skipping anything that isn't a number
+pub fn parse_points(scores: &str) -> Vec<(String, i64)> {
+    serde_json::from_str::<Map<String, Value>>(scores)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|(form_id, value)| {
+            let points = match value {
+                Value::String(value) => value.parse::<i64>().ok(),
+                value => value.as_i64(),
+            }?;
+            Some((form_id, points))
+        })
+        .collect()
+}
+
+/// The score each form earns from a set of placings, giving unplaced forms the default score
+pub fn scores_from_placings(
+    forms: &[Form],
+    placings: &[Placing],
+    scores: &[Score],
+) -> Vec<(String, Score)> {
+    let fallback = default_score(scores);
+    forms
+        .iter()
+        .map(|form| {
+            let score = placings
+                .iter()
+                .find(|placing| placing.form_id == form.id)
+                .map(|placing| &placing.score)
+                .unwrap_or(&fallback);
+            (form.id.clone(), score.clone())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scores() -> Vec<Score> {
+        [("1st", 20, false), ("2nd", 15, false), ("Nothing", 0, true)]
+            .iter()
+            .map(|(name, value, default)| Score {
+                name: name.to_string(),
+                value: *value,
+                default: *default,
+            })
+            .collect()
+    }
+
+    fn performances(values: &[(&str, f64)]) -> Vec<(String, f64)> {
+        values
+            .iter()
+            .map(|(form_id, value)| (form_id.to_string(), *value))
+            .collect()
+    }
+
+    #[test]
+    fn parses_times() {
+        assert_eq!(Measurement::Time.parse("8.43"), Ok(8.43));
+        assert_eq!(Measurement::Time.parse("8.43s"), Ok(8.43));
+        assert_eq!(Measurement::Time.parse("1:02.5"), Ok(62.5));
+        assert!(Measurement::Time.parse("1:72").is_err());
+        assert!(Measurement::Time.parse("fast").is_err());
+        assert!(Measurement::Time.parse("-3").is_err());
+    }
+
+    #[test]
+    fn parses_distances() {
+        assert_eq!(Measurement::Distance.parse("34.5"), Ok(34.5));
+        assert_eq!(Measurement::Height.parse("1.45m"), Ok(1.45));
+        assert!(Measurement::Distance.parse("").is_err());
+    }
+
+    #[test]
+    fn formats_performances() {
+        assert_eq!(Measurement::Time.format(8.4), "8.40s");
+        assert_eq!(Measurement::Time.format(62.5), "1:02.50");
+        assert_eq!(Measurement::Distance.format(34.5), "34.50m");
+    }
+
+    #[test]
+    fn places_times_ascending() {
+        let placings = place(
+            &Measurement::Time,
+            &performances(&[("w", 9.1), ("e", 8.7), ("s", 9.5)]),
+            &scores(),
+            &TieRule::SharedFull,
+        )
+        .unwrap();
+        let order: Vec<(&str, i64)> = placings
+            .iter()
+            .map(|p| (p.form_id.as_str(), p.score.value))
+            .collect();
+        assert_eq!(order, vec![("e", 20), ("w", 15), ("s", 0)]);
+        assert_eq!(placings[2].position, 3);
+    }
+
+    #[test]
+    fn unplaced_forms_get_default_score() {
+        let forms: Vec<Form> = ["w", "e"]
+            .iter()
+            .map(|id| Form {
+                id: id.to_string(),
+                name: id.to_string(),
+                colour: "red".to_string(),
+            })
+            .collect();
+        let placings = place(
+            &Measurement::Time,
+            &performances(&[("e", 8.7)]),
+            &scores(),
+            &TieRule::SharedFull,
+        )
+        .unwrap();
+        assert_eq!(
+            scores_from_placings(&forms, &placings, &scores())
+                .into_iter()
+                .map(|(form_id, score)| (form_id, score.value))
+                .collect::<Vec<_>>(),
+            vec![("w".to_string(), 0), ("e".to_string(), 20)]
+        );
+    }
+
+    #[test]
+    fn places_distances_descending() {
+        let placings = place(
+            &Measurement::Distance,
+            &performances(&[("w", 20.0), ("e", 31.5)]),
+            &scores(),
+            &TieRule::SharedFull,
+        )
+        .unwrap();
+        assert_eq!(placings[0].form_id, "e");
+        assert_eq!(placings[0].score.name, "1st");
+    }
+
+    fn summary(placings: &[Placing]) -> Vec<(String, String, i64)> {
+        placings
+            .iter()
+            .map(|p| (p.form_id.clone(), p.label(), p.score.value))
+            .collect()
+    }
+
+    fn row(form_id: &str, label: &str, value: i64) -> (String, String, i64) {
+        (form_id.to_string(), label.to_string(), value)
+    }
+
+    #[test]
+    fn shared_full_ties_keep_full_points() {
+        let placings = place(
+            &Measurement::Time,
+            &performances(&[("w", 8.7), ("e", 8.7), ("s", 9.5)]),
+            &scores(),
+            &TieRule::SharedFull,
+        )
+        .unwrap();
+        assert_eq!(
+            summary(&placings),
+            vec![
+                row("w", "=1st", 20),
+                row("e", "=1st", 20),
+                row("s", "Nothing", 0)
+            ]
+        );
+        assert_eq!(placings[2].position, 3);
+    }
+
+    #[test]
+    fn shared_average_ties_split_points() {
+        let placings = place_by_score(
+            &[("w".to_string(), 20), ("e".to_string(), 20)],
+            &scores(),
+            &TieRule::SharedAverage,
+        )
+        .unwrap();
+        assert_eq!(
+            summary(&placings),
+            vec![row("w", "=1st", 18), row("e", "=1st", 18)]
+        );
+    }
+
+    #[test]
+    fn countback_rejects_ties() {
+        assert!(place_by_score(
+            &[("w".to_string(), 20), ("e".to_string(), 20)],
+            &scores(),
+            &TieRule::Countback,
+        )
+        .is_err());
+        assert!(place_by_score(
+            &[("w".to_string(), 20), ("e".to_string(), 15)],
+            &scores(),
+            &TieRule::Countback,
+        )
+        .is_ok());
+        // Forms tied outside the scoring places all get the default score anyway
+        let placings = place(
+            &Measurement::Time,
+            &performances(&[("w", 8.1), ("e", 8.4), ("s", 9.5), ("t", 9.5)]),
+            &scores(),
+            &TieRule::Countback,
+        )
+        .unwrap();
+        assert_eq!(
+            placings.iter().map(|p| p.score.value).collect::<Vec<_>>(),
+            vec![20, 15, 0, 0]
+        );
+    }
+
+    #[test]
+    fn place_by_score_refuses_positions_skipped_by_ties() {
+        assert_eq!(
+            place_by_score(
+                &[
+                    ("w".to_string(), 20),
+                    ("e".to_string(), 20),
+                    ("s".to_string(), 15),
+                    ("t".to_string(), 0),
+                ],
+                &scores(),
+                &TieRule::SharedFull,
+            )
+            .unwrap_err(),
+            vec![PlacingError {
+                form_id: Some("s".to_string()),
+                message: "there's no 2nd because w, e are tied for 1st".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn place_by_score_refuses_positions_nobody_reached() {
+        let error = |form_id: &str, message: &str| PlacingError {
+            form_id: Some(form_id.to_string()),
+            message: message.to_string(),
+        };
+        assert_eq!(
+            place_by_score(&[("w".to_string(), 15)], &scores(), &TieRule::SharedFull).unwrap_err(),
+            vec![error("w", "there's no 2nd because nobody is placed 1st")]
+        );
+
+        let mut scores = scores();
+        scores.insert(
+            2,
+            Score {
+                name: "3rd".to_string(),
+                value: 10,
+                default: false,
+            },
+        );
+        assert_eq!(
+            place_by_score(
+                &[("w".to_string(), 20), ("e".to_string(), 10)],
+                &scores,
+                &TieRule::SharedFull,
+            )
+            .unwrap_err(),
+            vec![error("e", "there's no 3rd because nobody is placed 2nd")]
+        );
+    }
+
+    #[test]
+    fn standings_recover_ties_from_points() {
+        let placings = standings(
+            &[
+                ("w".to_string(), 18),
+                ("e".to_string(), 18),
+                ("s".to_string(), 0),
+            ],
+            &scores(),
+        );
+        assert_eq!(
+            summary(&placings),
+            vec![row("w", "=1st", 18), row("e", "=1st", 18)]
+        );
+    }
+
+    #[test]
+    fn qualifiers_include_ties_at_the_cut_off() {
+        let points = [
+            ("w".to_string(), 20),
+            ("e".to_string(), 15),
+            ("s".to_string(), 15),
+            ("t".to_string(), 0),
+        ];
+        assert_eq!(qualifiers(&points, &scores(), 1), vec!["w"]);
+        assert_eq!(qualifiers(&points, &scores(), 2), vec!["w", "e", "s"]);
+        assert_eq!(qualifiers(&points, &scores(), 4), vec!["w", "e", "s"]);
+    }
+
+    #[test]
+    fn top_scorers_share_the_most_points() {
+        assert_eq!(top_scorers(&[(1, 30), (2, 45), (3, 45)]), vec![2, 3]);
+        assert_eq!(top_scorers(&[(1, 0), (2, 0)]), Vec::<i32>::new());
+        assert_eq!(top_scorers::<i32>(&[]), Vec::<i32>::new());
+    }
+}
@@ -4,10 +4,26 @@ use std::collections::HashMap;
 use crate::{
     configurator::{
         self,
-        parser::{Form, Score},
+        parser::{Form, Measurement, Score, ScoringScheme},
     },
-    db::{events::Events, users::Users, years::Years},
-    routes::results::ResultsEvent,
+    db::{
+        api_tokens::ApiTokens,
+        archived_events::ArchivedEvents,
+        competitors::Competitors,
+        config_versions::ConfigVersions,
+        events::{EventStatus, Events},
+        records::Records,
+        score_changes::ScoreChanges,
+        users::Users,
+        years::Years,
+    },
+    routes::{
+        api::docs::{DocsOperation, DocsSchema},
+        records::RecordsEntry,
+        results::ResultsEvent,
+    },
+    scoring,
+    utils::Award,
 };
 
 #[derive(Template)]
@@ -18,6 +34,7 @@ pub struct IndexTemplate {}
 #[template(path = "scoreboard.html")]
 pub struct ScoreboardTemplate {
     pub scores: String,
+    pub now_next: String,
 }
 
 #[derive(Template)]
@@ -29,17 +46,222 @@ pub struct ScoreboardPartialTemplate {
     pub year_totals: HashMap<String, i64>,
     pub form_totals: HashMap<String, i64>,
     pub grand_total: i64,
+    /// Event counts for the progress bar
+    pub total_events: usize,
+    pub final_events: usize,
+    pub provisional_events: usize,
+    pub awards: Vec<Award>,
+}
+
+impl ScoreboardPartialTemplate {
+    pub fn award_title(&self, award: &Award) -> String {
+        award_title(&self.years, award)
+    }
+}
+
+#[derive(Template)]
+#[template(path = "awards.html")]
+pub struct AwardsTemplate {
+    pub awards: Vec<Award>,
+    pub years: Vec<Years>,
+    pub forms: Vec<Form>,
+}
+
+impl AwardsTemplate {
+    pub fn award_title(&self, award: &Award) -> String {
+        award_title(&self.years, award)
+    }
+
+    pub fn form_name(&self, form_id: &str) -> String {
+        self.forms
+            .iter()
+            .find(|form| form.id == form_id)
+            .map(|form| form.name.clone())
+            .unwrap_or_else(|| form_id.to_string())
+    }
+}
+
+/// e.g. "Victor ludorum (girls)" or "Year 9 top athlete (girls)"
+fn award_title(years: &[Years], award: &Award) -> String {
+    match &award.year_id {
+        None => format!("Victor ludorum ({})", award.gender_id),
+        Some(year_id) => format!(
+            "{} top athlete ({})",
+            years
+                .iter()
+                .find(|year| &year.id == year_id)
+                .map(|year| year.name.as_str())
+                .unwrap_or(year_id),
+            award.gender_id
+        ),
+    }
+}
+
+#[derive(Template)]
+#[template(path = "partials/now_next.html")]
+pub struct NowNextPartialTemplate {
+    pub now: Vec<Events>,
+    pub next: Vec<Events>,
+    pub years: Vec<configurator::parser::Year>,
+}
+
+impl NowNextPartialTemplate {
+    pub fn year_name(&self, event: &Events) -> String {
+        year_name(&self.years, event)
+    }
+}
+
+/// The announcement of a new record, pushed to scoreboards
+#[derive(Template)]
+#[template(path = "partials/record.html")]
+pub struct RecordPartialTemplate {
+    pub record: Records,
+    pub event: String,
+    pub year: String,
+    /// The record's mark, formatted for its measurement
+    pub mark: String,
+}
+
+#[derive(Template)]
+#[template(path = "api_docs.html")]
+pub struct ApiDocsTemplate {
+    pub operations: Vec<DocsOperation>,
+    pub schemas: Vec<DocsSchema>,
+}
+
+#[derive(Template)]
+#[template(path = "records.html")]
+pub struct RecordsTemplate {
+    pub records: Vec<RecordsEntry>,
+}
+
+#[derive(Template)]
+#[template(path = "schedule.html")]
+pub struct ScheduleTemplate {
+    /// Events with a start time, in timetable order
+    pub events: Vec<Events>,
+    pub years: Vec<configurator::parser::Year>,
+    pub now_next: String,
+}
+
+impl ScheduleTemplate {
+    pub fn year_name(&self, event: &Events) -> String {
+        year_name(&self.years, event)
+    }
+}
+
+fn year_name(years: &[configurator::parser::Year], event: &Events) -> String {
+    years
+        .iter()
+        .find(|year| year.id == event.year_id)
+        .map(|year| year.name.clone())
+        .unwrap_or_else(|| event.year_id.clone())
 }
 
 #[derive(Template)]
 #[template(path = "set_scores.html")]
 pub struct SetScoresTemplate {
     pub events: Vec<Events>,
+    /// Points awarded so far, keyed by event id, as (form id, points)
+    pub points: HashMap<String, Vec<(String, i64)>>,
+    /// Measurement kind of each measured event, keyed by config event id
+    pub measurements: HashMap<String, Measurement>,
+    /// Formatted performances, keyed by event id then form id
+    pub performances: HashMap<String, HashMap<String, String>>,
+    pub competitors: Vec<Competitors>,
+    /// Id of the competitor who placed for each form, keyed by event id then form id
+    pub entries: HashMap<String, HashMap<String, i64>>,
+    /// The forms that can be scored in events run in rounds, from `utils::entrants`
+    pub entrants: HashMap<String, Vec<String>>,
     pub activity_types: Vec<configurator::parser::Event>,
     pub year_types: Vec<configurator::parser::Year>,
     pub group_types: Vec<String>,
     pub forms: Vec<Form>,
     pub scores: Vec<Score>,
+    pub scoring_schemes: Vec<ScoringScheme>,
+    /// Whether the scorer can lock and unlock final results
+    pub is_admin: bool,
+}
+
+impl SetScoresTemplate {
+    /// The scores offered for an event, from its scoring scheme if it has one
+    pub fn event_scores(&self, event: &Events) -> &[Score] {
+        event
+            .scoring
+            .as_ref()
+            .and_then(|id| self.scoring_schemes.iter().find(|scheme| &scheme.id == id))
+            .map(|scheme| scheme.scores.as_slice())
+            .unwrap_or(&self.scores)
+    }
+
+    /// The statuses the scorer can move an event to from this page
+    pub fn status_moves(&self, event: &Events) -> Vec<EventStatus> {
+        [event.status.previous(), event.status.next()]
+            .into_iter()
+            .flatten()
+            .filter(|to| self.is_admin || !event.status.needs_admin_to_become(*to))
+            .collect()
+    }
+
+    pub fn measurement(&self, event: &Events) -> Option<&Measurement> {
+        self.measurements.get(&event.filter_key)
+    }
+
+    /// The score option to pre-select for each form, as JSON keyed by form id.
+    /// Tied forms select their placing's option even when the tie rule changed the points.
+    pub fn selected(&self, event: &Events) -> String {
+        let scores = self.event_scores(event);
+        let placing = scoring::placing_scores(scores);
+        let fallback = scoring::default_score(scores);
+        let points = self.points.get(&event.id).cloned().unwrap_or_default();
+        let standings = scoring::standings(&points, scores);
+        let mut selected = serde_json::Map::new();
+        for form in self.forms.iter() {
+            let value = standings
+                .iter()
+                .find(|placing| placing.form_id == form.id)
+                .and_then(|standing| placing.get(standing.position - 1))
+                .unwrap_or(&fallback)
+                .value;
+            selected.insert(form.id.clone(), value.to_string().into());
+        }
+        serde_json::Value::Object(selected).to_string()
+    }
+
+    /// Whether a form runs in an event: every form does unless it's a heat or a final
+    pub fn runs_in(&self, event: &Events, form_id: &str) -> bool {
+        self.entrants
+            .get(&event.id)
+            .is_none_or(|forms| forms.iter().any(|id| id == form_id))
+    }
+
+    /// The competitors who can place for a form in an event
+    pub fn form_competitors(&self, event: &Events, form_id: &str) -> Vec<&Competitors> {
+        if !self.runs_in(event, form_id) {
+            return vec![];
+        }
+        self.competitors
+            .iter()
+            .filter(|competitor| {
+                competitor.form_id == form_id && competitor.year_id == event.year_id
+            })
+            .collect()
+    }
+
+    pub fn entry(&self, event_id: &str, form_id: &str) -> Option<i64> {
+        self.entries
+            .get(event_id)
+            .and_then(|entries| entries.get(form_id))
+            .copied()
+    }
+
+    pub fn performance(&self, event_id: &str, form_id: &str) -> String {
+        self.performances
+            .get(event_id)
+            .and_then(|performances| performances.get(form_id))
+            .cloned()
+            .unwrap_or_default()
+    }
 }
 
 #[derive(Template)]
@@ -53,6 +275,92 @@ pub struct ResultsTemplate {
 #[template(path = "admin/index.html")]
 pub struct AdminIndexTemplate {}
 
+#[derive(Template)]
+#[template(path = "admin/archived_events.html")]
+pub struct AdminArchivedEventsTemplate {
+    pub events: Vec<ArchivedEvents>,
+}
+
+#[derive(Template)]
+#[template(path = "admin/competitors.html")]
+pub struct AdminCompetitorsTemplate {
+    pub competitors: Vec<Competitors>,
+    /// The CSV that was submitted, kept so it can be fixed when it's rejected
+    pub csv: String,
+    pub errors: Vec<String>,
+}
+
+#[derive(Template)]
+#[template(path = "admin/api_tokens.html")]
+pub struct AdminApiTokensTemplate {
+    pub tokens: Vec<ApiTokens>,
+    /// A token that was just made, shown once
+    pub created: Option<String>,
+    pub errors: Vec<String>,
+}
+
+#[derive(Template)]
+#[template(path = "admin/records.html")]
+pub struct AdminRecordsTemplate {
+    /// The CSV that was submitted, kept so it can be fixed when it's rejected
+    pub csv: String,
+    pub errors: Vec<String>,
+}
+
+#[derive(Template)]
+#[template(path = "admin/score_import.html")]
+pub struct AdminScoreImportTemplate {
+    /// The CSV that was submitted, kept so it can be fixed or imported after the preview
+    pub csv: String,
+    /// `event id=revision` pairs the preview was made at
+    pub revisions: String,
+    pub errors: Vec<String>,
+    /// What importing the CSV would save, empty until it's been checked
+    pub preview: Vec<ImportedEvent>,
+}
+
+/// An event's scores as a score import would save them
+pub struct ImportedEvent {
+    pub name: String,
+    pub year_id: String,
+    pub gender_id: String,
+    /// (form name, score, the different score it replaces) for every form, including
+    /// ones given the default score
+    pub scores: Vec<(String, Score, Option<String>)>,
+}
+
+#[derive(Template)]
+#[template(path = "admin/events.html")]
+pub struct AdminEventsTemplate {
+    pub events: Vec<Events>,
+}
+
+#[derive(Template)]
+#[template(path = "admin/score_changes.html")]
+pub struct AdminScoreChangesTemplate {
+    pub changes: Vec<ScoreChanges>,
+    pub events: Vec<Events>,
+    pub users: Vec<Users>,
+    /// The current filter values, to keep them in the form
+    pub event: String,
+    pub user: String,
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Template)]
+#[template(path = "admin/config_versions/list.html")]
+pub struct AdminConfigVersionsListTemplate {
+    pub versions: Vec<ConfigVersions>,
+}
+
+#[derive(Template)]
+#[template(path = "admin/config_versions/show.html")]
+pub struct AdminConfigVersionsShowTemplate {
+    pub version: ConfigVersions,
+    pub plan: String,
+}
+
 #[derive(Template)]
 #[template(path = "admin/users/list.html")]
 pub struct AdminUsersListTemplate {
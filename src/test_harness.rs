@@ -2,7 +2,7 @@ use tokio::fs;
 
 use async_sqlite::{Pool, PoolBuilder};
 
-use crate::db;
+use crate::{configurator::parser::Configuration, db};
 
 pub async fn setup_db(db_name: &str) -> Pool {
     fs::remove_file(format!("./test/{db_name}.db").as_str())
@@ -16,3 +16,108 @@ pub async fn setup_db(db_name: &str) -> Pool {
     db::create_tables(&pool).await.unwrap();
     pool
 }
+
+/// The config tests start from: two years, both genders, four forms, a timed 60m and a
+/// placed javelin, scored 1st, 2nd or Nothing
+pub const CONFIG: &str = r#"
+version: test
+genders: ["boys", "girls"]
+years:
+  - id: y9
+    name: Year 9
+  - id: y10
+    name: Year 10
+forms:
+  - id: w
+    name: Winston
+    colour: lightgreen
+  - id: e
+    name: Ennis
+    colour: pink
+  - id: s
+    name: Sharman
+    colour: yellow
+  - id: t
+    name: Turing
+    colour: lightblue
+events:
+  - id: 60m
+    name: 60m
+    applicable_years:
+      type: all
+    applicable_genders:
+      type: all
+    measurement: time
+  - id: javelin
+    name: Javelin
+    applicable_years:
+      type: all
+    applicable_genders:
+      type: all
+scores:
+  - name: "1st"
+    value: 20
+    default: false
+  - name: "2nd"
+    value: 15
+    default: false
+  - name: "Nothing"
+    value: 0
+    default: true
+"#;
+
+pub fn config() -> Configuration {
+    ConfigBuilder::default().build()
+}
+
+/// The shared config cut down for a test, keeping only the ids given
+pub struct ConfigBuilder {
+    config: Configuration,
+}
+
+impl Default for ConfigBuilder {
+    fn default() -> Self {
+        Self {
+            config: Configuration::from_yaml_str(CONFIG).unwrap(),
+        }
+    }
+}
+
+impl ConfigBuilder {
+    pub fn genders(mut self, ids: &[&str]) -> Self {
+        self.config
+            .genders
+            .retain(|gender| ids.contains(&gender.as_str()));
+        self
+    }
+
+    pub fn years(mut self, ids: &[&str]) -> Self {
+        self.config
+            .years
+            .retain(|year| ids.contains(&year.id.as_str()));
+        self
+    }
+
+    pub fn forms(mut self, ids: &[&str]) -> Self {
+        self.config
+            .forms
+            .retain(|form| ids.contains(&form.id.as_str()));
+        self
+    }
+
+    pub fn events(mut self, ids: &[&str]) -> Self {
+        self.config
+            .events
+            .retain(|event| ids.contains(&event.id.as_str()));
+        self
+    }
+
+    pub fn build(self) -> Configuration {
+        self.config
+    }
+
+    /// The config written back out, for tests that need the YAML it came from
+    pub fn yaml(self) -> String {
+        serde_yml::to_string(&self.config).unwrap()
+    }
+}
@@ -1,32 +1,99 @@
 use std::collections::HashMap;
 
-use actix_web::web;
 use askama::Template;
+use async_sqlite::Pool;
+use serde::Serialize;
+use utoipa::ToSchema;
 
 use crate::{
-    db::{events::Events, years::Years},
-    templates::ScoreboardPartialTemplate,
-    AppState,
+    configurator::parser::{Configuration, Form},
+    db::{
+        competitors::Competitors,
+        event_entries::EventEntries,
+        event_scores::EventScores,
+        events::{EventStatus, Events},
+        performances::Performances,
+        records::Records,
+        years::Years,
+    },
+    scoring,
+    templates::{NowNextPartialTemplate, RecordPartialTemplate, ScoreboardPartialTemplate},
+    websocket::{ChannelsActor, Publish},
 };
 
-pub async fn render_scoreboard(state: web::Data<AppState>) -> String {
-    let forms: Vec<crate::configurator::parser::Form> = state.config.forms.clone();
-    let years = Years::all(&state.pool).await.unwrap();
-    let events = Events::all(&state.pool).await.unwrap();
+pub async fn render_scoreboard(pool: &Pool, config: &Configuration) -> String {
+    let forms: Vec<Form> = config.forms.clone();
+    let years = Years::all(pool).await.unwrap();
+    let all_events = Events::all(pool).await.unwrap();
+    let count = |status: EventStatus| {
+        all_events
+            .iter()
+            .filter(|event| event.status == status)
+            .count()
+    };
+    let total_events = all_events.len();
+    let final_events = count(EventStatus::Final);
+    let provisional_events = count(EventStatus::Provisional);
+    let events: HashMap<String, Events> = all_events
+        .into_iter()
+        .map(|event| (event.id.clone(), event))
+        .collect();
+
+    let event_scores = EventScores::all(pool).await.unwrap();
+    let awards = individual_awards(
+        config,
+        &events,
+        &event_scores,
+        &EventEntries::all(pool).await.unwrap(),
+        &Competitors::all(pool).await.unwrap(),
+    );
+
+    let standings = standings(&forms, &events, &event_scores);
+
+    ScoreboardPartialTemplate {
+        forms,
+        years,
+        scores: standings.scores,
+        year_totals: standings.year_totals,
+        form_totals: standings.form_totals,
+        grand_total: standings.grand_total,
+        total_events,
+        final_events,
+        provisional_events,
+        awards,
+    }
+    .render()
+    .expect("template should bee valid")
+}
+
+/// The points each form has in each year, and their totals, as on the scoreboard
+#[derive(Serialize, Debug, PartialEq, ToSchema)]
+pub struct Standings {
+    /// Year id to form id to points, with event multipliers applied
+    pub scores: HashMap<String, HashMap<String, i64>>,
+    /// Year id to the points of every form in it
+    pub year_totals: HashMap<String, i64>,
+    /// Form id to its points across every year
+    pub form_totals: HashMap<String, i64>,
+    pub grand_total: i64,
+}
 
+/// Total up the scores of the given events
+pub fn standings(
+    forms: &[Form],
+    events: &HashMap<String, Events>,
+    event_scores: &[EventScores],
+) -> Standings {
     let mut year_form_scores: HashMap<String, HashMap<String, i64>> = HashMap::new();
-    for event in events.iter() {
-        let year_id = event.year_id.clone();
-        if let Ok(scores_map) =
-            serde_json::from_str::<HashMap<String, String>>(event.scores.as_str())
-        {
-            let year_scores = year_form_scores.entry(year_id).or_insert_with(HashMap::new);
-            for (form_id, score_str) in scores_map {
-                if let Ok(score) = score_str.parse::<i64>() {
-                    *year_scores.entry(form_id).or_insert(0) += score;
-                }
-            }
-        }
+    for score in event_scores {
+        let Some(event) = events.get(&score.event_id) else {
+            continue;
+        };
+        *year_form_scores
+            .entry(event.year_id.clone())
+            .or_default()
+            .entry(score.form_id.clone())
+            .or_insert(0) += score.points * event.multiplier;
     }
 
     // Calculate year totals (sum of all forms for each year)
@@ -38,7 +105,7 @@ pub async fn render_scoreboard(state: web::Data<AppState>) -> String {
 
     // Calculate form totals (sum of all years for each form)
     let mut form_totals: HashMap<String, i64> = HashMap::new();
-    for form in &forms {
+    for form in forms {
         let mut total: i64 = 0;
         for form_scores in year_form_scores.values() {
             if let Some(score) = form_scores.get(&form.id) {
@@ -51,17 +118,359 @@ pub async fn render_scoreboard(state: web::Data<AppState>) -> String {
     // Calculate grand total
     let grand_total: i64 = form_totals.values().sum();
 
-    let html = ScoreboardPartialTemplate {
-        forms,
-        years,
+    Standings {
         scores: year_form_scores,
         year_totals,
         form_totals,
         grand_total,
     }
+}
+
+/// The standings from everything scored so far
+pub async fn load_standings(pool: &Pool, config: &Configuration) -> Standings {
+    let events = Events::all(pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|event| (event.id.clone(), event))
+        .collect();
+    standings(
+        &config.forms,
+        &events,
+        &EventScores::all(pool).await.unwrap(),
+    )
+}
+
+/// An individual award and whoever has won it so far
+#[derive(Serialize, Debug, PartialEq, ToSchema)]
+pub struct Award {
+    /// The year it's for, or `None` for the victor ludorum across every year
+    pub year_id: Option<String>,
+    pub gender_id: String,
+    /// The winners' points, with event multipliers applied
+    pub points: i64,
+    /// Everyone tied on the most points
+    pub winners: Vec<AwardWinner>,
+}
+
+#[derive(Serialize, Debug, PartialEq, Clone, ToSchema)]
+pub struct AwardWinner {
+    pub competitor_id: i64,
+    pub name: String,
+    pub form_id: String,
+    pub year_id: String,
+}
+
+/// The individual champions: the victor ludorum for each gender, then the top athlete
+/// for each year and gender, in config order. A competitor earns the points of each
+/// form they placed for. Awards nobody has points for yet are left out.
+pub fn individual_awards(
+    config: &Configuration,
+    events: &HashMap<String, Events>,
+    scores: &[EventScores],
+    entries: &[EventEntries],
+    competitors: &[Competitors],
+) -> Vec<Award> {
+    let mut points: HashMap<i64, i64> = HashMap::new();
+    for entry in entries.iter() {
+        let Some(event) = events.get(&entry.event_id) else {
+            continue;
+        };
+        let Some(score) = scores
+            .iter()
+            .find(|score| score.event_id == entry.event_id && score.form_id == entry.form_id)
+        else {
+            continue;
+        };
+        *points.entry(entry.competitor_id).or_insert(0) += score.points * event.multiplier;
+    }
+
+    let award = |year_id: Option<&String>, gender_id: &String| {
+        let totals: Vec<(&Competitors, i64)> = competitors
+            .iter()
+            .filter(|competitor| {
+                &competitor.gender_id == gender_id
+                    && year_id.is_none_or(|year_id| &competitor.year_id == year_id)
+            })
+            .filter_map(|competitor| Some((competitor, *points.get(&competitor.id?)?)))
+            .collect();
+        let winners: Vec<&Competitors> = scoring::top_scorers(&totals);
+        let first = winners.first()?;
+        Some(Award {
+            year_id: year_id.cloned(),
+            gender_id: gender_id.clone(),
+            points: first
+                .id
+                .and_then(|id| points.get(&id))
+                .copied()
+                .unwrap_or_default(),
+            winners: winners
+                .into_iter()
+                .map(|competitor| AwardWinner {
+                    competitor_id: competitor.id.unwrap_or_default(),
+                    name: competitor.name.clone(),
+                    form_id: competitor.form_id.clone(),
+                    year_id: competitor.year_id.clone(),
+                })
+                .collect(),
+        })
+    };
+
+    let mut awards: Vec<Award> = config
+        .genders
+        .iter()
+        .filter_map(|gender_id| award(None, gender_id))
+        .collect();
+    for year in config.years.iter() {
+        awards.extend(
+            config
+                .genders
+                .iter()
+                .filter_map(|gender_id| award(Some(&year.id), gender_id)),
+        );
+    }
+    awards
+}
+
+/// The individual awards from everything scored so far
+pub async fn load_awards(pool: &Pool, config: &Configuration) -> Vec<Award> {
+    let events = Events::all(pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|event| (event.id.clone(), event))
+        .collect();
+    individual_awards(
+        config,
+        &events,
+        &EventScores::all(pool).await.unwrap(),
+        &EventEntries::all(pool).await.unwrap(),
+        &Competitors::all(pool).await.unwrap(),
+    )
+}
+
+pub async fn render_now_next(pool: &Pool, config: &Configuration) -> String {
+    let (now, next) = Events::now_and_next(pool, None).await.unwrap();
+    NowNextPartialTemplate {
+        now,
+        next,
+        years: config.years.clone(),
+    }
     .render()
-    .expect("template should bee valid");
-    html
+    .expect("template should be valid")
+}
+
+/// The forms that can be scored in each event run in rounds, keyed by event id: the
+/// forms in a heat, or those that have qualified from the heats so far for a final.
+/// Other events are left out, since every form can be scored in them.
+pub fn entrants(
+    config: &Configuration,
+    events: &[Events],
+    scores: &[EventScores],
+) -> HashMap<String, Vec<String>> {
+    let mut entrants = HashMap::new();
+    for event in events.iter() {
+        let Some(rounds) = config
+            .event_for_filter_key(&event.filter_key)
+            .and_then(|config_event| config_event.rounds.as_ref())
+        else {
+            continue;
+        };
+        let forms = match &event.heat {
+            Some(heat) => config
+                .forms_in_heat(rounds, heat.number)
+                .into_iter()
+                .map(|form| form.id.clone())
+                .collect(),
+            None => {
+                let qualified: Vec<String> = events
+                    .iter()
+                    .filter(|heat| {
+                        heat.heat
+                            .as_ref()
+                            .is_some_and(|heat| heat.final_id == event.id)
+                    })
+                    .flat_map(|heat| {
+                        let points: Vec<(String, i64)> = scores
+                            .iter()
+                            .filter(|score| score.event_id == heat.id)
+                            .map(|score| (score.form_id.clone(), score.points))
+                            .collect();
+                        scoring::qualifiers(
+                            &points,
+                            config.scores_for(heat.scoring.as_deref()),
+                            rounds.qualifiers,
+                        )
+                    })
+                    .collect();
+                config
+                    .forms
+                    .iter()
+                    .filter(|form| qualified.contains(&form.id))
+                    .map(|form| form.id.clone())
+                    .collect()
+            }
+        };
+        entrants.insert(event.id.clone(), forms);
+    }
+    entrants
+}
+
+/// An event's revision and what its row on the set-scores page should show
+#[derive(Serialize, Debug, PartialEq, ToSchema)]
+pub struct CurrentScores {
+    pub event_id: String,
+    pub revision: i64,
+    pub status: EventStatus,
+    /// Form id to the value of its field: the formatted performance for measured
+    /// events, the points otherwise. Forms without a score are left out.
+    pub values: HashMap<String, String>,
+    /// Form id to the id of the competitor who placed for it, where one was picked
+    pub competitors: HashMap<String, i64>,
+}
+
+pub async fn current_scores(
+    pool: &Pool,
+    config: &Configuration,
+    event_ids: &[String],
+) -> Vec<CurrentScores> {
+    let events = Events::all(pool).await.unwrap();
+    let scores = EventScores::all(pool).await.unwrap();
+    let performances = Performances::all(pool).await.unwrap();
+    let entries = EventEntries::all(pool).await.unwrap();
+
+    events
+        .into_iter()
+        .filter(|event| event_ids.contains(&event.id))
+        .map(|event| {
+            let measurement = config
+                .event_for_filter_key(&event.filter_key)
+                .and_then(|event| event.measurement.as_ref());
+            let values = match measurement {
+                Some(measurement) => performances
+                    .iter()
+                    .filter(|performance| performance.event_id == event.id)
+                    .map(|performance| {
+                        (
+                            performance.form_id.clone(),
+                            measurement.format(performance.value),
+                        )
+                    })
+                    .collect(),
+                None => scores
+                    .iter()
+                    .filter(|score| score.event_id == event.id)
+                    .map(|score| (score.form_id.clone(), score.points.to_string()))
+                    .collect(),
+            };
+            let competitors = entries
+                .iter()
+                .filter(|entry| entry.event_id == event.id)
+                .map(|entry| (entry.form_id.clone(), entry.competitor_id))
+                .collect();
+            CurrentScores {
+                event_id: event.id,
+                revision: event.revision,
+                status: event.status,
+                values,
+                competitors,
+            }
+        })
+        .collect()
+}
+
+/// Push the scoreboard, and the current scores of the given events to set-scores
+/// pages, to every screen. Returns the current scores that were pushed.
+pub async fn publish_scores(
+    pool: &Pool,
+    config: &Configuration,
+    channels: &actix::Addr<ChannelsActor>,
+    event_ids: &[String],
+) -> Vec<CurrentScores> {
+    channels.do_send(Publish {
+        channel: "scores".to_string(),
+        payload: render_scoreboard(pool, config).await,
+    });
+    let current = current_scores(pool, config, event_ids).await;
+    if !current.is_empty() {
+        channels.do_send(Publish {
+            channel: "set_scores".to_string(),
+            payload: serde_json::to_string(&current).expect("scores should serialize"),
+        });
+    }
+    current
+}
+
+/// A record set by a score submission
+#[derive(Serialize, Debug, PartialEq, ToSchema)]
+pub struct NewRecord {
+    pub event_id: String,
+    pub form_id: String,
+    pub holder: String,
+    /// The mark, formatted for the event's measurement
+    pub mark: String,
+}
+
+/// Recheck the records for the given measured events after their scores change, and
+/// announce each new one on scoreboards. Records are set in the year of sports day,
+/// or the current year if the config has no date. Returns the new records.
+pub async fn check_records(
+    pool: &Pool,
+    config: &Configuration,
+    channels: &actix::Addr<ChannelsActor>,
+    event_ids: &[String],
+) -> Vec<NewRecord> {
+    let set_in = config
+        .date
+        .as_deref()
+        .and_then(|date| date.get(..4))
+        .map(str::to_string);
+    let mut new_records = vec![];
+    for event in Events::all(pool).await.unwrap() {
+        if !event_ids.contains(&event.id) {
+            continue;
+        }
+        let Some(config_event) = config.event_for_filter_key(&event.filter_key) else {
+            continue;
+        };
+        let Some(measurement) = config_event.measurement.clone() else {
+            continue;
+        };
+        let event_name = event.name.clone();
+        let year = config
+            .years
+            .iter()
+            .find(|year| year.id == event.year_id)
+            .map(|year| year.name.clone())
+            .unwrap_or_else(|| event.year_id.clone());
+        let Some(record) =
+            Records::update_for_event(pool, event, measurement.clone(), set_in.clone())
+                .await
+                .unwrap()
+        else {
+            continue;
+        };
+
+        let mark = measurement.format(record.value);
+        channels.do_send(Publish {
+            channel: "announcements".to_string(),
+            payload: RecordPartialTemplate {
+                record: record.clone(),
+                event: event_name,
+                year,
+                mark: mark.clone(),
+            }
+            .render()
+            .expect("template should be valid"),
+        });
+        new_records.push(NewRecord {
+            event_id: record.event_id.unwrap_or_default(),
+            form_id: record.form_id.unwrap_or_default(),
+            holder: record.holder,
+            mark,
+        });
+    }
+    new_records
 }
 
 #[macro_export]
@@ -81,7 +81,7 @@ impl Channels {
     pub fn broadcast(&self, channel: &str, msg: String) {
         if let Some(ch) = self.inner.get(channel) {
             for client in &ch.clients {
-                let _ = client.do_send(BroadcastMessage(msg.clone()));
+                client.do_send(BroadcastMessage(msg.clone()));
             }
         }
     }